@@ -9,9 +9,15 @@ let MODE_UPSAMPLE: u32 = 3u;
 
 let EPSILON: f32 = 1.0e-4;
 
+struct exposure_buffer {
+	luminance: f32;
+	exposure: f32;
+};
+
 struct bloom_param {
 	parameters: vec4<f32>; // (x) threshold, (y) threshold - knee, (z) knee * 2, (w) 0.25 / knee
 	combine_constant: f32;
+	intensity: f32; // only used by bloom_composite.wgsl
 };
 
 [[group(0), binding(0)]] var output_texture: texture_storage_2d<rgba16float, write>;
@@ -19,6 +25,7 @@ struct bloom_param {
 [[group(0), binding(2)]] var bloom_texture: texture_2d<f32>;
 [[group(0), binding(3)]] var samp: sampler;
 [[group(0), binding(4)]] var<uniform> param: bloom_param;
+[[group(0), binding(5)]] var<storage, read> exposure: exposure_buffer;
 
 struct PushConstants {
 	mode_lod: u32;
@@ -43,7 +50,8 @@ fn QuadraticThreshold(color: vec4<f32>, threshold: f32, curve: vec3<f32>) -> vec
 fn Prefilter(color: vec4<f32>, uv: vec2<f32>) -> vec4<f32>
 {
 	let clamp_value = 20.0;
-	var color: vec4<f32> = min(vec4<f32>(clamp_value), color);
+	// Threshold the exposed color so the bloom follows the exposure like the scene does
+	var color: vec4<f32> = min(vec4<f32>(clamp_value), color * exposure.exposure);
 	color = QuadraticThreshold(color, param.parameters.x, param.parameters.yzw);
 	return color;
 }
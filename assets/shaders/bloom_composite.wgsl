@@ -0,0 +1,50 @@
+// Vertex Shader
+
+struct Vertexinput {
+	[[location(0)]] position: vec3<f32>;
+	[[location(1)]] normal: vec3<f32>;
+	[[location(2)]] uv: vec2<f32>;
+};
+
+struct VertexOutput {
+	[[builtin(position)]] clip_position: vec4<f32>;
+	[[location(0)]] uv: vec2<f32>;
+};
+
+[[stage(vertex)]]
+fn vs_main(in: Vertexinput) -> VertexOutput {
+	var out: VertexOutput;
+	out.uv = in.uv;
+	out.clip_position = vec4<f32>(in.position, 1.0);
+	return out;
+}
+
+// Fragment Shader
+
+struct exposure_buffer {
+	luminance: f32;
+	exposure: f32;
+};
+
+struct bloom_param {
+	parameters: vec4<f32>; // (x) threshold, (y) threshold - knee, (z) knee * 2, (w) 0.25 / knee
+	combine_constant: f32;
+	intensity: f32;
+};
+
+[[group(0), binding(0)]] var scene: texture_2d<f32>;
+[[group(0), binding(1)]] var bloom: texture_2d<f32>;
+[[group(0), binding(2)]] var samp: sampler;
+[[group(0), binding(3)]] var<uniform> param: bloom_param;
+[[group(0), binding(4)]] var<storage, read> exposure: exposure_buffer;
+
+
+[[stage(fragment)]]
+fn fs_main(in: VertexOutput) -> [[location(0)]] vec4<f32> {
+	let input_color = textureSample(scene, samp, in.uv).rgb;
+	let bloom_color = textureSample(bloom, samp, in.uv).rgb;
+	// The bloom is computed from the exposed color, the output stays unexposed like the input
+	let bloom_light = bloom_color * param.intensity * param.combine_constant / max(exposure.exposure, 1.0e-4);
+
+	return vec4<f32>(input_color + bloom_light, 1.0);
+}
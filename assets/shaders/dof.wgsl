@@ -0,0 +1,110 @@
+// Compute Shader
+
+let MODE_FOCUS: u32 = 0u;
+let MODE_PREPARE: u32 = 1u;
+let MODE_BLUR: u32 = 2u;
+
+let BLUR_SAMPLE_COUNT: i32 = 48;
+let GOLDEN_ANGLE: f32 = 2.39996323;
+
+struct dof_param {
+	near: f32;
+	far: f32;
+	focus_distance: f32; // used when focus_point is negative
+	focal_length: f32; // in meters
+	aperture: f32; // f-number
+	pixels_per_meter: f32; // on the sensor
+	max_coc: f32; // in full resolution pixels
+	_padding: f32;
+	focus_point: vec2<i32>; // pixel the focus distance is read from
+};
+
+struct focus_buffer {
+	distance: f32;
+};
+
+[[group(0), binding(0)]] var input_texture: texture_2d<f32>;
+[[group(0), binding(1)]] var depth_texture: texture_depth_2d;
+[[group(0), binding(2)]] var output_texture: texture_storage_2d<rgba16float, write>;
+[[group(0), binding(3)]] var<uniform> param: dof_param;
+[[group(0), binding(4)]] var<storage, read_write> focus: focus_buffer;
+
+struct PushConstants {
+	mode: u32;
+};
+var<push_constant> pc: PushConstants;
+
+
+// Distance to the camera plane, for the 0 to 1 depth range of perspective_wgpu_dx
+fn linear_depth(coord: vec2<i32>) -> f32 {
+	let depth = textureLoad(depth_texture, coord, 0);
+	return param.near * param.far / (param.far - depth * (param.far - param.near));
+}
+
+// Signed radius of the circle of confusion of the thin lens model, in full resolution pixels,
+// negative in front of the focus plane
+fn coc(z: f32) -> f32 {
+	let f = param.focal_length;
+	let diameter = f * f / (param.aperture * max(focus.distance - f, 0.0001)) * (z - focus.distance) / z;
+	return clamp(0.5 * diameter * param.pixels_per_meter, -param.max_coc, param.max_coc);
+}
+
+
+[[stage(compute), workgroup_size(8, 8, 1)]]
+fn cs_main([[builtin(global_invocation_id)]] global_invocation_id: vec3<u32>)
+{
+	if (pc.mode == MODE_FOCUS) {
+		if (any(global_invocation_id.xy != vec2<u32>(0u))) {
+			return;
+		}
+		if (param.focus_point.x >= 0) {
+			let size = textureDimensions(depth_texture);
+			focus.distance = linear_depth(clamp(param.focus_point, vec2<i32>(0), size - 1));
+		} else {
+			focus.distance = param.focus_distance;
+		}
+		return;
+	}
+
+	let size = textureDimensions(output_texture);
+	let coord = vec2<i32>(global_invocation_id.xy);
+	if (coord.x >= size.x || coord.y >= size.y) {
+		return;
+	}
+
+	// Half resolution color with the average circle of confusion of the 4 pixels in alpha
+	if (pc.mode == MODE_PREPARE) {
+		let full_size = textureDimensions(depth_texture);
+		var color = vec3<f32>(0.0);
+		var radius = 0.0;
+		for (var i: i32 = 0; i < 4; i = i + 1) {
+			let full_coord = min(coord * 2 + vec2<i32>(i % 2, i / 2), full_size - 1);
+			color = color + textureLoad(input_texture, full_coord, 0).rgb;
+			radius = radius + coc(linear_depth(full_coord));
+		}
+		textureStore(output_texture, coord, vec4<f32>(color * 0.25, radius * 0.25));
+		return;
+	}
+
+	// Gather over a disk the size of the circle of confusion, a sample only contributes if its
+	// own circle reaches the center so sharp pixels do not bleed into their blurred neighbours
+	let center = textureLoad(input_texture, coord, 0);
+	let radius = abs(center.a) * 0.5;
+	if (radius < 0.5) {
+		textureStore(output_texture, coord, center);
+		return;
+	}
+
+	var sum = center.rgb;
+	var weight = 1.0;
+	for (var i: i32 = 0; i < BLUR_SAMPLE_COUNT; i = i + 1) {
+		let r = sqrt((f32(i) + 0.5) / f32(BLUR_SAMPLE_COUNT)) * radius;
+		let theta = f32(i) * GOLDEN_ANGLE;
+		let offset = vec2<i32>(round(vec2<f32>(cos(theta), sin(theta)) * r));
+		let s = textureLoad(input_texture, clamp(coord + offset, vec2<i32>(0), size - 1), 0);
+		let w = clamp(abs(s.a) * 0.5 - r + 1.0, 0.0, 1.0);
+		sum = sum + s.rgb * w;
+		weight = weight + w;
+	}
+	textureStore(output_texture, coord, vec4<f32>(sum / weight, center.a));
+}
@@ -0,0 +1,37 @@
+// Vertex Shader
+
+struct Vertexinput {
+	[[location(0)]] position: vec3<f32>;
+	[[location(1)]] normal: vec3<f32>;
+	[[location(2)]] uv: vec2<f32>;
+};
+
+struct VertexOutput {
+	[[builtin(position)]] clip_position: vec4<f32>;
+	[[location(0)]] uv: vec2<f32>;
+};
+
+[[stage(vertex)]]
+fn vs_main(in: Vertexinput) -> VertexOutput {
+	var out: VertexOutput;
+	out.uv = in.uv;
+	out.clip_position = vec4<f32>(in.position, 1.0);
+	return out;
+}
+
+// Fragment Shader
+
+[[group(0), binding(0)]] var input_texture: texture_2d<f32>;
+[[group(0), binding(1)]] var blur_texture: texture_2d<f32>; // half resolution, circle of confusion in alpha
+[[group(0), binding(2)]] var samp: sampler;
+
+
+[[stage(fragment)]]
+fn fs_main(in: VertexOutput) -> [[location(0)]] vec4<f32> {
+	let sharp = textureSample(input_texture, samp, in.uv);
+	let blurred = textureSample(blur_texture, samp, in.uv);
+
+	// Circles smaller than a pixel keep the full resolution image
+	let blend = smoothStep(0.5, 1.5, abs(blurred.a));
+	return vec4<f32>(mix(sharp.rgb, blurred.rgb, blend), sharp.a);
+}
@@ -0,0 +1,125 @@
+// Compute Shader
+
+let MODE_HISTOGRAM: u32 = 0u;
+let MODE_AVERAGE: u32 = 1u;
+
+let EXPOSURE_FIXED: u32 = 0u;
+let EXPOSURE_AUTO: u32 = 1u;
+let EXPOSURE_PHYSICAL: u32 = 2u;
+
+let HISTOGRAM_BIN_COUNT: u32 = 256u;
+
+let EPSILON: f32 = 1.0e-4;
+// Luminance the average of the scene is mapped to
+let MIDDLE_GREY: f32 = 0.18;
+
+struct exposure_param {
+	min_ev: f32; // log2 of the darkest luminance taken into account
+	max_ev: f32; // log2 of the brightest luminance taken into account
+	speed_up: f32; // adaptation speed when the scene gets brighter
+	speed_down: f32; // adaptation speed when the scene gets darker
+	compensation: f32; // in EV, applied on top of every mode
+	mode: u32;
+	camera_exposure: f32; // exposure computed from the camera aperture, shutter speed and ISO
+};
+
+struct histogram_buffer {
+	bins: array<atomic<u32>, 256>;
+};
+
+struct exposure_buffer {
+	luminance: f32; // adapted scene luminance
+	exposure: f32;
+};
+
+[[group(0), binding(0)]] var hdr_texture: texture_2d<f32>;
+[[group(0), binding(1)]] var<storage, read_write> histogram: histogram_buffer;
+[[group(0), binding(2)]] var<storage, read_write> exposure: exposure_buffer;
+[[group(0), binding(3)]] var<uniform> param: exposure_param;
+
+struct PushConstants {
+	mode: u32;
+	delta_time: f32;
+};
+var<push_constant> pc: PushConstants;
+
+var<workgroup> histogram_shared: array<atomic<u32>, 256>;
+var<workgroup> sum_shared: array<f32, 256>;
+
+
+// Bin 0 is reserved for black pixels, the others cover [min_ev, max_ev]
+fn luminance_bin(color: vec3<f32>) -> u32
+{
+	let luminance = dot(color, vec3<f32>(0.2126, 0.7152, 0.0722));
+	if (luminance < EPSILON) {
+		return 0u;
+	}
+	let t = clamp((log2(luminance) - param.min_ev) / (param.max_ev - param.min_ev), 0.0, 1.0);
+	return u32(t * f32(HISTOGRAM_BIN_COUNT - 2u) + 1.0);
+}
+
+fn build_histogram(global_invocation_id: vec3<u32>, local_index: u32)
+{
+	atomicStore(&histogram_shared[local_index], 0u);
+	workgroupBarrier();
+
+	let imgSize = textureDimensions(hdr_texture);
+	if (global_invocation_id.x < u32(imgSize.x) && global_invocation_id.y < u32(imgSize.y)) {
+		let color = textureLoad(hdr_texture, vec2<i32>(global_invocation_id.xy), 0).rgb;
+		atomicAdd(&histogram_shared[luminance_bin(color)], 1u);
+	}
+	workgroupBarrier();
+
+	atomicAdd(&histogram.bins[local_index], atomicLoad(&histogram_shared[local_index]));
+}
+
+// Dispatched as a single workgroup, one invocation per bin
+fn average_histogram(local_index: u32)
+{
+	// Reading the bin also clears it for the next frame
+	let count = atomicExchange(&histogram.bins[local_index], 0u);
+	sum_shared[local_index] = f32(count) * f32(local_index);
+	workgroupBarrier();
+
+	for (var cutoff: u32 = HISTOGRAM_BIN_COUNT >> 1u; cutoff > 0u; cutoff = cutoff >> 1u) {
+		if (local_index < cutoff) {
+			sum_shared[local_index] = sum_shared[local_index] + sum_shared[local_index + cutoff];
+		}
+		workgroupBarrier();
+	}
+
+	if (local_index == 0u) {
+		let imgSize = textureDimensions(hdr_texture);
+		// count is the number of black pixels here, they are left out of the average
+		let lit_pixels = max(f32(imgSize.x * imgSize.y) - f32(count), 1.0);
+		let average_bin = max(sum_shared[0] / lit_pixels - 1.0, 0.0);
+		let average_ev = average_bin / f32(HISTOGRAM_BIN_COUNT - 2u) * (param.max_ev - param.min_ev) + param.min_ev;
+		let target_luminance = exp2(average_ev);
+
+		var speed: f32 = param.speed_down;
+		if (target_luminance > exposure.luminance) {
+			speed = param.speed_up;
+		}
+		let adapted = exposure.luminance + (target_luminance - exposure.luminance) * (1.0 - exp(-pc.delta_time * speed));
+		exposure.luminance = adapted;
+
+		var base_exposure: f32 = 1.0;
+		if (param.mode == EXPOSURE_AUTO) {
+			base_exposure = MIDDLE_GREY / max(adapted, EPSILON);
+		} else if (param.mode == EXPOSURE_PHYSICAL) {
+			base_exposure = param.camera_exposure;
+		}
+		exposure.exposure = base_exposure * exp2(param.compensation);
+	}
+}
+
+
+[[stage(compute), workgroup_size(16, 16, 1)]]
+fn cs_main([[builtin(global_invocation_id)]] global_invocation_id: vec3<u32>, [[builtin(local_invocation_index)]] local_index: u32)
+{
+	if (pc.mode == MODE_HISTOGRAM) {
+		build_histogram(global_invocation_id, local_index);
+	} else if (pc.mode == MODE_AVERAGE) {
+		average_histogram(local_index);
+	}
+}
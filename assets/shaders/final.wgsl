@@ -23,64 +23,195 @@ fn vs_main(in: Vertexinput) -> VertexOutput {
 
 // Fragment Shader
 
-struct bloom_composite {
-	bloom_intensity: f32;
-	bloom_combine_constant: f32;
+// Must match output.rs
+let TRANSFER_NONE: u32 = 0u;
+let TRANSFER_SRGB: u32 = 1u;
+let TRANSFER_GAMMA22: u32 = 2u;
+let TRANSFER_PQ: u32 = 3u;
+
+// Must match effects.rs
+let EFFECT_VIGNETTE: u32 = 1u;
+let EFFECT_CHROMATIC_ABERRATION: u32 = 2u;
+let EFFECT_FILM_GRAIN: u32 = 4u;
+let EFFECT_DITHER: u32 = 8u;
+let CHROMATIC_ABERRATION_SAMPLES: i32 = 8;
+let GOLDEN_RATIO_CONJUGATE: f32 = 0.61803398875;
+
+struct final_param {
+	grading_domain_min: vec3<f32>;
+	grading_strength: f32;
+	grading_domain_max: vec3<f32>;
+	effects: u32;
+	vignette_intensity: f32;
+	vignette_smoothness: f32;
+	vignette_roundness: f32;
+	chromatic_aberration: f32;
+	grain_intensity: f32;
+	grain_response: f32; // how much the grain fades out in the highlights
+	grain_time: f32;
+	compare_split: f32; // x of the comparison divider, as a fraction of the width
+	compare: u32; // when not 0, compare_texture is shown left of the divider
+};
+
+// Result of the post processing stack
+[[group(0), binding(0)]] var input_texture: texture_2d<f32>;
+[[group(0), binding(1)]] var samp: sampler;
+[[group(0), binding(2)]] var<uniform> composite_parameter: final_param;
+[[group(0), binding(3)]] var grading_lut: texture_3d<f32>;
+[[group(0), binding(4)]] var spectral_lut: texture_2d<f32>;
+// Result of the stack without the bloom, for the split screen comparison
+[[group(0), binding(5)]] var compare_texture: texture_2d<f32>;
+// Tiled, generated at startup by effects.rs
+[[group(0), binding(6)]] var blue_noise: texture_2d<f32>;
+
+struct PushConstants {
+	transfer: u32;
+	scale: f32; // applied to the linear display color before the transfer
+	quantization: f32; // step of the surface format, 0 for float formats
+	frame: u32;
 };
+var<push_constant> output: PushConstants;
+
+
+fn effect_enabled(effect: u32) -> bool {
+	return (composite_parameter.effects & effect) != 0u;
+}
+
+fn scene_color(uv: vec2<f32>, compare_side: bool) -> vec3<f32> {
+	if (compare_side) {
+		return textureSampleLevel(compare_texture, samp, uv, 0.0).rgb;
+	}
+	return textureSampleLevel(input_texture, samp, uv, 0.0).rgb;
+}
 
-[[group(0), binding(0)]] var hdr_texture: texture_2d<f32>;
-[[group(0), binding(1)]] var bloom_texture: texture_2d<f32>;
-[[group(0), binding(2)]] var hdr_sampler: sampler;
-[[group(0), binding(3)]] var<uniform> composite_parameter: bloom_composite;
-
-
-// can be optimized into lut (compute can gen it)
-fn GTTonemap_point(x: f32) -> f32{
-	let m: f32 = 0.22; // linear section start
-	let a: f32 = 1.0;  // contrast
-	let c: f32 = 1.33; // black brightness
-	let P: f32 = 1.0;  // maximum brightness
-	let l: f32 = 0.4;  // linear section length
-	let l0: f32 = ((P-m)*l) / a; // 0.312
-	let S0: f32 = m + l0; // 0.532
-	let S1: f32 = m + a * l0; // 0.532
-	let C2: f32 = (a*P) / (P - S1); // 2.13675213675
-	let L: f32 = m + a * (x - m);
-	let T: f32 = m * pow(x/m, c);
-	let S: f32 = P - (P - S1) * exp(-C2*(x - S0)/P);
-	let w0: f32 = 1.0 - smoothStep(0.0, m, x);
-	var w2: f32 = 1.0;
-	if (x < m+l) {
-		w2 = 0.0;
+// Spreads the samples toward the center of the screen, each tinted by the spectral lut
+fn chromatic_aberration(uv: vec2<f32>, compare_side: bool) -> vec3<f32> {
+	let offset = (uv - 0.5) * dot(uv - 0.5, uv - 0.5) * composite_parameter.chromatic_aberration * 0.5;
+	var sum = vec3<f32>(0.0);
+	var weight_sum = vec3<f32>(0.0);
+	for (var i: i32 = 0; i < CHROMATIC_ABERRATION_SAMPLES; i = i + 1) {
+		let t = (f32(i) + 0.5) / f32(CHROMATIC_ABERRATION_SAMPLES);
+		let weight = textureSampleLevel(spectral_lut, samp, vec2<f32>(t, 0.5), 0.0).rgb;
+		sum = sum + scene_color(uv - offset * t, compare_side) * weight;
+		weight_sum = weight_sum + weight;
 	}
-	let w1: f32 = 1.0 - w0 - w2;
-	return f32(T * w0 + L * w1 + S * w2);
+	return sum / weight_sum;
 }
 
-// this costs about 0.2-0.3ms more than aces, as-is
-fn GTTonemap(x: vec3<f32>) -> vec3<f32>{
-	return vec3<f32>(GTTonemap_point(x.r), GTTonemap_point(x.g), GTTonemap_point(x.b));
+fn vignette(color: vec3<f32>, uv: vec2<f32>) -> vec3<f32> {
+	let dimensions = vec2<f32>(textureDimensions(input_texture));
+	var d = abs(uv - 0.5) * composite_parameter.vignette_intensity;
+	// A roundness of 1 gives a circle whatever the aspect ratio of the screen
+	d.x = d.x * mix(1.0, dimensions.x / dimensions.y, composite_parameter.vignette_roundness);
+	let factor = pow(clamp(1.0 - dot(d, d), 0.0, 1.0), composite_parameter.vignette_smoothness * 5.0 + 0.01);
+	return color * factor;
 }
 
-fn aces(x: vec3<f32>) -> vec3<f32> {
-	let a: f32 = 2.51;
-	let b: f32 = 0.03;
-	let c: f32 = 2.43;
-	let d: f32 = 0.59;
-	let e: f32 = 0.14;
-	return clamp((x * (a * x + b)) / (x * (c * x + d) + e), vec3<f32>(0.0), vec3<f32>(1.0));
+fn hash(p: vec3<f32>) -> f32 {
+	var q = fract(p * 0.1031);
+	q = q + dot(q, q.zyx + 31.32);
+	return fract((q.x + q.y) * q.z);
+}
+
+// Applied to display encoded colors, the grain is stronger in the shadows than in the highlights
+fn film_grain(color: vec3<f32>, position: vec2<f32>) -> vec3<f32> {
+	let noise = hash(vec3<f32>(position, fract(composite_parameter.grain_time) * 1000.0)) - 0.5;
+	let luminance = dot(color, vec3<f32>(0.2126, 0.7152, 0.0722));
+	let response = mix(1.0, 1.0 - sqrt(clamp(luminance, 0.0, 1.0)), composite_parameter.grain_response);
+	return max(color + color * noise * composite_parameter.grain_intensity * response, vec3<f32>(0.0));
+}
+
+fn srgb_encode(x: vec3<f32>) -> vec3<f32> {
+	return select(1.055 * pow(x, vec3<f32>(1.0 / 2.4)) - 0.055, x * 12.92, x <= vec3<f32>(0.0031308));
+}
+
+fn srgb_decode(x: vec3<f32>) -> vec3<f32> {
+	return select(pow((x + 0.055) / 1.055, vec3<f32>(2.4)), x / 12.92, x <= vec3<f32>(0.04045));
+}
+
+// SMPTE ST 2084, x is the luminance normalized to 10000 nits
+fn pq_encode(x: vec3<f32>) -> vec3<f32> {
+	let m1 = 0.1593017578125;
+	let m2 = 78.84375;
+	let c1 = 0.8359375;
+	let c2 = 18.8515625;
+	let c3 = 18.6875;
+	let y = pow(max(x, vec3<f32>(0.0)), vec3<f32>(m1));
+	return pow((c1 + c2 * y) / (1.0 + c3 * y), vec3<f32>(m2));
+}
+
+fn output_transfer(x: vec3<f32>) -> vec3<f32> {
+	let scaled = x * output.scale;
+	if (output.transfer == TRANSFER_SRGB) {
+		return srgb_encode(scaled);
+	} else if (output.transfer == TRANSFER_GAMMA22) {
+		return pow(scaled, vec3<f32>(1.0 / 2.2));
+	} else if (output.transfer == TRANSFER_PQ) {
+		return pq_encode(scaled);
+	}
+	return scaled;
+}
+
+// Triangular distributed noise in -1 to 1 from the blue noise texture. The noise is animated by
+// adding the golden ratio every frame, which keeps its spatial distribution (Wolfe, 2017)
+fn dither_noise(position: vec2<f32>) -> f32 {
+	let coord = vec2<i32>(position) % textureDimensions(blue_noise);
+	let frame = f32(output.frame % 4096u);
+	let n = fract(textureLoad(blue_noise, coord, 0).r + frame * GOLDEN_RATIO_CONJUGATE) * 2.0 - 1.0;
+	return sign(n) * (1.0 - sqrt(1.0 - abs(n)));
+}
+
+// Adds noise of a quantization step to the encoded color, in the space the surface quantizes
+fn dither(color: vec3<f32>, position: vec2<f32>) -> vec3<f32> {
+	let noise = dither_noise(position) * output.quantization;
+	if (output.transfer == TRANSFER_NONE) {
+		// Only sRGB surfaces are dithered without a transfer, they encode on write
+		return srgb_decode(max(srgb_encode(color) + noise, vec3<f32>(0.0)));
+	}
+	return max(color + noise, vec3<f32>(0.0));
+}
+
+// .cube LUTs are authored on display encoded colors
+fn grade(color: vec3<f32>) -> vec3<f32> {
+	let domain = composite_parameter.grading_domain_max - composite_parameter.grading_domain_min;
+	let lut_size = vec3<f32>(textureDimensions(grading_lut));
+	let normalized = clamp((color - composite_parameter.grading_domain_min) / domain, vec3<f32>(0.0), vec3<f32>(1.0));
+	let uvw = normalized * ((lut_size - 1.0) / lut_size) + 0.5 / lut_size;
+	let graded = textureSampleLevel(grading_lut, samp, uvw, 0.0).rgb;
+	return mix(color, graded, composite_parameter.grading_strength);
 }
 
 
 [[stage(fragment)]]
 fn fs_main(in: VertexOutput) -> [[location(0)]] vec4<f32> {
-	let bloom_color = textureSample(bloom_texture, hdr_sampler, in.uv);
-	let hdr_color = textureSample(hdr_texture, hdr_sampler, in.uv);
-	let combined_color = ((bloom_color * composite_parameter.bloom_intensity) * composite_parameter.bloom_combine_constant) + hdr_color;
+	// The side is picked per pixel so the effects sampling around it do not cross the divider
+	let compare = composite_parameter.compare != 0u;
+	let compare_side = compare && in.uv.x < composite_parameter.compare_split;
+
+	var color: vec3<f32>;
+	if (effect_enabled(EFFECT_CHROMATIC_ABERRATION)) {
+		color = chromatic_aberration(in.uv, compare_side);
+	} else {
+		color = scene_color(in.uv, compare_side);
+	}
+	if (effect_enabled(EFFECT_VIGNETTE)) {
+		color = vignette(color, in.uv);
+	}
 
-	let mapped_color = GTTonemap(combined_color.rgb);
-	// let mapped_color = aces(hdr_color.rgb);
-	let gamma_corrected_color = pow(mapped_color, vec3<f32>(1.0 / 2.2));
+	var display_color = grade(srgb_encode(color));
+	if (effect_enabled(EFFECT_FILM_GRAIN)) {
+		display_color = film_grain(display_color, in.clip_position.xy);
+	}
+	if (compare) {
+		let divider = composite_parameter.compare_split * f32(textureDimensions(input_texture).x);
+		if (abs(in.clip_position.x - divider) < 1.0) {
+			display_color = vec3<f32>(1.0);
+		}
+	}
 
-	return vec4<f32>(gamma_corrected_color, 1.0);
+	var encoded = output_transfer(srgb_decode(display_color));
+	if (effect_enabled(EFFECT_DITHER) && output.quantization > 0.0) {
+		encoded = dither(encoded, in.clip_position.xy);
+	}
+	return vec4<f32>(encoded, 1.0);
 }
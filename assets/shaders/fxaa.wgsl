@@ -0,0 +1,187 @@
+// Vertex Shader
+
+struct Vertexinput {
+	[[location(0)]] position: vec3<f32>;
+	[[location(1)]] normal: vec3<f32>;
+	[[location(2)]] uv: vec2<f32>;
+};
+
+struct VertexOutput {
+	[[builtin(position)]] clip_position: vec4<f32>;
+	[[location(0)]] uv: vec2<f32>;
+};
+
+[[stage(vertex)]]
+fn vs_main(in: Vertexinput) -> VertexOutput {
+	var out: VertexOutput;
+	out.uv = in.uv;
+	out.clip_position = vec4<f32>(in.position, 1.0);
+	return out;
+}
+
+// Fragment Shader
+
+// FXAA 3.11 quality settings
+let EDGE_THRESHOLD: f32 = 0.166;
+let EDGE_THRESHOLD_MIN: f32 = 0.0833;
+let SUBPIX: f32 = 0.75;
+let SEARCH_STEPS: i32 = 12;
+
+[[group(0), binding(0)]] var input_texture: texture_2d<f32>;
+[[group(0), binding(1)]] var samp: sampler;
+
+
+// The input is linear, the thresholds are tuned for gamma encoded luma
+fn luma(color: vec3<f32>) -> f32 {
+	return sqrt(dot(color, vec3<f32>(0.299, 0.587, 0.114)));
+}
+
+fn sample_luma(uv: vec2<f32>) -> f32 {
+	return luma(textureSampleLevel(input_texture, samp, uv, 0.0).rgb);
+}
+
+fn sample_luma_offset(uv: vec2<f32>, texel: vec2<f32>, offset: vec2<f32>) -> f32 {
+	return sample_luma(uv + offset * texel);
+}
+
+fn search_step(i: i32) -> f32 {
+	if (i < 5) {
+		return 1.0;
+	}
+	if (i == 5) {
+		return 1.5;
+	}
+	if (i < 10) {
+		return 2.0;
+	}
+	if (i == 10) {
+		return 4.0;
+	}
+	return 8.0;
+}
+
+
+[[stage(fragment)]]
+fn fs_main(in: VertexOutput) -> [[location(0)]] vec4<f32> {
+	let texel = 1.0 / vec2<f32>(textureDimensions(input_texture));
+	let color = textureSampleLevel(input_texture, samp, in.uv, 0.0);
+
+	let luma_m = luma(color.rgb);
+	let luma_n = sample_luma_offset(in.uv, texel, vec2<f32>(0.0, -1.0));
+	let luma_s = sample_luma_offset(in.uv, texel, vec2<f32>(0.0, 1.0));
+	let luma_e = sample_luma_offset(in.uv, texel, vec2<f32>(1.0, 0.0));
+	let luma_w = sample_luma_offset(in.uv, texel, vec2<f32>(-1.0, 0.0));
+
+	let luma_max = max(luma_m, max(max(luma_n, luma_s), max(luma_e, luma_w)));
+	let luma_min = min(luma_m, min(min(luma_n, luma_s), min(luma_e, luma_w)));
+	let luma_range = luma_max - luma_min;
+	if (luma_range < max(EDGE_THRESHOLD_MIN, luma_max * EDGE_THRESHOLD)) {
+		return color;
+	}
+
+	let luma_nw = sample_luma_offset(in.uv, texel, vec2<f32>(-1.0, -1.0));
+	let luma_ne = sample_luma_offset(in.uv, texel, vec2<f32>(1.0, -1.0));
+	let luma_sw = sample_luma_offset(in.uv, texel, vec2<f32>(-1.0, 1.0));
+	let luma_se = sample_luma_offset(in.uv, texel, vec2<f32>(1.0, 1.0));
+
+	// Sub-pixel blend factor from the low pass of the neighbourhood
+	let luma_ns = luma_n + luma_s;
+	let luma_we = luma_w + luma_e;
+	let luma_average = (2.0 * (luma_ns + luma_we) + luma_nw + luma_ne + luma_sw + luma_se) / 12.0;
+	let subpix_linear = clamp(abs(luma_average - luma_m) / luma_range, 0.0, 1.0);
+	let subpix = smoothStep(0.0, 1.0, subpix_linear);
+	let subpix_blend = subpix * subpix * SUBPIX;
+
+	let edge_horizontal =
+		abs(luma_nw + luma_ne - 2.0 * luma_n) +
+		2.0 * abs(luma_we - 2.0 * luma_m) +
+		abs(luma_sw + luma_se - 2.0 * luma_s);
+	let edge_vertical =
+		abs(luma_nw + luma_sw - 2.0 * luma_w) +
+		2.0 * abs(luma_ns - 2.0 * luma_m) +
+		abs(luma_ne + luma_se - 2.0 * luma_e);
+	let horizontal = edge_horizontal >= edge_vertical;
+
+	// Pick the side of the edge with the strongest gradient
+	var luma_negative = luma_w;
+	var luma_positive = luma_e;
+	var step_length = texel.x;
+	if (horizontal) {
+		luma_negative = luma_n;
+		luma_positive = luma_s;
+		step_length = texel.y;
+	}
+	let gradient_negative = abs(luma_negative - luma_m);
+	let gradient_positive = abs(luma_positive - luma_m);
+	var luma_edge = 0.5 * (luma_m + luma_positive);
+	if (gradient_negative >= gradient_positive) {
+		step_length = -step_length;
+		luma_edge = 0.5 * (luma_m + luma_negative);
+	}
+	let gradient_scaled = 0.25 * max(gradient_negative, gradient_positive);
+
+	// Walk along the edge in both directions until its end
+	var edge_uv = in.uv;
+	var edge_step = vec2<f32>(texel.x, 0.0);
+	if (horizontal) {
+		edge_uv.y = edge_uv.y + step_length * 0.5;
+	} else {
+		edge_uv.x = edge_uv.x + step_length * 0.5;
+		edge_step = vec2<f32>(0.0, texel.y);
+	}
+
+	var uv_negative = edge_uv - edge_step;
+	var uv_positive = edge_uv + edge_step;
+	var delta_negative = sample_luma(uv_negative) - luma_edge;
+	var delta_positive = sample_luma(uv_positive) - luma_edge;
+	var done_negative = abs(delta_negative) >= gradient_scaled;
+	var done_positive = abs(delta_positive) >= gradient_scaled;
+
+	for (var i: i32 = 1; i < SEARCH_STEPS; i = i + 1) {
+		if (done_negative && done_positive) {
+			break;
+		}
+		let step = edge_step * search_step(i);
+		if (!done_negative) {
+			uv_negative = uv_negative - step;
+			delta_negative = sample_luma(uv_negative) - luma_edge;
+			done_negative = abs(delta_negative) >= gradient_scaled;
+		}
+		if (!done_positive) {
+			uv_positive = uv_positive + step;
+			delta_positive = sample_luma(uv_positive) - luma_edge;
+			done_positive = abs(delta_positive) >= gradient_scaled;
+		}
+	}
+
+	var distance_negative = in.uv.x - uv_negative.x;
+	var distance_positive = uv_positive.x - in.uv.x;
+	if (!horizontal) {
+		distance_negative = in.uv.y - uv_negative.y;
+		distance_positive = uv_positive.y - in.uv.y;
+	}
+
+	// Only blend when the pixel is on the side of the edge that ends closest
+	let closest_negative = distance_negative < distance_positive;
+	let distance = min(distance_negative, distance_positive);
+	let edge_length = distance_negative + distance_positive;
+	let center_smaller = luma_m < luma_edge;
+	var good_span = (delta_positive < 0.0) != center_smaller;
+	if (closest_negative) {
+		good_span = (delta_negative < 0.0) != center_smaller;
+	}
+	var edge_blend = 0.0;
+	if (good_span) {
+		edge_blend = 0.5 - distance / edge_length;
+	}
+
+	let blend = max(edge_blend, subpix_blend);
+	var final_uv = in.uv;
+	if (horizontal) {
+		final_uv.y = final_uv.y + blend * step_length;
+	} else {
+		final_uv.x = final_uv.x + blend * step_length;
+	}
+
+	return textureSampleLevel(input_texture, samp, final_uv, 0.0);
+}
@@ -0,0 +1,61 @@
+// Compute Shader
+
+let PI: f32 = 3.14159265359;
+let SAMPLE_COUNT: u32 = 1024u;
+
+// Scale (r) and bias (g) of the fresnel reflectance at normal incidence, indexed by n dot v (u)
+// and the roughness (v)
+[[group(0), binding(0)]] var output_texture: texture_storage_2d<rgba16float, write>;
+
+
+fn hammersley(i: u32, n: u32) -> vec2<f32> {
+	return vec2<f32>(f32(i) / f32(n), f32(reverseBits(i)) * 2.3283064365386963e-10);
+}
+
+// Around n = (0, 0, 1)
+fn importance_sample_ggx(xi: vec2<f32>, a: f32) -> vec3<f32> {
+	let phi = 2.0 * PI * xi.x;
+	let cos_theta = sqrt((1.0 - xi.y) / (1.0 + (a * a - 1.0) * xi.y));
+	let sin_theta = sqrt(1.0 - cos_theta * cos_theta);
+	return vec3<f32>(cos(phi) * sin_theta, sin(phi) * sin_theta, cos_theta);
+}
+
+// The k of image based lighting is a / 2, not the (roughness + 1)^2 / 8 of analytic lights
+fn geometry_schlick_ggx(n_dot_x: f32, roughness: f32) -> f32 {
+	let k = roughness * roughness / 2.0;
+	return n_dot_x / (n_dot_x * (1.0 - k) + k);
+}
+
+
+[[stage(compute), workgroup_size(8, 8, 1)]]
+fn cs_main([[builtin(global_invocation_id)]] global_invocation_id: vec3<u32>)
+{
+	let size = textureDimensions(output_texture);
+	let coord = vec2<i32>(global_invocation_id.xy);
+	if (coord.x >= size.x || coord.y >= size.y) {
+		return;
+	}
+	let texel = (vec2<f32>(coord) + 0.5) / vec2<f32>(size);
+	let n_dot_v = texel.x;
+	let roughness = texel.y;
+	let a = roughness * roughness;
+	let v = vec3<f32>(sqrt(1.0 - n_dot_v * n_dot_v), 0.0, n_dot_v);
+
+	var scale = 0.0;
+	var bias = 0.0;
+	for (var i: u32 = 0u; i < SAMPLE_COUNT; i = i + 1u) {
+		let h = importance_sample_ggx(hammersley(i, SAMPLE_COUNT), a);
+		let l = normalize(2.0 * dot(v, h) * h - v);
+		let n_dot_l = max(l.z, 0.0);
+		let n_dot_h = max(h.z, 0.0);
+		let v_dot_h = max(dot(v, h), 0.0);
+		if (n_dot_l > 0.0) {
+			let g = geometry_schlick_ggx(n_dot_v, roughness) * geometry_schlick_ggx(n_dot_l, roughness);
+			let g_vis = g * v_dot_h / (n_dot_h * n_dot_v);
+			let fc = pow(1.0 - v_dot_h, 5.0);
+			scale = scale + (1.0 - fc) * g_vis;
+			bias = bias + fc * g_vis;
+		}
+	}
+	textureStore(output_texture, coord, vec4<f32>(scale, bias, 0.0, 1.0) / vec4<f32>(f32(SAMPLE_COUNT), f32(SAMPLE_COUNT), 1.0, 1.0));
+}
@@ -0,0 +1,54 @@
+// Compute Shader
+
+let PI: f32 = 3.14159265359;
+
+[[group(0), binding(0)]] var equirect_texture: texture_2d<f32>;
+[[group(0), binding(1)]] var samp: sampler;
+// One mip of the environment cubemap, the faces are the array layers
+[[group(0), binding(2)]] var output_texture: texture_storage_2d_array<rgba16float, write>;
+
+struct PushConstants {
+	samples: u32; // per side of an output texel, averages the equirect texels it covers
+};
+var<push_constant> pc: PushConstants;
+
+
+// Direction through a point of a cubemap face, uv in -1 to 1 with v going down
+fn cube_direction(face: i32, uv: vec2<f32>) -> vec3<f32> {
+	switch (face) {
+		case 0: { return normalize(vec3<f32>(1.0, -uv.y, -uv.x)); }
+		case 1: { return normalize(vec3<f32>(-1.0, -uv.y, uv.x)); }
+		case 2: { return normalize(vec3<f32>(uv.x, 1.0, uv.y)); }
+		case 3: { return normalize(vec3<f32>(uv.x, -1.0, -uv.y)); }
+		case 4: { return normalize(vec3<f32>(uv.x, -uv.y, 1.0)); }
+		default: { return normalize(vec3<f32>(-uv.x, -uv.y, -1.0)); }
+	}
+}
+
+fn equirect_uv(dir: vec3<f32>) -> vec2<f32> {
+	return vec2<f32>(atan2(dir.z, dir.x) / (2.0 * PI) + 0.5, acos(clamp(dir.y, -1.0, 1.0)) / PI);
+}
+
+
+[[stage(compute), workgroup_size(8, 8, 1)]]
+fn cs_main([[builtin(global_invocation_id)]] global_invocation_id: vec3<u32>)
+{
+	let size = textureDimensions(output_texture);
+	let coord = vec2<i32>(global_invocation_id.xy);
+	if (coord.x >= size.x || coord.y >= size.y) {
+		return;
+	}
+	let face = i32(global_invocation_id.z);
+
+	var color = vec3<f32>(0.0);
+	let samples = max(pc.samples, 1u);
+	for (var y: u32 = 0u; y < samples; y = y + 1u) {
+		for (var x: u32 = 0u; x < samples; x = x + 1u) {
+			let offset = (vec2<f32>(f32(x), f32(y)) + 0.5) / f32(samples);
+			let uv = (vec2<f32>(coord) + offset) / vec2<f32>(size) * 2.0 - 1.0;
+			let dir = cube_direction(face, uv);
+			color = color + textureSampleLevel(equirect_texture, samp, equirect_uv(dir), 0.0).rgb;
+		}
+	}
+	textureStore(output_texture, coord, face, vec4<f32>(color / f32(samples * samples), 1.0));
+}
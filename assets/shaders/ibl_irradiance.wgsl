@@ -0,0 +1,70 @@
+// Compute Shader
+
+let PI: f32 = 3.14159265359;
+let SAMPLE_COUNT: u32 = 512u;
+
+[[group(0), binding(0)]] var environment: texture_cube<f32>;
+[[group(0), binding(1)]] var samp: sampler;
+[[group(0), binding(2)]] var output_texture: texture_storage_2d_array<rgba16float, write>;
+
+
+// Direction through a point of a cubemap face, uv in -1 to 1 with v going down
+fn cube_direction(face: i32, uv: vec2<f32>) -> vec3<f32> {
+	switch (face) {
+		case 0: { return normalize(vec3<f32>(1.0, -uv.y, -uv.x)); }
+		case 1: { return normalize(vec3<f32>(-1.0, -uv.y, uv.x)); }
+		case 2: { return normalize(vec3<f32>(uv.x, 1.0, uv.y)); }
+		case 3: { return normalize(vec3<f32>(uv.x, -1.0, -uv.y)); }
+		case 4: { return normalize(vec3<f32>(uv.x, -uv.y, 1.0)); }
+		default: { return normalize(vec3<f32>(-uv.x, -uv.y, -1.0)); }
+	}
+}
+
+fn hammersley(i: u32, n: u32) -> vec2<f32> {
+	return vec2<f32>(f32(i) / f32(n), f32(reverseBits(i)) * 2.3283064365386963e-10);
+}
+
+fn tangent_to_world(v: vec3<f32>, n: vec3<f32>) -> vec3<f32> {
+	var up = vec3<f32>(1.0, 0.0, 0.0);
+	if (abs(n.z) < 0.999) {
+		up = vec3<f32>(0.0, 0.0, 1.0);
+	}
+	let tangent = normalize(cross(up, n));
+	let bitangent = cross(n, tangent);
+	return tangent * v.x + bitangent * v.y + n * v.z;
+}
+
+
+// Cosine weighted importance sampling of the hemisphere. Each sample reads the mip whose texels
+// cover the solid angle of the sample, which avoids the noise of small bright regions
+// (Colbert and Krivanek, GPU Gems 3 chapter 20)
+[[stage(compute), workgroup_size(8, 8, 1)]]
+fn cs_main([[builtin(global_invocation_id)]] global_invocation_id: vec3<u32>)
+{
+	let size = textureDimensions(output_texture);
+	let coord = vec2<i32>(global_invocation_id.xy);
+	if (coord.x >= size.x || coord.y >= size.y) {
+		return;
+	}
+	let face = i32(global_invocation_id.z);
+	let n = cube_direction(face, (vec2<f32>(coord) + 0.5) / vec2<f32>(size) * 2.0 - 1.0);
+
+	let environment_size = f32(textureDimensions(environment).x);
+	let texel_solid_angle = 4.0 * PI / (6.0 * environment_size * environment_size);
+
+	var irradiance = vec3<f32>(0.0);
+	for (var i: u32 = 0u; i < SAMPLE_COUNT; i = i + 1u) {
+		let xi = hammersley(i, SAMPLE_COUNT);
+		let phi = 2.0 * PI * xi.y;
+		let cos_theta = sqrt(1.0 - xi.x);
+		let sin_theta = sqrt(xi.x);
+		let l = tangent_to_world(vec3<f32>(cos(phi) * sin_theta, sin(phi) * sin_theta, cos_theta), n);
+
+		let pdf = max(cos_theta / PI, 0.0001);
+		let sample_solid_angle = 1.0 / (f32(SAMPLE_COUNT) * pdf);
+		let lod = max(0.5 * log2(sample_solid_angle / texel_solid_angle) + 1.0, 0.0);
+		irradiance = irradiance + textureSampleLevel(environment, samp, l, lod).rgb;
+	}
+	// The cosine of the integral cancels with the pdf, pi is divided by the lambert brdf
+	textureStore(output_texture, coord, face, vec4<f32>(irradiance / f32(SAMPLE_COUNT), 1.0));
+}
@@ -0,0 +1,99 @@
+// Compute Shader
+
+let PI: f32 = 3.14159265359;
+let SAMPLE_COUNT: u32 = 256u;
+
+[[group(0), binding(0)]] var environment: texture_cube<f32>;
+[[group(0), binding(1)]] var samp: sampler;
+// One mip of the prefiltered cubemap, the faces are the array layers
+[[group(0), binding(2)]] var output_texture: texture_storage_2d_array<rgba16float, write>;
+
+struct PushConstants {
+	roughness: f32;
+};
+var<push_constant> pc: PushConstants;
+
+
+// Direction through a point of a cubemap face, uv in -1 to 1 with v going down
+fn cube_direction(face: i32, uv: vec2<f32>) -> vec3<f32> {
+	switch (face) {
+		case 0: { return normalize(vec3<f32>(1.0, -uv.y, -uv.x)); }
+		case 1: { return normalize(vec3<f32>(-1.0, -uv.y, uv.x)); }
+		case 2: { return normalize(vec3<f32>(uv.x, 1.0, uv.y)); }
+		case 3: { return normalize(vec3<f32>(uv.x, -1.0, -uv.y)); }
+		case 4: { return normalize(vec3<f32>(uv.x, -uv.y, 1.0)); }
+		default: { return normalize(vec3<f32>(-uv.x, -uv.y, -1.0)); }
+	}
+}
+
+fn hammersley(i: u32, n: u32) -> vec2<f32> {
+	return vec2<f32>(f32(i) / f32(n), f32(reverseBits(i)) * 2.3283064365386963e-10);
+}
+
+fn tangent_to_world(v: vec3<f32>, n: vec3<f32>) -> vec3<f32> {
+	var up = vec3<f32>(1.0, 0.0, 0.0);
+	if (abs(n.z) < 0.999) {
+		up = vec3<f32>(0.0, 0.0, 1.0);
+	}
+	let tangent = normalize(cross(up, n));
+	let bitangent = cross(n, tangent);
+	return tangent * v.x + bitangent * v.y + n * v.z;
+}
+
+fn importance_sample_ggx(xi: vec2<f32>, n: vec3<f32>, a: f32) -> vec3<f32> {
+	let phi = 2.0 * PI * xi.x;
+	let cos_theta = sqrt((1.0 - xi.y) / (1.0 + (a * a - 1.0) * xi.y));
+	let sin_theta = sqrt(1.0 - cos_theta * cos_theta);
+	return tangent_to_world(vec3<f32>(cos(phi) * sin_theta, sin(phi) * sin_theta, cos_theta), n);
+}
+
+fn distribution_ggx(n_dot_h: f32, a: f32) -> f32 {
+	let a2 = a * a;
+	let d = n_dot_h * n_dot_h * (a2 - 1.0) + 1.0;
+	return a2 / (PI * d * d);
+}
+
+
+// GGX prefiltered radiance under the n = v = r assumption of the split sum approximation
+// (Karis, Real Shading in Unreal Engine 4), with the same filtered importance sampling as the
+// irradiance
+[[stage(compute), workgroup_size(8, 8, 1)]]
+fn cs_main([[builtin(global_invocation_id)]] global_invocation_id: vec3<u32>)
+{
+	let size = textureDimensions(output_texture);
+	let coord = vec2<i32>(global_invocation_id.xy);
+	if (coord.x >= size.x || coord.y >= size.y) {
+		return;
+	}
+	let face = i32(global_invocation_id.z);
+	let n = cube_direction(face, (vec2<f32>(coord) + 0.5) / vec2<f32>(size) * 2.0 - 1.0);
+
+	let environment_size = f32(textureDimensions(environment).x);
+	let texel_solid_angle = 4.0 * PI / (6.0 * environment_size * environment_size);
+
+	// A mirror reflects the environment, read at the mip of the resolution of the output
+	if (pc.roughness == 0.0) {
+		let lod = log2(environment_size / f32(size.x));
+		textureStore(output_texture, coord, face, vec4<f32>(textureSampleLevel(environment, samp, n, lod).rgb, 1.0));
+		return;
+	}
+
+	let a = pc.roughness * pc.roughness;
+	var color = vec3<f32>(0.0);
+	var weight = 0.0;
+	for (var i: u32 = 0u; i < SAMPLE_COUNT; i = i + 1u) {
+		let h = importance_sample_ggx(hammersley(i, SAMPLE_COUNT), n, a);
+		let l = normalize(2.0 * dot(n, h) * h - n);
+		let n_dot_l = dot(n, l);
+		if (n_dot_l > 0.0) {
+			// With n = v the pdf of l is D(h) / 4
+			let n_dot_h = max(dot(n, h), 0.0);
+			let pdf = max(distribution_ggx(n_dot_h, a) / 4.0, 0.0001);
+			let sample_solid_angle = 1.0 / (f32(SAMPLE_COUNT) * pdf);
+			let lod = max(0.5 * log2(sample_solid_angle / texel_solid_angle) + 1.0, 0.0);
+			color = color + textureSampleLevel(environment, samp, l, lod).rgb * n_dot_l;
+			weight = weight + n_dot_l;
+		}
+	}
+	textureStore(output_texture, coord, face, vec4<f32>(color / max(weight, 0.0001), 1.0));
+}
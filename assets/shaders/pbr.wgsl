@@ -4,6 +4,16 @@ struct Vertexinput {
 	[[location(0)]] position: vec3<f32>;
 	[[location(1)]] normal: vec3<f32>;
 	[[location(2)]] uv: vec2<f32>;
+	[[location(3)]] tangent: vec4<f32>;
+};
+
+// Must match Instance in renderer/mod.rs
+struct InstanceInput {
+	[[location(4)]] model_0: vec4<f32>;
+	[[location(5)]] model_1: vec4<f32>;
+	[[location(6)]] model_2: vec4<f32>;
+	[[location(7)]] model_3: vec4<f32>;
+	[[location(8)]] material: vec2<f32>; // roughness and metallic, negative to keep the ones of the param
 };
 
 struct VertexOutput {
@@ -11,24 +21,32 @@ struct VertexOutput {
 	[[location(0)]] normal: vec3<f32>;
 	[[location(1)]] uv: vec2<f32>;
 	[[location(2)]] world_pos: vec3<f32>;
+	[[location(3)]] tangent: vec4<f32>;
+	[[location(4)]] material: vec2<f32>;
 };
 
 struct Matrices {
 	vp: mat4x4<f32>;
 	model: mat4x4<f32>;
+	normal: mat4x4<f32>; // inverse transpose of the model
 };
 
 [[group(0), binding(0)]]
 var<uniform> matrix: Matrices;
 
 [[stage(vertex)]]
-fn vs_main(in: Vertexinput) -> VertexOutput {
+fn vs_main(in: Vertexinput, instance: InstanceInput) -> VertexOutput {
 	var out: VertexOutput;
-	let world_pos = matrix.model * vec4<f32>(in.position, 1.0);
+	let instance_model = mat4x4<f32>(instance.model_0, instance.model_1, instance.model_2, instance.model_3);
+	let model = matrix.model * instance_model;
+	let world_pos = model * vec4<f32>(in.position, 1.0);
 	let screen_pos = matrix.vp * world_pos;
 
 	out.uv = in.uv;
-	out.normal = (matrix.model * vec4<f32>(in.normal, 1.0)).xyz;
+	// The instance transform keeps the angles, it does not change the normal matrix
+	out.normal = (matrix.normal * instance_model * vec4<f32>(in.normal, 0.0)).xyz;
+	out.tangent = vec4<f32>((model * vec4<f32>(in.tangent.xyz, 0.0)).xyz, in.tangent.w);
+	out.material = instance.material;
 	out.world_pos = world_pos.xyz;
 	out.clip_position = screen_pos;
 	return out;
@@ -43,14 +61,81 @@ struct PbrParam {
 	roughness: f32;
 	emissive_color: vec3<f32>;
 	ao: f32;
-	light_position: vec3<f32>;
 	emissive_intensity: f32;
-	light_color: vec3<f32>;
+	environment_intensity: f32;
+	normal_scale: f32;
+	occlusion_strength: f32;
+};
+
+// Must match lights.rs
+let LIGHT_POINT: u32 = 0u;
+let LIGHT_DIRECTIONAL: u32 = 1u;
+let LIGHT_SPOT: u32 = 2u;
+
+struct Light {
+	position: vec3<f32>;
+	kind: u32;
+	direction: vec3<f32>; // the light points toward it
+	range: f32;
+	color: vec3<f32>;
+	spot_scale: f32;
+	spot_offset: f32;
+};
+
+struct Lights {
+	count: u32;
+	lights: array<Light, 16>;
 };
 
 [[group(0), binding(1)]]
 var<uniform> param: PbrParam;
 
+[[group(0), binding(2)]]
+var<storage, read> lights: Lights;
+
+// Image based lighting, precomputed by ibl.rs
+[[group(1), binding(0)]]
+var irradiance_map: texture_cube<f32>;
+[[group(1), binding(1)]]
+var prefiltered_map: texture_cube<f32>;
+[[group(1), binding(2)]]
+var brdf_lut: texture_2d<f32>;
+[[group(1), binding(3)]]
+var environment_sampler: sampler;
+
+// Shadow map of the first light, rendered by shadow.rs
+struct ShadowParam {
+	light_view_proj: mat4x4<f32>;
+	depth_bias: f32;
+	normal_bias: f32; // world units along the normal
+	texel_size: f32;
+	enabled: u32;
+};
+
+[[group(2), binding(0)]]
+var shadow_map: texture_depth_2d;
+[[group(2), binding(1)]]
+var shadow_sampler: sampler_comparison;
+[[group(2), binding(2)]]
+var<uniform> shadow: ShadowParam;
+
+// Material maps, multiplied by the factors of PbrParam, must match pbr_textures.rs
+[[group(3), binding(0)]]
+var albedo_map: texture_2d<f32>;
+[[group(3), binding(1)]]
+var normal_map: texture_2d<f32>;
+[[group(3), binding(2)]]
+var metallic_roughness_map: texture_2d<f32>;
+[[group(3), binding(3)]]
+var occlusion_map: texture_2d<f32>;
+[[group(3), binding(4)]]
+var emissive_map: texture_2d<f32>;
+[[group(3), binding(5)]]
+var material_sampler: sampler;
+
+// Mip of the roughest prefiltered radiance, must match ibl.rs
+let PREFILTERED_MAX_LOD: f32 = 4.0;
+
 let PI: f32 = 3.14159265359;
 
 fn DistributionGGX(N: vec3<f32>, H: vec3<f32>, roughness: f32) -> f32 {
@@ -89,46 +174,118 @@ fn fresnelSchlick(cosTheta: f32, F0: vec3<f32>) -> vec3<f32> {
 	return F0 + (1.0 - F0) * pow(clamp(1.0 - cosTheta, 0.0, 1.0), 5.0);
 }
 // ----------------------------------------------------------------------------
+// Rough surfaces reflect less of the environment at grazing angles
+fn fresnelSchlickRoughness(cosTheta: f32, F0: vec3<f32>, roughness: f32) -> vec3<f32> {
+	return F0 + (max(vec3<f32>(1.0 - roughness), F0) - F0) * pow(clamp(1.0 - cosTheta, 0.0, 1.0), 5.0);
+}
+// ----------------------------------------------------------------------------
+// Fraction of the light reaching the point, 3x3 percentage closer filtering
+fn shadowFactor(world_pos: vec3<f32>, N: vec3<f32>) -> f32 {
+	let light_pos = shadow.light_view_proj * vec4<f32>(world_pos + N * shadow.normal_bias, 1.0);
+	let ndc = light_pos.xyz / light_pos.w;
+	let uv = ndc.xy * vec2<f32>(0.5, -0.5) + 0.5;
+	// Outside of the shadow map, or behind the light
+	if (light_pos.w <= 0.0 || any(uv < vec2<f32>(0.0)) || any(uv > vec2<f32>(1.0)) || ndc.z > 1.0) {
+		return 1.0;
+	}
+
+	let depth = ndc.z - shadow.depth_bias;
+	var lit: f32 = 0.0;
+	for (var y: i32 = -1; y <= 1; y = y + 1) {
+		for (var x: i32 = -1; x <= 1; x = x + 1) {
+			let offset = vec2<f32>(f32(x), f32(y)) * shadow.texel_size;
+			lit = lit + textureSampleCompareLevel(shadow_map, shadow_sampler, uv + offset, depth);
+		}
+	}
+	return lit / 9.0;
+}
+// ----------------------------------------------------------------------------
 
 
 [[stage(fragment)]]
 fn fs_main(in: VertexOutput) -> [[location(0)]] vec4<f32> {
-	let N = normalize(in.normal);
+	let albedo = param.albedo * textureSample(albedo_map, material_sampler, in.uv).rgb;
+	let metallic_roughness = textureSample(metallic_roughness_map, material_sampler, in.uv);
+	let metallic = select(param.metallic, in.material.y, in.material.y >= 0.0) * metallic_roughness.b;
+	let roughness = select(param.roughness, in.material.x, in.material.x >= 0.0) * metallic_roughness.g;
+	let occlusion = textureSample(occlusion_map, material_sampler, in.uv).r;
+	let ao = param.ao * (1.0 + param.occlusion_strength * (occlusion - 1.0));
+	let emissive = param.emissive_color * textureSample(emissive_map, material_sampler, in.uv).rgb;
+
+	// Tangent space normal
+	var tangent_normal: vec3<f32> = textureSample(normal_map, material_sampler, in.uv).xyz * 2.0 - 1.0;
+	tangent_normal = vec3<f32>(tangent_normal.xy * param.normal_scale, tangent_normal.z);
+	let geometric_normal = normalize(in.normal);
+	let T = normalize(in.tangent.xyz - geometric_normal * dot(geometric_normal, in.tangent.xyz));
+	let B = cross(geometric_normal, T) * in.tangent.w;
+	let N = normalize(mat3x3<f32>(T, B, geometric_normal) * tangent_normal);
 	let V = normalize(param.cam_pos - in.world_pos);
 
 	var F0: vec3<f32> = vec3<f32>(0.04); 
-	F0 = mix(F0, param.albedo, param.metallic);
+	F0 = mix(F0, albedo, metallic);
 
 	// reflectance equation
 	var Lo: vec3<f32> = vec3<f32>(0.0);
-	// calculate per-light radiance
-	let L = normalize(param.light_position - in.world_pos);
-	let H = normalize(V + L);
-	let distance = length(param.light_position - in.world_pos);
-	let attenuation = 1.0 / (distance * distance);
-	let radiance = param.light_color * attenuation;
-
-	// cook-torrance brdf
-	let NDF = DistributionGGX(N, H, param.roughness);
-	let G = GeometrySmith(N, V, L, param.roughness);
-	let F = fresnelSchlick(max(dot(H, V), 0.0), F0);
-
-	let kS = F;
-	var kD: vec3<f32> = vec3<f32>(1.0) - kS;
-	kD = kD * (1.0 - param.metallic);
-
-	let numerator = NDF * G * F;
-	let denominator = 4.0 * max(dot(N, V), 0.0) * max(dot(N, L), 0.0) + 0.0001;
-	let specular = numerator / denominator;
-
-	// add to outgoing radiance Lo
-	let NdotL = max(dot(N, L), 0.0);
-	Lo = Lo + ((kD * param.albedo / PI + specular) * radiance * NdotL);
+	for (var i: u32 = 0u; i < lights.count; i = i + 1u) {
+		let light = lights.lights[i];
+
+		// calculate per-light radiance
+		var L: vec3<f32>;
+		var radiance: vec3<f32>;
+		if (light.kind == LIGHT_DIRECTIONAL) {
+			L = -light.direction;
+			radiance = light.color;
+		} else {
+			let to_light = light.position - in.world_pos;
+			let distance = length(to_light);
+			L = to_light / distance;
+			// inverse square falloff windowed to reach 0 at the range (Karis 2013)
+			let window = clamp(1.0 - pow(distance / light.range, 4.0), 0.0, 1.0);
+			var attenuation = window * window / (distance * distance);
+			if (light.kind == LIGHT_SPOT) {
+				let cone = clamp(dot(-L, light.direction) * light.spot_scale + light.spot_offset, 0.0, 1.0);
+				attenuation = attenuation * cone * cone;
+			}
+			radiance = light.color * attenuation;
+		}
+		if (i == 0u && shadow.enabled != 0u) {
+			radiance = radiance * shadowFactor(in.world_pos, geometric_normal);
+		}
+		let H = normalize(V + L);
+
+		// cook-torrance brdf
+		let NDF = DistributionGGX(N, H, roughness);
+		let G = GeometrySmith(N, V, L, roughness);
+		let F = fresnelSchlick(max(dot(H, V), 0.0), F0);
+
+		let kS = F;
+		var kD: vec3<f32> = vec3<f32>(1.0) - kS;
+		kD = kD * (1.0 - metallic);
+
+		let numerator = NDF * G * F;
+		let denominator = 4.0 * max(dot(N, V), 0.0) * max(dot(N, L), 0.0) + 0.0001;
+		let specular = numerator / denominator;
+
+		// add to outgoing radiance Lo
+		let NdotL = max(dot(N, L), 0.0);
+		Lo = Lo + ((kD * albedo / PI + specular) * radiance * NdotL);
+	}
+
+	// ambient lighting from the environment (split sum approximation)
+	let NdotV = max(dot(N, V), 0.0);
+	let F = fresnelSchlickRoughness(NdotV, F0, roughness);
+	let kD = (vec3<f32>(1.0) - F) * (1.0 - metallic);
+	let irradiance = textureSample(irradiance_map, environment_sampler, N).rgb;
+	let diffuse = irradiance * albedo;
 
+	let R = reflect(-V, N);
+	let prefiltered = textureSampleLevel(prefiltered_map, environment_sampler, R, roughness * PREFILTERED_MAX_LOD).rgb;
+	let brdf = textureSample(brdf_lut, environment_sampler, vec2<f32>(NdotV, roughness)).rg;
+	let specular = prefiltered * (F * brdf.x + brdf.y);
 
-	let ambient = vec3<f32>(0.03) * param.albedo * param.ao;
+	let ambient = (kD * diffuse + specular) * ao * param.environment_intensity;
 	let color = ambient + Lo;
 
 
-	return vec4<f32>((color + (param.emissive_color * param.emissive_intensity)).xyz, 1.0);
+	return vec4<f32>((color + (emissive * param.emissive_intensity)).xyz, 1.0);
 }
@@ -0,0 +1,38 @@
+// Vertex Shader
+
+struct Vertexinput {
+	[[location(0)]] position: vec3<f32>;
+	[[location(1)]] normal: vec3<f32>;
+	[[location(2)]] uv: vec2<f32>;
+};
+
+// Model matrix of the Instance in renderer/mod.rs
+struct InstanceInput {
+	[[location(4)]] model_0: vec4<f32>;
+	[[location(5)]] model_1: vec4<f32>;
+	[[location(6)]] model_2: vec4<f32>;
+	[[location(7)]] model_3: vec4<f32>;
+};
+
+struct shadow_param {
+	light_view_proj: mat4x4<f32>;
+	depth_bias: f32;
+	normal_bias: f32;
+	texel_size: f32;
+	enabled: u32;
+};
+
+[[group(0), binding(0)]] var<uniform> param: shadow_param;
+
+struct PushConstants {
+	model: mat4x4<f32>;
+};
+var<push_constant> pc: PushConstants;
+
+
+// Depth only, no fragment shader
+[[stage(vertex)]]
+fn vs_main(in: Vertexinput, instance: InstanceInput) -> [[builtin(position)]] vec4<f32> {
+	let instance_model = mat4x4<f32>(instance.model_0, instance.model_1, instance.model_2, instance.model_3);
+	return param.light_view_proj * pc.model * instance_model * vec4<f32>(in.position, 1.0);
+}
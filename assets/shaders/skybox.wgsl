@@ -0,0 +1,48 @@
+// Vertex Shader
+
+struct Vertexinput {
+	[[location(0)]] position: vec3<f32>;
+	[[location(1)]] normal: vec3<f32>;
+	[[location(2)]] uv: vec2<f32>;
+};
+
+struct VertexOutput {
+	[[builtin(position)]] clip_position: vec4<f32>;
+	[[location(0)]] ndc: vec2<f32>;
+};
+
+// On the far plane, only drawn where no geometry was
+[[stage(vertex)]]
+fn vs_main(in: Vertexinput) -> VertexOutput {
+	var out: VertexOutput;
+	out.ndc = in.position.xy;
+	out.clip_position = vec4<f32>(in.position.xy, 1.0, 1.0);
+	return out;
+}
+
+// Fragment Shader
+
+struct skybox_param {
+	inv_view_proj: mat4x4<f32>;
+	rotation: f32; // around the up axis, in radians
+	intensity: f32;
+};
+
+[[group(0), binding(0)]] var<uniform> param: skybox_param;
+[[group(0), binding(1)]] var environment: texture_cube<f32>;
+[[group(0), binding(2)]] var samp: sampler;
+
+
+[[stage(fragment)]]
+fn fs_main(in: VertexOutput) -> [[location(0)]] vec4<f32> {
+	let near = param.inv_view_proj * vec4<f32>(in.ndc, 0.0, 1.0);
+	let far = param.inv_view_proj * vec4<f32>(in.ndc, 1.0, 1.0);
+	let view_dir = far.xyz / far.w - near.xyz / near.w;
+
+	let c = cos(param.rotation);
+	let s = sin(param.rotation);
+	let dir = vec3<f32>(c * view_dir.x + s * view_dir.z, view_dir.y, -s * view_dir.x + c * view_dir.z);
+
+	let color = textureSample(environment, samp, dir).rgb * param.intensity;
+	return vec4<f32>(color, 1.0);
+}
@@ -0,0 +1,124 @@
+// Vertex Shader
+
+struct Vertexinput {
+	[[location(0)]] position: vec3<f32>;
+	[[location(1)]] normal: vec3<f32>;
+	[[location(2)]] uv: vec2<f32>;
+};
+
+struct VertexOutput {
+	[[builtin(position)]] clip_position: vec4<f32>;
+	[[location(0)]] uv: vec2<f32>;
+};
+
+[[stage(vertex)]]
+fn vs_main(in: Vertexinput) -> VertexOutput {
+	var out: VertexOutput;
+	out.uv = in.uv;
+	out.clip_position = vec4<f32>(in.position, 1.0);
+	return out;
+}
+
+// Fragment Shader
+
+struct taa_param {
+	inv_view_proj: mat4x4<f32>; // jittered, the one the depth was rendered with
+	view_proj: mat4x4<f32>;
+	prev_view_proj: mat4x4<f32>;
+	blend: f32; // weight of the current frame
+	reset: u32; // the history is discarded when not 0
+};
+
+[[group(0), binding(0)]] var input_texture: texture_2d<f32>;
+[[group(0), binding(1)]] var history_texture: texture_2d<f32>;
+[[group(0), binding(2)]] var depth_texture: texture_depth_2d;
+[[group(0), binding(3)]] var samp: sampler;
+[[group(0), binding(4)]] var<uniform> param: taa_param;
+
+
+fn rgb_to_ycocg(c: vec3<f32>) -> vec3<f32> {
+	return vec3<f32>(
+		0.25 * c.r + 0.5 * c.g + 0.25 * c.b,
+		0.5 * c.r - 0.5 * c.b,
+		-0.25 * c.r + 0.5 * c.g - 0.25 * c.b
+	);
+}
+
+fn ycocg_to_rgb(c: vec3<f32>) -> vec3<f32> {
+	return vec3<f32>(c.x + c.y - c.z, c.x + c.z, c.x - c.y - c.z);
+}
+
+// Blends in a tonemapped space so a few very bright samples do not dominate the resolve
+// (Karis, High Quality Temporal Supersampling)
+fn tonemap_weight(c: vec3<f32>) -> f32 {
+	return 1.0 / (1.0 + c.x);
+}
+
+fn uv_to_ndc(uv: vec2<f32>) -> vec2<f32> {
+	return vec2<f32>(uv.x * 2.0 - 1.0, 1.0 - uv.y * 2.0);
+}
+
+fn ndc_to_uv(ndc: vec2<f32>) -> vec2<f32> {
+	return vec2<f32>(ndc.x * 0.5 + 0.5, 0.5 - ndc.y * 0.5);
+}
+
+// Clips the history toward the center of the neighbourhood box instead of clamping each
+// channel, which keeps its hue
+fn clip_aabb(box_min: vec3<f32>, box_max: vec3<f32>, history: vec3<f32>) -> vec3<f32> {
+	let center = 0.5 * (box_max + box_min);
+	let extents = 0.5 * (box_max - box_min) + 0.0001;
+	let offset = history - center;
+	let unit = abs(offset / extents);
+	let max_unit = max(unit.x, max(unit.y, unit.z));
+	if (max_unit > 1.0) {
+		return center + offset / max_unit;
+	}
+	return history;
+}
+
+
+[[stage(fragment)]]
+fn fs_main(in: VertexOutput) -> [[location(0)]] vec4<f32> {
+	let size = textureDimensions(input_texture);
+	let pixel = vec2<i32>(in.clip_position.xy);
+
+	var current = vec3<f32>(0.0);
+	var box_min = vec3<f32>(1e9);
+	var box_max = vec3<f32>(-1e9);
+	for (var y: i32 = -1; y <= 1; y = y + 1) {
+		for (var x: i32 = -1; x <= 1; x = x + 1) {
+			let coord = clamp(pixel + vec2<i32>(x, y), vec2<i32>(0), size - 1);
+			let c = rgb_to_ycocg(textureLoad(input_texture, coord, 0).rgb);
+			box_min = min(box_min, c);
+			box_max = max(box_max, c);
+			if (x == 0 && y == 0) {
+				current = c;
+			}
+		}
+	}
+
+	if (param.reset != 0u) {
+		return vec4<f32>(ycocg_to_rgb(current), 1.0);
+	}
+
+	// Camera motion: where the surface of this pixel was in the previous frame
+	let depth = textureLoad(depth_texture, pixel, 0);
+	let world = param.inv_view_proj * vec4<f32>(uv_to_ndc(in.uv), depth, 1.0);
+	let current_clip = param.view_proj * world;
+	let prev_clip = param.prev_view_proj * world;
+	let velocity = ndc_to_uv(current_clip.xy / current_clip.w) - ndc_to_uv(prev_clip.xy / prev_clip.w);
+	let history_uv = in.uv - velocity;
+
+	if (any(history_uv < vec2<f32>(0.0)) || any(history_uv > vec2<f32>(1.0))) {
+		return vec4<f32>(ycocg_to_rgb(current), 1.0);
+	}
+
+	let history_sample = textureSampleLevel(history_texture, samp, history_uv, 0.0).rgb;
+	let history = clip_aabb(box_min, box_max, rgb_to_ycocg(history_sample));
+
+	let current_weight = param.blend * tonemap_weight(current);
+	let history_weight = (1.0 - param.blend) * tonemap_weight(history);
+	let color = (current * current_weight + history * history_weight) / (current_weight + history_weight);
+
+	return vec4<f32>(ycocg_to_rgb(color), 1.0);
+}
@@ -0,0 +1,56 @@
+// Vertex Shader
+
+struct Vertexinput {
+	[[location(0)]] position: vec3<f32>;
+	[[location(1)]] normal: vec3<f32>;
+	[[location(2)]] uv: vec2<f32>;
+};
+
+struct VertexOutput {
+	[[builtin(position)]] clip_position: vec4<f32>;
+	[[location(0)]] uv: vec2<f32>;
+};
+
+[[stage(vertex)]]
+fn vs_main(in: Vertexinput) -> VertexOutput {
+	var out: VertexOutput;
+	out.uv = in.uv;
+	out.clip_position = vec4<f32>(in.position, 1.0);
+	return out;
+}
+
+// Fragment Shader
+
+// Must match tonemap.rs and tonemap_lut.wgsl
+let LUT_SCALE: f32 = 256.0;
+let LUT_MAX: f32 = 64.0;
+
+struct exposure_buffer {
+	luminance: f32;
+	exposure: f32;
+};
+
+[[group(0), binding(0)]] var input_texture: texture_2d<f32>;
+[[group(0), binding(1)]] var samp: sampler;
+[[group(0), binding(2)]] var tonemap_lut: texture_3d<f32>;
+[[group(0), binding(3)]] var<storage, read> exposure: exposure_buffer;
+
+
+fn lut_shaper(x: vec3<f32>) -> vec3<f32> {
+	return clamp(log2(1.0 + LUT_SCALE * max(x, vec3<f32>(0.0))) / log2(1.0 + LUT_SCALE * LUT_MAX), vec3<f32>(0.0), vec3<f32>(1.0));
+}
+
+// Tonemapping curve baked by tonemap_lut.wgsl
+fn tonemap(x: vec3<f32>) -> vec3<f32> {
+	let lut_size = vec3<f32>(textureDimensions(tonemap_lut));
+	let uvw = lut_shaper(x) * ((lut_size - 1.0) / lut_size) + 0.5 / lut_size;
+	return textureSampleLevel(tonemap_lut, samp, uvw, 0.0).rgb;
+}
+
+
+[[stage(fragment)]]
+fn fs_main(in: VertexOutput) -> [[location(0)]] vec4<f32> {
+	let color = textureSample(input_texture, samp, in.uv).rgb * exposure.exposure;
+
+	return vec4<f32>(tonemap(color), 1.0);
+}
@@ -0,0 +1,182 @@
+// Compute Shader
+
+// Bakes the active tonemapping curve into a 3D LUT sampled by tonemap.wgsl.
+// The LUT is indexed by shaped HDR color, see lut_shaper_inverse.
+
+// Must match tonemap.rs and tonemap.wgsl
+let LUT_SCALE: f32 = 256.0;
+let LUT_MAX: f32 = 64.0;
+
+let TONEMAP_CLAMP: u32 = 0u;
+let TONEMAP_REINHARD: u32 = 1u;
+let TONEMAP_REINHARD_EXTENDED: u32 = 2u;
+let TONEMAP_ACES: u32 = 3u;
+let TONEMAP_UCHIMURA: u32 = 4u;
+let TONEMAP_AGX: u32 = 5u;
+let TONEMAP_UNCHARTED2: u32 = 6u;
+
+struct tonemap_param {
+	tonemapper: u32;
+	white_point: f32;
+	gt_max_brightness: f32;
+	gt_contrast: f32;
+	gt_linear_start: f32;
+	gt_linear_length: f32;
+	gt_black_tightness: f32;
+	aces_a: f32;
+	aces_b: f32;
+	aces_c: f32;
+	aces_d: f32;
+	aces_e: f32;
+	uc2_shoulder_strength: f32;
+	uc2_linear_strength: f32;
+	uc2_linear_angle: f32;
+	uc2_toe_strength: f32;
+	uc2_toe_numerator: f32;
+	uc2_toe_denominator: f32;
+	uc2_linear_white: f32;
+	uc2_exposure_bias: f32;
+	agx_offset: f32;
+	agx_slope: f32;
+	agx_power: f32;
+	agx_saturation: f32;
+};
+
+fn reinhard(x: vec3<f32>) -> vec3<f32> {
+	return x / (1.0 + x);
+}
+
+fn reinhard_extended(x: vec3<f32>, white_point: f32) -> vec3<f32> {
+	let numerator = x * (1.0 + x / vec3<f32>(white_point * white_point));
+	return clamp(numerator / (1.0 + x), vec3<f32>(0.0), vec3<f32>(1.0));
+}
+
+fn GTTonemap_point(x: f32, p: tonemap_param) -> f32{
+	let m: f32 = p.gt_linear_start;    // linear section start
+	let a: f32 = p.gt_contrast;        // contrast
+	let c: f32 = p.gt_black_tightness; // black brightness
+	let P: f32 = p.gt_max_brightness;  // maximum brightness
+	let l: f32 = p.gt_linear_length;   // linear section length
+	let l0: f32 = ((P-m)*l) / a; // 0.312
+	let S0: f32 = m + l0; // 0.532
+	let S1: f32 = m + a * l0; // 0.532
+	let C2: f32 = (a*P) / (P - S1); // 2.13675213675
+	let L: f32 = m + a * (x - m);
+	let T: f32 = m * pow(x/m, c);
+	let S: f32 = P - (P - S1) * exp(-C2*(x - S0)/P);
+	let w0: f32 = 1.0 - smoothStep(0.0, m, x);
+	var w2: f32 = 1.0;
+	if (x < m+l) {
+		w2 = 0.0;
+	}
+	let w1: f32 = 1.0 - w0 - w2;
+	return f32(T * w0 + L * w1 + S * w2);
+}
+
+fn GTTonemap(x: vec3<f32>, p: tonemap_param) -> vec3<f32>{
+	return vec3<f32>(GTTonemap_point(x.r, p), GTTonemap_point(x.g, p), GTTonemap_point(x.b, p));
+}
+
+fn aces(x: vec3<f32>, p: tonemap_param) -> vec3<f32> {
+	let a: f32 = p.aces_a;
+	let b: f32 = p.aces_b;
+	let c: f32 = p.aces_c;
+	let d: f32 = p.aces_d;
+	let e: f32 = p.aces_e;
+	return clamp((x * (a * x + b)) / (x * (c * x + d) + e), vec3<f32>(0.0), vec3<f32>(1.0));
+}
+
+fn uncharted2_partial(x: vec3<f32>, p: tonemap_param) -> vec3<f32> {
+	let A = p.uc2_shoulder_strength;
+	let B = p.uc2_linear_strength;
+	let C = p.uc2_linear_angle;
+	let D = p.uc2_toe_strength;
+	let E = p.uc2_toe_numerator;
+	let F = p.uc2_toe_denominator;
+	return ((x * (A * x + C * B) + D * E) / (x * (A * x + B) + D * F)) - E / F;
+}
+
+fn uncharted2(x: vec3<f32>, p: tonemap_param) -> vec3<f32> {
+	let current = uncharted2_partial(x * p.uc2_exposure_bias, p);
+	let white_scale = 1.0 / uncharted2_partial(vec3<f32>(p.uc2_linear_white), p);
+	return clamp(current * white_scale, vec3<f32>(0.0), vec3<f32>(1.0));
+}
+
+// Polynomial fit of the AgX base contrast sigmoid
+fn agx_contrast_approx(x: vec3<f32>) -> vec3<f32> {
+	let x2 = x * x;
+	let x4 = x2 * x2;
+	return 15.5 * x4 * x2
+		- 40.14 * x4 * x
+		+ 31.96 * x4
+		- 6.868 * x2 * x
+		+ 0.4298 * x2
+		+ 0.1191 * x
+		- 0.00232;
+}
+
+fn agx(x: vec3<f32>, p: tonemap_param) -> vec3<f32> {
+	let agx_mat = mat3x3<f32>(
+		vec3<f32>(0.842479062253094, 0.0423282422610123, 0.0423756549057051),
+		vec3<f32>(0.0784335999999992, 0.878468636469772, 0.0784336),
+		vec3<f32>(0.0792237451477643, 0.0791661274605434, 0.879142973793104)
+	);
+	let agx_mat_inv = mat3x3<f32>(
+		vec3<f32>(1.19687900512017, -0.0528968517574562, -0.0529716355144438),
+		vec3<f32>(-0.0980208811401368, 1.15190312990417, -0.0980434501171241),
+		vec3<f32>(-0.0990297440797205, -0.0989611768448433, 1.15107367264116)
+	);
+	let min_ev = -12.47393;
+	let max_ev = 4.026069;
+
+	var color: vec3<f32> = agx_mat * x;
+	color = clamp(log2(max(color, vec3<f32>(1.0e-10))), vec3<f32>(min_ev), vec3<f32>(max_ev));
+	color = (color - min_ev) / (max_ev - min_ev);
+	color = agx_contrast_approx(color);
+
+	// Look
+	let luma = dot(color, vec3<f32>(0.2126, 0.7152, 0.0722));
+	color = pow(max(color * p.agx_slope + p.agx_offset, vec3<f32>(0.0)), vec3<f32>(p.agx_power));
+	color = luma + p.agx_saturation * (color - luma);
+
+	color = agx_mat_inv * color;
+	// AgX outputs a 2.2 encoded signal, go back to linear like the other operators
+	return pow(clamp(color, vec3<f32>(0.0), vec3<f32>(1.0)), vec3<f32>(2.2));
+}
+
+fn tonemap(x: vec3<f32>, p: tonemap_param) -> vec3<f32> {
+	if (p.tonemapper == TONEMAP_REINHARD) {
+		return reinhard(x);
+	} else if (p.tonemapper == TONEMAP_REINHARD_EXTENDED) {
+		return reinhard_extended(x, p.white_point);
+	} else if (p.tonemapper == TONEMAP_ACES) {
+		return aces(x, p);
+	} else if (p.tonemapper == TONEMAP_UCHIMURA) {
+		return GTTonemap(x, p);
+	} else if (p.tonemapper == TONEMAP_AGX) {
+		return agx(x, p);
+	} else if (p.tonemapper == TONEMAP_UNCHARTED2) {
+		return uncharted2(x, p);
+	}
+	return clamp(x, vec3<f32>(0.0), vec3<f32>(1.0));
+}
+
+[[group(0), binding(0)]] var lut_texture: texture_storage_3d<rgba16float, write>;
+[[group(0), binding(1)]] var<uniform> param: tonemap_param;
+
+// Inverse of tonemap.wgsl lut_shaper, log like for bright values and linear near black so 0 maps to 0
+fn lut_shaper_inverse(t: vec3<f32>) -> vec3<f32> {
+	return (exp2(t * log2(1.0 + LUT_SCALE * LUT_MAX)) - 1.0) / LUT_SCALE;
+}
+
+[[stage(compute), workgroup_size(4, 4, 4)]]
+fn cs_main([[builtin(global_invocation_id)]] global_invocation_id: vec3<u32>)
+{
+	let lut_size = vec3<u32>(textureDimensions(lut_texture));
+
+	if (global_invocation_id.x < lut_size.x && global_invocation_id.y < lut_size.y && global_invocation_id.z < lut_size.z) {
+		let t = vec3<f32>(global_invocation_id) / vec3<f32>(lut_size - 1u);
+		let color = tonemap(lut_shaper_inverse(t), param);
+		textureStore(lut_texture, vec3<i32>(global_invocation_id), vec4<f32>(color, 1.0));
+	}
+}
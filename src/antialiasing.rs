@@ -0,0 +1,391 @@
+use crate::context::Context;
+use crate::renderer::{
+	mesh::Material,
+	post_process::{self, PostProcessPass},
+	texture::Texture,
+	Renderer,
+};
+
+pub const FXAA_PASS_NAME: &str = "FXAA";
+pub const TAA_PASS_NAME: &str = "TAA";
+
+/// Length of the Halton sequence the camera jitter cycles through.
+const TAA_SAMPLE_COUNT: u32 = 8;
+/// Weight of the current frame in the TAA resolve.
+const TAA_BLEND: f32 = 0.1;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AntiAliasing {
+	None,
+	Msaa(u32),
+	/// Post pass on the tonemapped image, the scene is rendered with a single sample.
+	Fxaa,
+	/// Jittered projection accumulated over frames by the TAA pass, before the bloom.
+	Taa,
+}
+
+impl AntiAliasing {
+	/// wgpu 0.12 render passes only accept 1 or 4 samples, and it has no per format sample
+	/// count query to offer the others.
+	pub const ALL: [AntiAliasing; 4] = [
+		AntiAliasing::None,
+		AntiAliasing::Msaa(4),
+		AntiAliasing::Fxaa,
+		AntiAliasing::Taa,
+	];
+
+	pub fn name(self) -> &'static str {
+		match self {
+			AntiAliasing::None => "None",
+			AntiAliasing::Msaa(4) => "MSAA 4x",
+			AntiAliasing::Msaa(_) => "MSAA",
+			AntiAliasing::Fxaa => "FXAA",
+			AntiAliasing::Taa => "TAA",
+		}
+	}
+
+	/// Sample count of the color and depth targets of the pbr pass.
+	pub fn sample_count(self) -> u32 {
+		match self {
+			AntiAliasing::Msaa(samples) => samples,
+			_ => 1,
+		}
+	}
+
+	/// Whether the color and depth formats of the pbr pass can be rendered to with the sample
+	/// count, counts other than 1 and 4 are rejected by wgpu 0.12.
+	pub fn is_supported(self, context: &Context) -> bool {
+		match self.sample_count() {
+			1 => true,
+			4 => [
+				wgpu::TextureFormat::Rgba16Float,
+				wgpu::TextureFormat::Depth32Float,
+			]
+			.iter()
+			.all(|format| {
+				context
+					.format_features(*format)
+					.allowed_usages
+					.contains(wgpu::TextureUsages::RENDER_ATTACHMENT)
+			}),
+			_ => false,
+		}
+	}
+}
+
+/// FXAA 3.11 quality preset on the luma of the tonemapped image.
+pub struct FxaaPass {
+	fxaa_mat: Material,
+}
+
+impl FxaaPass {
+	pub fn new(renderer: &Renderer) -> Self {
+		let mut fxaa_mat = Material::new(1, 0);
+		fxaa_mat.add_bind_group(
+			&renderer.context.device,
+			Some("fxaa bind group layout"),
+			vec![
+				wgpu::BindGroupLayoutEntry {
+					binding: 0,
+					visibility: wgpu::ShaderStages::FRAGMENT,
+					ty: wgpu::BindingType::Texture {
+						sample_type: wgpu::TextureSampleType::Float { filterable: true },
+						view_dimension: wgpu::TextureViewDimension::D2,
+						multisampled: false,
+					},
+					count: None,
+				},
+				wgpu::BindGroupLayoutEntry {
+					binding: 1,
+					visibility: wgpu::ShaderStages::FRAGMENT,
+					ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+					count: None,
+				},
+			],
+			vec![],
+		);
+		fxaa_mat.set_render_pipeline(
+			&renderer.context.device,
+			Some("fxaa pipeline"),
+			wgpu::include_wgsl!("../assets/shaders/fxaa.wgsl"),
+			wgpu::TextureFormat::Rgba16Float,
+			1,
+			None,
+		);
+
+		FxaaPass { fxaa_mat }
+	}
+}
+
+impl PostProcessPass for FxaaPass {
+	fn name(&self) -> &'static str {
+		FXAA_PASS_NAME
+	}
+
+	fn resize(&mut self, _renderer: &mut Renderer) {}
+
+	fn encode(
+		&mut self,
+		renderer: &Renderer,
+		encoder: &mut wgpu::CommandEncoder,
+		input: &Texture,
+		output: &Texture,
+	) {
+		let bind_group = renderer
+			.context
+			.device
+			.create_bind_group(&wgpu::BindGroupDescriptor {
+				label: Some("fxaa bind group"),
+				layout: &self.fxaa_mat.bind_group_layout[0],
+				entries: &[
+					wgpu::BindGroupEntry {
+						binding: 0,
+						resource: wgpu::BindingResource::TextureView(&input.view),
+					},
+					wgpu::BindGroupEntry {
+						binding: 1,
+						resource: wgpu::BindingResource::Sampler(
+							renderer.hdr_texture.sampler.as_ref().unwrap(),
+						),
+					},
+				],
+			});
+
+		post_process::encode_fullscreen(
+			renderer,
+			encoder,
+			Some("fxaa Render Pass"),
+			self.fxaa_mat.render_pipeline.as_ref().unwrap(),
+			&bind_group,
+			&output.view,
+		);
+	}
+}
+
+fn halton(mut index: u32, base: u32) -> f32 {
+	let mut fraction = 1.0;
+	let mut result = 0.0;
+	while index > 0 {
+		fraction /= base as f32;
+		result += fraction * (index % base) as f32;
+		index /= base;
+	}
+	result
+}
+
+/// Sub-pixel offset of the projection for `frame`, in NDC.
+pub fn taa_jitter(frame: u32, width: u32, height: u32) -> uv::Vec2 {
+	// The sequence starts at 1, 0 would give the same offset on both axes
+	let index = frame % TAA_SAMPLE_COUNT + 1;
+	uv::Vec2::new(
+		(halton(index, 2) - 0.5) * 2.0 / width as f32,
+		(halton(index, 3) - 0.5) * 2.0 / height as f32,
+	)
+}
+
+#[repr(C, align(16))]
+#[derive(Clone, Copy)]
+struct TaaParam {
+	inv_view_proj: uv::Mat4, // jittered, the one the depth was rendered with
+	view_proj: uv::Mat4,
+	prev_view_proj: uv::Mat4,
+	blend: f32, // weight of the current frame
+	reset: u32, // the history is discarded when not 0
+}
+
+/// Accumulates the jittered frames in a history texture, reprojected with the camera motion
+/// and clipped to the neighbourhood of the current frame.
+pub struct TaaPass {
+	taa_mat: Material,
+	prev_view_proj: uv::Mat4,
+	// Renderer::frame_index of the last resolve, the history is only valid on the next frame
+	last_frame: Option<u32>,
+}
+
+impl TaaPass {
+	pub fn new(renderer: &Renderer) -> Self {
+		let mut taa_mat = Material::new(1, 0);
+		taa_mat.add_bind_group(
+			&renderer.context.device,
+			Some("taa bind group layout"),
+			vec![
+				wgpu::BindGroupLayoutEntry {
+					binding: 0,
+					visibility: wgpu::ShaderStages::FRAGMENT,
+					ty: wgpu::BindingType::Texture {
+						sample_type: wgpu::TextureSampleType::Float { filterable: true },
+						view_dimension: wgpu::TextureViewDimension::D2,
+						multisampled: false,
+					},
+					count: None,
+				},
+				wgpu::BindGroupLayoutEntry {
+					binding: 1,
+					visibility: wgpu::ShaderStages::FRAGMENT,
+					ty: wgpu::BindingType::Texture {
+						sample_type: wgpu::TextureSampleType::Float { filterable: true },
+						view_dimension: wgpu::TextureViewDimension::D2,
+						multisampled: false,
+					},
+					count: None,
+				},
+				wgpu::BindGroupLayoutEntry {
+					binding: 2,
+					visibility: wgpu::ShaderStages::FRAGMENT,
+					ty: wgpu::BindingType::Texture {
+						sample_type: wgpu::TextureSampleType::Depth,
+						view_dimension: wgpu::TextureViewDimension::D2,
+						multisampled: false,
+					},
+					count: None,
+				},
+				wgpu::BindGroupLayoutEntry {
+					binding: 3,
+					visibility: wgpu::ShaderStages::FRAGMENT,
+					ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+					count: None,
+				},
+				wgpu::BindGroupLayoutEntry {
+					binding: 4,
+					visibility: wgpu::ShaderStages::FRAGMENT,
+					ty: wgpu::BindingType::Buffer {
+						ty: wgpu::BufferBindingType::Uniform,
+						has_dynamic_offset: false,
+						min_binding_size: std::num::NonZeroU64::new(
+							std::mem::size_of::<TaaParam>() as u64,
+						),
+					},
+					count: None,
+				},
+			],
+			vec![],
+		);
+		taa_mat.set_render_pipeline(
+			&renderer.context.device,
+			Some("taa pipeline"),
+			wgpu::include_wgsl!("../assets/shaders/taa.wgsl"),
+			wgpu::TextureFormat::Rgba16Float,
+			1,
+			None,
+		);
+
+		taa_mat
+			.bind_groups_buffers
+			.push(
+				renderer
+					.context
+					.device
+					.create_buffer(&wgpu::BufferDescriptor {
+						label: Some("taa parameters buffer"),
+						size: std::mem::size_of::<TaaParam>() as u64,
+						usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+						mapped_at_creation: false,
+					}),
+			);
+		taa_mat.bind_groups_textures.push(Texture::new(
+			&renderer.context.device,
+			Some("taa history texture"),
+			renderer.hdr_texture.size.width,
+			renderer.hdr_texture.size.height,
+			1,
+			1,
+			1,
+			wgpu::TextureDimension::D2,
+			wgpu::TextureFormat::Rgba16Float,
+			wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+			wgpu::TextureAspect::All,
+		));
+
+		TaaPass {
+			taa_mat,
+			prev_view_proj: renderer.camera.unjittered_view_proj,
+			last_frame: None,
+		}
+	}
+}
+
+impl PostProcessPass for TaaPass {
+	fn name(&self) -> &'static str {
+		TAA_PASS_NAME
+	}
+
+	fn resize(&mut self, renderer: &mut Renderer) {
+		self.taa_mat.bind_groups_textures[0]
+			.recreate(&renderer.context.device, renderer.hdr_texture.size);
+		self.last_frame = None;
+	}
+
+	fn encode(
+		&mut self,
+		renderer: &Renderer,
+		encoder: &mut wgpu::CommandEncoder,
+		input: &Texture,
+		output: &Texture,
+	) {
+		let reset = self.last_frame != Some(renderer.frame_index.wrapping_sub(1));
+		let param = [TaaParam {
+			inv_view_proj: renderer.camera.view_proj.inversed(),
+			view_proj: renderer.camera.unjittered_view_proj,
+			prev_view_proj: self.prev_view_proj,
+			blend: TAA_BLEND,
+			reset: reset as u32,
+		}];
+		renderer
+			.context
+			.queue
+			.write_buffer(&self.taa_mat.bind_groups_buffers[0], 0, unsafe {
+				param.align_to::<u8>().1
+			});
+
+		let history = &self.taa_mat.bind_groups_textures[0];
+		let bind_group = renderer
+			.context
+			.device
+			.create_bind_group(&wgpu::BindGroupDescriptor {
+				label: Some("taa bind group"),
+				layout: &self.taa_mat.bind_group_layout[0],
+				entries: &[
+					wgpu::BindGroupEntry {
+						binding: 0,
+						resource: wgpu::BindingResource::TextureView(&input.view),
+					},
+					wgpu::BindGroupEntry {
+						binding: 1,
+						resource: wgpu::BindingResource::TextureView(&history.view),
+					},
+					wgpu::BindGroupEntry {
+						binding: 2,
+						resource: wgpu::BindingResource::TextureView(&renderer.depth_texture.view),
+					},
+					wgpu::BindGroupEntry {
+						binding: 3,
+						resource: wgpu::BindingResource::Sampler(
+							renderer.hdr_texture.sampler.as_ref().unwrap(),
+						),
+					},
+					wgpu::BindGroupEntry {
+						binding: 4,
+						resource: self.taa_mat.bind_groups_buffers[0].as_entire_binding(),
+					},
+				],
+			});
+
+		post_process::encode_fullscreen(
+			renderer,
+			encoder,
+			Some("taa Render Pass"),
+			self.taa_mat.render_pipeline.as_ref().unwrap(),
+			&bind_group,
+			&output.view,
+		);
+
+		encoder.copy_texture_to_texture(
+			output.texture.as_image_copy(),
+			history.texture.as_image_copy(),
+			history.size,
+		);
+
+		self.prev_view_proj = renderer.camera.unjittered_view_proj;
+		self.last_frame = Some(renderer.frame_index);
+	}
+}
@@ -3,13 +3,84 @@ extern crate ultraviolet as uv;
 use wgpu::util::DeviceExt;
 
 use super::renderer::mesh;
+use crate::context::Context;
 use crate::renderer::{
+	binding,
 	mesh::Material,
+	post_process::{self, PostProcessPass},
 	texture::{self, Texture},
-	Renderer, Vertex,
+	Instance, Renderer,
 };
+use crate::{effects, exposure, ibl, lights, pbr_textures, scene, shadow, skybox, sphere_grid};
 
 pub const BLOOM_MIP_COUNT: usize = 7;
+pub const BLOOM_PASS_NAME: &str = "Bloom";
+
+/// Storage format of the bloom mip chain.
+#[allow(clippy::enum_variant_names)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum BloomFormat {
+	Rg11b10Float,
+	/// Usable as a filtered storage texture on every adapter.
+	#[default]
+	Rgba16Float,
+	Rgba32Float,
+}
+
+impl BloomFormat {
+	pub const ALL: [BloomFormat; 3] = [
+		BloomFormat::Rg11b10Float,
+		BloomFormat::Rgba16Float,
+		BloomFormat::Rgba32Float,
+	];
+
+	pub fn texture_format(self) -> wgpu::TextureFormat {
+		match self {
+			BloomFormat::Rg11b10Float => wgpu::TextureFormat::Rg11b10Float,
+			BloomFormat::Rgba16Float => wgpu::TextureFormat::Rgba16Float,
+			BloomFormat::Rgba32Float => wgpu::TextureFormat::Rgba32Float,
+		}
+	}
+
+	pub fn name(self) -> &'static str {
+		match self {
+			BloomFormat::Rg11b10Float => "Rg11b10Float",
+			BloomFormat::Rgba16Float => "Rgba16Float",
+			BloomFormat::Rgba32Float => "Rgba32Float",
+		}
+	}
+
+	// Format name as written in a WGSL storage texture declaration
+	fn storage_format(self) -> &'static str {
+		match self {
+			BloomFormat::Rg11b10Float => "rg11b10float",
+			BloomFormat::Rgba16Float => "rgba16float",
+			BloomFormat::Rgba32Float => "rgba32float",
+		}
+	}
+
+	/// The chain is written as a storage texture and read back with a filtering sampler,
+	/// so the device has to support both for the format.
+	pub fn is_supported(self, context: &Context) -> bool {
+		let features = context.format_features(self.texture_format());
+		features
+			.allowed_usages
+			.contains(wgpu::TextureUsages::STORAGE_BINDING | wgpu::TextureUsages::TEXTURE_BINDING)
+			&& features.filterable
+	}
+}
+
+// bloom.wgsl is written against rgba16float, the other variants are generated from it
+fn bloom_shader(format: BloomFormat) -> wgpu::ShaderModuleDescriptor<'static> {
+	let source = include_str!("../assets/shaders/bloom.wgsl").replace(
+		"texture_storage_2d<rgba16float, write>",
+		&format!("texture_storage_2d<{}, write>", format.storage_format()),
+	);
+	wgpu::ShaderModuleDescriptor {
+		label: Some("bloom.wgsl"),
+		source: wgpu::ShaderSource::Wgsl(source.into()),
+	}
+}
 
 #[repr(C, align(16))]
 #[derive(Clone, Copy)]
@@ -20,9 +91,10 @@ pub struct PbrParam {
 	pub roughness: f32,
 	pub emissive_color: uv::Vec3,
 	pub ao: f32,
-	pub light_position: uv::Vec3,
 	pub emissive_intensity: f32,
-	pub light_color: uv::Vec3,
+	pub environment_intensity: f32, // scales the image based lighting
+	pub normal_scale: f32,          // of the normal map
+	pub occlusion_strength: f32,    // of the occlusion map
 }
 
 #[repr(C, align(16))]
@@ -30,55 +102,74 @@ pub struct PbrParam {
 pub struct BloomParam {
 	pub parameters: uv::Vec4, // (x) threshold, (y) threshold - knee, (z) knee * 2, (w) 0.25 / knee
 	pub combine_constant: f32,
+	pub intensity: f32,
 }
 
-fn load_sphere() -> Vec<Vertex> {
-	let input = std::io::BufReader::new(std::io::Cursor::new(include_bytes!(
-		"../assets/obj/uv_sphere.obj"
-	)));
-	let vertices: obj::Obj<obj::Vertex, u32> = obj::load_obj(input).unwrap();
-	let mut vertex_data = Vec::<Vertex>::with_capacity(vertices.indices.len());
-
-	for ind in vertices.indices.into_iter() {
-		let position = vertices.vertices[ind as usize].position;
-		let normal = vertices.vertices[ind as usize].normal;
-		vertex_data.push(Vertex {
-			position: uv::Vec3::new(position[0], position[1], position[2]),
-			normal: uv::Vec3::new(normal[0], normal[1], normal[2]),
-			uv: uv::Vec2::zero(),
-		});
-	}
+#[repr(C, align(16))]
+#[derive(Clone, Copy)]
+pub struct FinalParam {
+	pub grading_domain_min: uv::Vec3, // CubeLut::domain_min of the grading lut
+	pub grading_strength: f32,
+	pub grading_domain_max: uv::Vec3, // CubeLut::domain_max of the grading lut
+	pub effects: u32,                 // effects::EFFECT_* bits
+	pub vignette_intensity: f32,
+	pub vignette_smoothness: f32,
+	pub vignette_roundness: f32,
+	pub chromatic_aberration: f32,
+	pub grain_intensity: f32,
+	pub grain_response: f32, // how much the grain fades out in the highlights
+	pub grain_time: f32,     // animates the grain, in seconds
+	pub compare_split: f32,  // x of the comparison divider, as a fraction of the width
+	pub compare: u32,        // when not 0, the image without bloom is shown left of the divider
+}
 
-	vertex_data
+impl Default for FinalParam {
+	fn default() -> Self {
+		FinalParam {
+			grading_domain_min: uv::Vec3::zero(),
+			grading_strength: 1.0,
+			grading_domain_max: uv::Vec3::one(),
+			effects: effects::EFFECT_DITHER,
+			vignette_intensity: 0.45,
+			vignette_smoothness: 0.2,
+			vignette_roundness: 1.0,
+			chromatic_aberration: 0.2,
+			grain_intensity: 0.25,
+			grain_response: 0.8,
+			grain_time: 0.0,
+			compare_split: 0.5,
+			compare: 0,
+		}
+	}
 }
 
 pub fn init_pbr(renderer: &Renderer) -> mesh::Mesh {
-	let sample = 4u32;
 	let param = vec![PbrParam {
 		cam_pos: renderer.camera.position,
 		metallic: 0.0,
 		albedo: uv::Vec3::new(1.0, 0.0, 0.0),
 		roughness: 0.2,
 		emissive_color: uv::Vec3::zero(),
-		ao: 0.01,
-		light_position: uv::Vec3::new(-4.0, 5.0, -5.0),
+		ao: 1.0,
 		emissive_intensity: 0.0,
-		light_color: uv::Vec3::new(25.0, 25.0, 25.0),
+		environment_intensity: 1.0,
+		normal_scale: 1.0,
+		occlusion_strength: 1.0,
 	}];
+	let light_buffer = [lights::LightBuffer::new(&[lights::Light::default()])];
 
-	let vertex_data = load_sphere();
+	// Drawn when no scene is loaded, its material comes from the gui
+	let (vertex_data, index_data) = mesh::primitives::uv_sphere::<u16>(1.0, 64, 32);
 
-	let mut pbr_mesh = mesh::Mesh::new::<u32>(
+	let mut pbr_mesh = mesh::Mesh::new(
 		&renderer.context.device,
 		Some("pbrMesh"),
 		&vertex_data,
-		None,
+		Some(&index_data),
 		None,
 	);
 
-	let mut matrices = Vec::with_capacity(2);
-	matrices.push(renderer.camera.view_proj);
-	matrices.push(pbr_mesh.model);
+	let matrices = scene::object_matrices(renderer.camera.view_proj, pbr_mesh.model);
 
 	let content = unsafe { matrices.align_to::<u8>().1 };
 	let matrix_buffer =
@@ -102,10 +193,22 @@ pub fn init_pbr(renderer: &Renderer) -> mesh::Mesh {
 				usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
 			});
 
-	let mut pbr_mat = mesh::Material::new(1, 0);
+	let content = unsafe { light_buffer.align_to::<u8>().1 };
+	let light_buffer =
+		renderer
+			.context
+			.device
+			.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+				label: Some("pbr light buffer"),
+				contents: content,
+				usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
+			});
+
+	let mut pbr_mat = mesh::Material::new(4, 0);
+	pbr_mat.add_vertex_layout(Instance::layout());
 	pbr_mat.add_bind_group(
 		&renderer.context.device,
-		Some("matrices and PbrParam bind group"),
+		Some("matrices, PbrParam and lights bind group"),
 		vec![
 			wgpu::BindGroupLayoutEntry {
 				binding: 0,
@@ -114,7 +217,7 @@ pub fn init_pbr(renderer: &Renderer) -> mesh::Mesh {
 					ty: wgpu::BufferBindingType::Uniform,
 					has_dynamic_offset: false,
 					min_binding_size: std::num::NonZeroU64::new(
-						std::mem::size_of::<ultraviolet::Mat4>() as u64 * 2u64,
+						std::mem::size_of::<ultraviolet::Mat4>() as u64 * 3u64,
 					),
 				},
 				count: None,
@@ -131,37 +234,89 @@ pub fn init_pbr(renderer: &Renderer) -> mesh::Mesh {
 				},
 				count: None,
 			},
+			wgpu::BindGroupLayoutEntry {
+				binding: 2,
+				visibility: wgpu::ShaderStages::FRAGMENT,
+				ty: wgpu::BindingType::Buffer {
+					ty: wgpu::BufferBindingType::Storage { read_only: true },
+					has_dynamic_offset: false,
+					min_binding_size: std::num::NonZeroU64::new(std::mem::size_of::<
+						lights::LightBuffer,
+					>() as u64),
+				},
+				count: None,
+			},
 		],
 		vec![
 			wgpu::BindingResource::Buffer(wgpu::BufferBinding {
 				buffer: &matrix_buffer,
 				offset: 0,
-				size: std::num::NonZeroU64::new((std::mem::size_of::<uv::Mat4>() * 2) as u64),
+				size: std::num::NonZeroU64::new((std::mem::size_of::<uv::Mat4>() * 3) as u64),
 			}),
 			wgpu::BindingResource::Buffer(wgpu::BufferBinding {
 				buffer: &param_buffer,
 				offset: 0,
 				size: std::num::NonZeroU64::new(std::mem::size_of::<PbrParam>() as u64),
 			}),
+			wgpu::BindingResource::Buffer(wgpu::BufferBinding {
+				buffer: &light_buffer,
+				offset: 0,
+				size: std::num::NonZeroU64::new(std::mem::size_of::<lights::LightBuffer>() as u64),
+			}),
 		],
 	);
 	pbr_mat.bind_groups_buffers.push(matrix_buffer);
 	pbr_mat.bind_groups_buffers.push(param_buffer);
-
-	let multisampled_texture = texture::Texture::new(
+	pbr_mat.bind_groups_buffers.push(light_buffer);
+	pbr_mat.add_bind_group(
 		&renderer.context.device,
-		Some("pbr multisampled texture"),
-		renderer.hdr_texture.size.width,
-		renderer.hdr_texture.size.height,
-		1,
-		sample,
-		wgpu::TextureDimension::D2,
-		wgpu::TextureFormat::Rgba16Float,
-		wgpu::TextureUsages::RENDER_ATTACHMENT,
-		wgpu::TextureAspect::All,
+		Some("environment bind group"),
+		ibl::Environment::pbr_layout_entries(),
+		renderer.environment.pbr_bindings(),
 	);
+	pbr_mat.add_bind_group(
+		&renderer.context.device,
+		Some("shadow bind group"),
+		shadow::ShadowMap::pbr_layout_entries(),
+		renderer.shadow.pbr_bindings(),
+	);
+	pbr_mat.add_bind_group(
+		&renderer.context.device,
+		Some("material textures bind group"),
+		pbr_textures::PbrTextures::pbr_layout_entries(),
+		renderer.material_textures.pbr_bindings(),
+	);
+
+	set_pbr_sample_count(renderer, &mut pbr_mat);
+
+	pbr_mesh.material = Some(pbr_mat);
+
+	pbr_mesh
+}
 
-	pbr_mat.bind_groups_textures.push(multisampled_texture);
+/// Recreates the color target and the pipeline of the pbr pass for the sample count of
+/// `renderer.anti_aliasing`, the depth texture has to be recreated first.
+pub fn set_pbr_sample_count(renderer: &Renderer, pbr_mat: &mut Material) {
+	let sample = renderer.anti_aliasing.sample_count();
+
+	// With a single sample the pass renders straight into the hdr texture
+	pbr_mat.bind_groups_textures.clear();
+	if sample > 1 {
+		let multisampled_texture = texture::Texture::new(
+			&renderer.context.device,
+			Some("pbr multisampled texture"),
+			renderer.hdr_texture.size.width,
+			renderer.hdr_texture.size.height,
+			1,
+			1,
+			sample,
+			wgpu::TextureDimension::D2,
+			wgpu::TextureFormat::Rgba16Float,
+			wgpu::TextureUsages::RENDER_ATTACHMENT,
+			wgpu::TextureAspect::All,
+		);
+		pbr_mat.bind_groups_textures.push(multisampled_texture);
+	}
 
 	pbr_mat.set_render_pipeline(
 		&renderer.context.device,
@@ -171,26 +326,27 @@ pub fn init_pbr(renderer: &Renderer) -> mesh::Mesh {
 		sample,
 		Some(renderer.depth_texture.format),
 	);
-
-	pbr_mesh.material = Some(pbr_mat);
-
-	pbr_mesh
 }
 
 pub fn render_pbr(renderer: &Renderer, encoder: &mut wgpu::CommandEncoder) {
+	let (view, resolve_target) = match renderer
+		.meshes
+		.get("pbr")
+		.unwrap()
+		.material
+		.as_ref()
+		.unwrap()
+		.bind_groups_textures
+		.first()
+	{
+		Some(multisampled) => (&multisampled.view, Some(&renderer.hdr_texture.view)),
+		None => (&renderer.hdr_texture.view, None),
+	};
 	let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
 		label: Some("hdr Render Pass"),
 		color_attachments: &[wgpu::RenderPassColorAttachment {
-			view: &renderer
-				.meshes
-				.get("pbr")
-				.unwrap()
-				.material
-				.as_ref()
-				.unwrap()
-				.bind_groups_textures[0]
-				.view,
-			resolve_target: Some(&renderer.hdr_texture.view),
+			view,
+			resolve_target,
 			ops: wgpu::Operations {
 				load: wgpu::LoadOp::Clear(wgpu::Color {
 					r: 0.0,
@@ -198,54 +354,53 @@ pub fn render_pbr(renderer: &Renderer, encoder: &mut wgpu::CommandEncoder) {
 					b: 0.0,
 					a: 0.0,
 				}),
-				store: false,
+				// Only the resolved samples are needed
+				store: resolve_target.is_none(),
 			},
 		}],
 		depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
 			view: &renderer.depth_texture.view,
 			depth_ops: Some(wgpu::Operations {
 				load: wgpu::LoadOp::Clear(1.0),
-				store: false,
+				// Read by the TAA and depth of field passes
+				store: true,
 			}),
 			stencil_ops: None,
 		}),
 	});
-	render_pass.set_pipeline(
-		renderer
-			.meshes
-			.get("pbr")
-			.unwrap()
-			.material
-			.as_ref()
-			.unwrap()
-			.render_pipeline
-			.as_ref()
-			.unwrap(),
-	);
-	render_pass.set_vertex_buffer(
-		0,
-		renderer
-			.meshes
-			.get("pbr")
-			.unwrap()
-			.vertex_buffer
-			.as_ref()
-			.unwrap()
-			.slice(..),
-	);
-	render_pass.set_bind_group(
-		0,
-		&renderer
-			.meshes
-			.get("pbr")
-			.unwrap()
-			.material
-			.as_ref()
-			.unwrap()
-			.bind_group[0],
-		&[],
-	);
-	render_pass.draw(0..renderer.meshes.get("pbr").unwrap().draw_count, 0..1);
+	let pbr_mat = renderer
+		.meshes
+		.get("pbr")
+		.unwrap()
+		.material
+		.as_ref()
+		.unwrap();
+	render_pass.set_pipeline(pbr_mat.render_pipeline.as_ref().unwrap());
+	render_pass.set_bind_group(1, &pbr_mat.bind_group[1], &[]);
+	render_pass.set_bind_group(2, &pbr_mat.bind_group[2], &[]);
+	render_pass.set_vertex_buffer(1, renderer.default_instance.slice(..));
+	// A loaded scene replaces the sphere and the ground
+	if renderer.scene.is_empty() {
+		render_pass.set_bind_group(0, &pbr_mat.bind_group[0], &[]);
+		render_pass.set_bind_group(3, &pbr_mat.bind_group[3], &[]);
+		let sphere = renderer.meshes.get("pbr").unwrap();
+		// The chart replaces the sphere and hides the ground
+		match &renderer.sphere_grid {
+			Some(instances) => {
+				render_pass.set_vertex_buffer(1, instances.slice(..));
+				sphere.draw(&mut render_pass, 0..sphere_grid::INSTANCE_COUNT);
+			}
+			None => {
+				sphere.draw(&mut render_pass, 0..1);
+				scene::render_object(renderer.meshes.get("ground").unwrap(), &mut render_pass);
+			}
+		}
+	} else {
+		for object in &renderer.scene {
+			scene::render_object(&object.mesh, &mut render_pass);
+		}
+	}
+	skybox::render_skybox(renderer, &mut render_pass);
 }
 
 fn create_bloom_bind_group(
@@ -290,6 +445,10 @@ fn create_bloom_bind_group(
 					binding: 4,
 					resource: wgpu::BindingResource::Buffer(parameters),
 				},
+				wgpu::BindGroupEntry {
+					binding: 5,
+					resource: renderer.exposure_buffer.buffer.as_entire_binding(),
+				},
 			],
 		});
 
@@ -297,17 +456,8 @@ fn create_bloom_bind_group(
 }
 
 fn set_all_bind_group(renderer: &Renderer, bloom_mat: &mut Material) {
+	// The prefilter bind group depends on the input of the pass, it is created every frame
 	bloom_mat.bind_group.clear();
-	// Prefilter bind group
-	bloom_mat.bind_group.push(create_bloom_bind_group(
-		renderer,
-		bloom_mat,
-		&bloom_mat.bind_groups_textures[0].mip_view[0],
-		&renderer.hdr_texture.view,
-		&renderer.hdr_texture.view,
-		renderer.hdr_texture.sampler.as_ref().unwrap(),
-		&bloom_mat.bind_groups_buffers[0],
-	));
 
 	// Downsample bind groups
 	for i in 1..BLOOM_MIP_COUNT {
@@ -375,6 +525,7 @@ fn set_all_bind_group(renderer: &Renderer, bloom_mat: &mut Material) {
 }
 
 pub fn init_bloom(renderer: &mut Renderer) -> mesh::Mesh {
+	let format = renderer.bloom_format.texture_format();
 	let mut bloom_mesh = mesh::Mesh::new::<u32>(
 		&renderer.context.device,
 		Some("bloomMesh"),
@@ -383,7 +534,7 @@ pub fn init_bloom(renderer: &mut Renderer) -> mesh::Mesh {
 		None,
 	);
 
-	let mut bloom_mat = mesh::Material::new(BLOOM_MIP_COUNT * 2 + 2, 1);
+	let mut bloom_mat = mesh::Material::new(BLOOM_MIP_COUNT * 2 + 1, 1);
 
 	let bind_group_layout_entries = vec![
 		wgpu::BindGroupLayoutEntry {
@@ -391,7 +542,7 @@ pub fn init_bloom(renderer: &mut Renderer) -> mesh::Mesh {
 			visibility: wgpu::ShaderStages::COMPUTE,
 			ty: wgpu::BindingType::StorageTexture {
 				access: wgpu::StorageTextureAccess::WriteOnly,
-				format: wgpu::TextureFormat::Rgba16Float,
+				format,
 				view_dimension: wgpu::TextureViewDimension::D2,
 			},
 			count: None,
@@ -434,6 +585,18 @@ pub fn init_bloom(renderer: &mut Renderer) -> mesh::Mesh {
 			},
 			count: None,
 		},
+		wgpu::BindGroupLayoutEntry {
+			binding: 5,
+			visibility: wgpu::ShaderStages::COMPUTE,
+			ty: wgpu::BindingType::Buffer {
+				ty: wgpu::BufferBindingType::Storage { read_only: true },
+				has_dynamic_offset: false,
+				min_binding_size: std::num::NonZeroU64::new(
+					std::mem::size_of::<exposure::Exposure>() as u64,
+				),
+			},
+			count: None,
+		},
 	];
 
 	bloom_mat.add_bind_group(
@@ -450,10 +613,11 @@ pub fn init_bloom(renderer: &mut Renderer) -> mesh::Mesh {
 		Some("bloom downsampler image 0"),
 		renderer.context.size.width / 2,
 		renderer.context.size.height / 2,
+		1,
 		BLOOM_MIP_COUNT as u32,
 		1,
 		wgpu::TextureDimension::D2,
-		wgpu::TextureFormat::Rgba16Float,
+		format,
 		wgpu::TextureUsages::STORAGE_BINDING | wgpu::TextureUsages::TEXTURE_BINDING,
 		wgpu::TextureAspect::All,
 	));
@@ -462,10 +626,11 @@ pub fn init_bloom(renderer: &mut Renderer) -> mesh::Mesh {
 		Some("bloom downsampler image 1"),
 		renderer.context.size.width / 2,
 		renderer.context.size.height / 2,
+		1,
 		BLOOM_MIP_COUNT as u32,
 		1,
 		wgpu::TextureDimension::D2,
-		wgpu::TextureFormat::Rgba16Float,
+		format,
 		wgpu::TextureUsages::STORAGE_BINDING | wgpu::TextureUsages::TEXTURE_BINDING,
 		wgpu::TextureAspect::All,
 	));
@@ -474,10 +639,11 @@ pub fn init_bloom(renderer: &mut Renderer) -> mesh::Mesh {
 		Some("bloom upsampler image"),
 		renderer.context.size.width / 2,
 		renderer.context.size.height / 2,
+		1,
 		BLOOM_MIP_COUNT as u32,
 		1,
 		wgpu::TextureDimension::D2,
-		wgpu::TextureFormat::Rgba16Float,
+		format,
 		wgpu::TextureUsages::STORAGE_BINDING | wgpu::TextureUsages::TEXTURE_BINDING,
 		wgpu::TextureAspect::All,
 	));
@@ -502,6 +668,7 @@ pub fn init_bloom(renderer: &mut Renderer) -> mesh::Mesh {
 								0.25f32 / bloom_knee,
 							),
 							combine_constant: 0.68,
+							intensity: 4.0,
 						}]
 						.align_to::<u8>()
 						.1
@@ -512,7 +679,7 @@ pub fn init_bloom(renderer: &mut Renderer) -> mesh::Mesh {
 	bloom_mat.set_compute_pipeline(
 		&renderer.context.device,
 		Some("bloom compute pipeline"),
-		wgpu::include_wgsl!("../assets/shaders/bloom.wgsl"),
+		bloom_shader(renderer.bloom_format),
 	);
 
 	bloom_mesh.material = Some(bloom_mat);
@@ -536,7 +703,16 @@ fn get_mip_size(current_mip: usize, texture: &Texture) -> wgpu::Extent3d {
 	}
 }
 
-pub fn render_bloom(renderer: &mut Renderer, encoder: &mut wgpu::CommandEncoder) {
+/// Texture holding the result of the last upsample.
+fn bloom_result(bloom_mat: &Material) -> &Texture {
+	if (0..=BLOOM_MIP_COUNT - 2).count() % 2 == 1 {
+		&bloom_mat.bind_groups_textures[1]
+	} else {
+		&bloom_mat.bind_groups_textures[2]
+	}
+}
+
+fn render_bloom(renderer: &Renderer, encoder: &mut wgpu::CommandEncoder, input: &Texture) {
 	const MODE_PREFILTER: u32 = 0;
 	const MODE_DOWNSAMPLE: u32 = 1;
 	const MODE_UPSAMPLE_FIRST: u32 = 2;
@@ -546,13 +722,23 @@ pub fn render_bloom(renderer: &mut Renderer, encoder: &mut wgpu::CommandEncoder)
 		mode_lod: u32,
 	}
 
-	let mut bloom_mat =
-		std::mem::take(&mut renderer.meshes.get_mut("bloom").unwrap().material).unwrap();
-
-	if renderer.resized {
-		set_all_bind_group(renderer, &mut bloom_mat);
-	}
+	let bloom_mat = renderer
+		.meshes
+		.get("bloom")
+		.unwrap()
+		.material
+		.as_ref()
+		.unwrap();
 
+	let prefilter_bind_group = create_bloom_bind_group(
+		renderer,
+		bloom_mat,
+		&bloom_mat.bind_groups_textures[0].mip_view[0],
+		&input.view,
+		&input.view,
+		renderer.hdr_texture.sampler.as_ref().unwrap(),
+		&bloom_mat.bind_groups_buffers[0],
+	);
 	let mut bind_group_index = 0usize;
 
 	let mut pc = Vec::with_capacity(1);
@@ -567,8 +753,7 @@ pub fn render_bloom(renderer: &mut Renderer, encoder: &mut wgpu::CommandEncoder)
 	let pc_data = unsafe { pc.align_to::<u8>().1 };
 	compute_pass.set_push_constants(0, pc_data);
 
-	compute_pass.set_bind_group(0, &bloom_mat.bind_group[bind_group_index], &[]);
-	bind_group_index += 1;
+	compute_pass.set_bind_group(0, &prefilter_bind_group, &[]);
 	let mip_size = get_mip_size(0, &bloom_mat.bind_groups_textures[0]);
 	compute_pass.dispatch(mip_size.width / 8 + 1, mip_size.height / 4 + 1, 1);
 
@@ -612,7 +797,124 @@ pub fn render_bloom(renderer: &mut Renderer, encoder: &mut wgpu::CommandEncoder)
 		bind_group_index += 1;
 		compute_pass.dispatch(mip_size.width / 8 + 1, mip_size.height / 4 + 1, 1);
 	}
+}
+
+const COMPOSITE_SHADER: &str = include_str!("../assets/shaders/bloom_composite.wgsl");
+
+/// Bindings of the composite, checked against `bloom_composite.wgsl` when the pass is created.
+const COMPOSITE_SLOTS: [binding::Slot; 5] = [
+	binding::Slot::new("scene", 0, binding::SlotType::Texture2d),
+	binding::Slot::new("bloom", 1, binding::SlotType::Texture2d),
+	binding::Slot::new("samp", 2, binding::SlotType::Sampler),
+	binding::Slot::new(
+		"param",
+		3,
+		binding::SlotType::Uniform(std::mem::size_of::<BloomParam>() as u64),
+	),
+	binding::Slot::new(
+		"exposure",
+		4,
+		binding::SlotType::ReadOnlyStorage(std::mem::size_of::<exposure::Exposure>() as u64),
+	),
+];
+
+/// Bloom chain computed from the input, added back to it by a fullscreen composite.
+pub struct BloomPass {
+	composite_mat: Material,
+}
+
+impl BloomPass {
+	pub fn new(renderer: &Renderer) -> Self {
+		binding::validate(
+			"bloom_composite.wgsl",
+			COMPOSITE_SHADER,
+			0,
+			&COMPOSITE_SLOTS,
+		);
+		let mut composite_mat = Material::new(1, 0);
+		composite_mat.bind_group_layout.push(binding::create_layout(
+			&renderer.context.device,
+			Some("bloom composite bind group layout"),
+			&COMPOSITE_SLOTS,
+			wgpu::ShaderStages::FRAGMENT,
+		));
+		composite_mat.set_render_pipeline(
+			&renderer.context.device,
+			Some("bloom composite pipeline"),
+			wgpu::ShaderModuleDescriptor {
+				label: Some("bloom_composite.wgsl"),
+				source: wgpu::ShaderSource::Wgsl(COMPOSITE_SHADER.into()),
+			},
+			wgpu::TextureFormat::Rgba16Float,
+			1,
+			None,
+		);
+
+		BloomPass { composite_mat }
+	}
+}
+
+impl PostProcessPass for BloomPass {
+	fn name(&self) -> &'static str {
+		BLOOM_PASS_NAME
+	}
+
+	fn resize(&mut self, renderer: &mut Renderer) {
+		let mut bloom_mat =
+			std::mem::take(&mut renderer.meshes.get_mut("bloom").unwrap().material).unwrap();
+		set_all_bind_group(renderer, &mut bloom_mat);
+		renderer.meshes.get_mut("bloom").unwrap().material = Some(bloom_mat);
+	}
+
+	fn encode(
+		&mut self,
+		renderer: &Renderer,
+		encoder: &mut wgpu::CommandEncoder,
+		input: &Texture,
+		output: &Texture,
+	) {
+		render_bloom(renderer, encoder, input);
+
+		let bloom_mat = renderer
+			.meshes
+			.get("bloom")
+			.unwrap()
+			.material
+			.as_ref()
+			.unwrap();
+		let bind_group = binding::create_bind_group(
+			&renderer.context.device,
+			Some("bloom composite bind group"),
+			&self.composite_mat.bind_group_layout[0],
+			&COMPOSITE_SLOTS,
+			&[
+				("scene", wgpu::BindingResource::TextureView(&input.view)),
+				(
+					"bloom",
+					wgpu::BindingResource::TextureView(&bloom_result(bloom_mat).view),
+				),
+				(
+					"samp",
+					wgpu::BindingResource::Sampler(renderer.hdr_texture.sampler.as_ref().unwrap()),
+				),
+				(
+					"param",
+					bloom_mat.bind_groups_buffers[0].as_entire_binding(),
+				),
+				(
+					"exposure",
+					renderer.exposure_buffer.buffer.as_entire_binding(),
+				),
+			],
+		);
 
-	drop(compute_pass);
-	renderer.meshes.get_mut("bloom").unwrap().material = Some(bloom_mat);
+		post_process::encode_fullscreen(
+			renderer,
+			encoder,
+			Some("bloom composite Render Pass"),
+			self.composite_mat.render_pipeline.as_ref().unwrap(),
+			&bind_group,
+			&output.view,
+		);
+	}
 }
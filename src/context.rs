@@ -1,10 +1,22 @@
 use winit::{dpi::PhysicalSize, window::Window};
 
+/// Every format a wgpu 0.12 backend can present.
+const SURFACE_FORMATS: [wgpu::TextureFormat; 6] = [
+	wgpu::TextureFormat::Bgra8UnormSrgb,
+	wgpu::TextureFormat::Bgra8Unorm,
+	wgpu::TextureFormat::Rgba8UnormSrgb,
+	wgpu::TextureFormat::Rgba8Unorm,
+	wgpu::TextureFormat::Rgb10a2Unorm,
+	wgpu::TextureFormat::Rgba16Float,
+];
+
 pub struct Context {
 	pub surface: wgpu::Surface,
 	pub device: wgpu::Device,
 	pub queue: wgpu::Queue,
 	pub config: wgpu::SurfaceConfiguration,
+	pub preferred_format: wgpu::TextureFormat,
+	pub surface_formats: Vec<wgpu::TextureFormat>, // the formats the surface accepts
 	pub adapter: wgpu::Adapter,
 	pub window: Window,
 	pub size: PhysicalSize<u32>,
@@ -14,6 +26,7 @@ impl Context {
 	pub async fn new(
 		window: Window,
 		features: Option<wgpu::Features>,
+		optional_features: wgpu::Features, // requested when the adapter supports them
 		limits: Option<wgpu::Limits>,
 	) -> Self {
 		let size = window.inner_size();
@@ -41,6 +54,7 @@ impl Context {
 		} else {
 			wgpu::Features::empty()
 		};
+		let features = features | (optional_features & adapter.features());
 		let limits = if let Some(l) = limits {
 			l
 		} else {
@@ -59,18 +73,22 @@ impl Context {
 			.await
 			.expect("Failed to request for the device and queue.");
 
+		let preferred_format = surface.get_preferred_format(&adapter).unwrap();
 		let config = wgpu::SurfaceConfiguration {
 			usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
-			format: surface.get_preferred_format(&adapter).unwrap(),
+			format: preferred_format,
 			width: size.width,
 			height: size.height,
 			present_mode: wgpu::PresentMode::Fifo,
 		};
+		let surface_formats = probe_surface_formats(&surface, &device, &config);
 		surface.configure(&device, &config);
 
 		Context {
 			surface,
 			config,
+			preferred_format,
+			surface_formats,
 			device,
 			queue,
 			adapter,
@@ -108,6 +126,26 @@ impl Context {
 		self.config = config;
 	}
 
+	/// Features of `format` usable by the device, the ones specific to the adapter need the
+	/// `TEXTURE_ADAPTER_SPECIFIC_FORMAT_FEATURES` feature.
+	pub fn format_features(&self, format: wgpu::TextureFormat) -> wgpu::TextureFormatFeatures {
+		if self
+			.device
+			.features()
+			.contains(wgpu::Features::TEXTURE_ADAPTER_SPECIFIC_FORMAT_FEATURES)
+		{
+			self.adapter.get_texture_format_features(format)
+		} else {
+			format.describe().guaranteed_format_features
+		}
+	}
+
+	/// Reconfigures the surface with `format`, which has to be supported by the surface.
+	pub fn set_format(&mut self, format: wgpu::TextureFormat) {
+		self.config.format = format;
+		self.surface.configure(&self.device, &self.config);
+	}
+
 	pub fn resize(&mut self, new_size: PhysicalSize<u32>) {
 		if new_size.width > 0 && new_size.height > 0 {
 			self.size = new_size;
@@ -117,3 +155,29 @@ impl Context {
 		}
 	}
 }
+
+/// wgpu 0.12 can not list the formats of a surface and panics when it is configured with one it
+/// does not accept, so each format is tried on a thread of its own and a panic means it is not
+/// supported. wgpu validates the format before touching the surface, which is left as it was.
+fn probe_surface_formats(
+	surface: &wgpu::Surface,
+	device: &wgpu::Device,
+	config: &wgpu::SurfaceConfiguration,
+) -> Vec<wgpu::TextureFormat> {
+	SURFACE_FORMATS
+		.into_iter()
+		.filter(|&format| {
+			let probe = wgpu::SurfaceConfiguration {
+				format,
+				..config.clone()
+			};
+			format == config.format
+				|| std::thread::scope(|scope| {
+					scope
+						.spawn(|| surface.configure(device, &probe))
+						.join()
+						.is_ok()
+				})
+		})
+		.collect()
+}
@@ -0,0 +1,401 @@
+use crate::renderer::{
+	mesh::Material,
+	post_process::{self, PostProcessPass},
+	texture::Texture,
+	Renderer,
+};
+
+pub const DOF_PASS_NAME: &str = "Depth of field";
+
+/// Height of the simulated sensor, a full frame one. With the vertical fov of the camera it
+/// gives the focal length of the lens.
+const SENSOR_HEIGHT: f32 = 0.024;
+
+/// Controls of the depth of field, read by the pass every frame.
+#[derive(Clone, Copy)]
+pub struct DofSettings {
+	pub focus_distance: f32, // in meters, used when focus_point is None
+	pub focus_point: Option<(u32, u32)>, // pixel the focus distance is read from on the GPU
+	pub max_coc: f32,        // radius of the largest blur, in pixels
+	pub pick_focus: bool,    // the next click in the scene sets focus_point
+}
+
+impl Default for DofSettings {
+	fn default() -> Self {
+		DofSettings {
+			focus_distance: 5.0,
+			focus_point: None,
+			max_coc: 16.0,
+			pick_focus: false,
+		}
+	}
+}
+
+#[repr(C, align(16))]
+#[derive(Clone, Copy)]
+struct DofParam {
+	near: f32,
+	far: f32,
+	focus_distance: f32,
+	focal_length: f32,     // in meters
+	aperture: f32,         // f-number
+	pixels_per_meter: f32, // on the sensor
+	max_coc: f32,
+	_padding: f32,
+	focus_point: [i32; 2], // negative to use focus_distance
+}
+
+// dof.wgsl is written against a single sampled depth texture, the multisampled variant is
+// generated from it
+fn dof_shader(sample_count: u32) -> wgpu::ShaderModuleDescriptor<'static> {
+	let source = include_str!("../assets/shaders/dof.wgsl");
+	let source = if sample_count > 1 {
+		source.replace("texture_depth_2d", "texture_depth_multisampled_2d")
+	} else {
+		source.to_owned()
+	};
+	wgpu::ShaderModuleDescriptor {
+		label: Some("dof.wgsl"),
+		source: wgpu::ShaderSource::Wgsl(source.into()),
+	}
+}
+
+fn half_size(renderer: &Renderer) -> wgpu::Extent3d {
+	wgpu::Extent3d {
+		width: (renderer.hdr_texture.size.width / 2).max(1),
+		height: (renderer.hdr_texture.size.height / 2).max(1),
+		depth_or_array_layers: 1,
+	}
+}
+
+/// Compute material of the focus, prepare and blur steps, for a depth texture of
+/// `sample_count` samples.
+fn create_dof_material(renderer: &Renderer, sample_count: u32) -> Material {
+	let mut dof_mat = Material::new(1, 1);
+	dof_mat.add_bind_group(
+		&renderer.context.device,
+		Some("dof bind group layout"),
+		vec![
+			wgpu::BindGroupLayoutEntry {
+				binding: 0,
+				visibility: wgpu::ShaderStages::COMPUTE,
+				ty: wgpu::BindingType::Texture {
+					sample_type: wgpu::TextureSampleType::Float { filterable: true },
+					view_dimension: wgpu::TextureViewDimension::D2,
+					multisampled: false,
+				},
+				count: None,
+			},
+			wgpu::BindGroupLayoutEntry {
+				binding: 1,
+				visibility: wgpu::ShaderStages::COMPUTE,
+				ty: wgpu::BindingType::Texture {
+					sample_type: wgpu::TextureSampleType::Depth,
+					view_dimension: wgpu::TextureViewDimension::D2,
+					multisampled: sample_count > 1,
+				},
+				count: None,
+			},
+			wgpu::BindGroupLayoutEntry {
+				binding: 2,
+				visibility: wgpu::ShaderStages::COMPUTE,
+				ty: wgpu::BindingType::StorageTexture {
+					access: wgpu::StorageTextureAccess::WriteOnly,
+					format: wgpu::TextureFormat::Rgba16Float,
+					view_dimension: wgpu::TextureViewDimension::D2,
+				},
+				count: None,
+			},
+			wgpu::BindGroupLayoutEntry {
+				binding: 3,
+				visibility: wgpu::ShaderStages::COMPUTE,
+				ty: wgpu::BindingType::Buffer {
+					ty: wgpu::BufferBindingType::Uniform,
+					has_dynamic_offset: false,
+					min_binding_size: std::num::NonZeroU64::new(
+						std::mem::size_of::<DofParam>() as u64
+					),
+				},
+				count: None,
+			},
+			wgpu::BindGroupLayoutEntry {
+				binding: 4,
+				visibility: wgpu::ShaderStages::COMPUTE,
+				ty: wgpu::BindingType::Buffer {
+					ty: wgpu::BufferBindingType::Storage { read_only: false },
+					has_dynamic_offset: false,
+					min_binding_size: std::num::NonZeroU64::new(std::mem::size_of::<f32>() as u64),
+				},
+				count: None,
+			},
+		],
+		vec![],
+	);
+	dof_mat.add_push_constant(wgpu::ShaderStages::COMPUTE, 0..4);
+	dof_mat.set_compute_pipeline(
+		&renderer.context.device,
+		Some("dof compute pipeline"),
+		dof_shader(sample_count),
+	);
+
+	dof_mat
+		.bind_groups_buffers
+		.push(
+			renderer
+				.context
+				.device
+				.create_buffer(&wgpu::BufferDescriptor {
+					label: Some("dof parameters buffer"),
+					size: std::mem::size_of::<DofParam>() as u64,
+					usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+					mapped_at_creation: false,
+				}),
+		);
+	dof_mat
+		.bind_groups_buffers
+		.push(
+			renderer
+				.context
+				.device
+				.create_buffer(&wgpu::BufferDescriptor {
+					label: Some("dof focus buffer"),
+					size: std::mem::size_of::<f32>() as u64,
+					usage: wgpu::BufferUsages::STORAGE,
+					mapped_at_creation: false,
+				}),
+		);
+
+	let size = half_size(renderer);
+	for label in ["dof prepared texture", "dof blurred texture"] {
+		dof_mat.bind_groups_textures.push(Texture::new(
+			&renderer.context.device,
+			Some(label),
+			size.width,
+			size.height,
+			1,
+			1,
+			1,
+			wgpu::TextureDimension::D2,
+			wgpu::TextureFormat::Rgba16Float,
+			wgpu::TextureUsages::STORAGE_BINDING | wgpu::TextureUsages::TEXTURE_BINDING,
+			wgpu::TextureAspect::All,
+		));
+	}
+
+	dof_mat
+}
+
+/// Thin lens depth of field: circle of confusion from the depth buffer, gather blur at half
+/// resolution and a composite with the sharp image.
+pub struct DofPass {
+	dof_mat: Material,
+	composite_mat: Material,
+	// Of the depth texture the compute material was created for
+	sample_count: u32,
+}
+
+impl DofPass {
+	pub fn new(renderer: &Renderer) -> Self {
+		let sample_count = renderer.anti_aliasing.sample_count();
+		let dof_mat = create_dof_material(renderer, sample_count);
+
+		let mut composite_mat = Material::new(1, 0);
+		composite_mat.add_bind_group(
+			&renderer.context.device,
+			Some("dof composite bind group layout"),
+			vec![
+				wgpu::BindGroupLayoutEntry {
+					binding: 0,
+					visibility: wgpu::ShaderStages::FRAGMENT,
+					ty: wgpu::BindingType::Texture {
+						sample_type: wgpu::TextureSampleType::Float { filterable: true },
+						view_dimension: wgpu::TextureViewDimension::D2,
+						multisampled: false,
+					},
+					count: None,
+				},
+				wgpu::BindGroupLayoutEntry {
+					binding: 1,
+					visibility: wgpu::ShaderStages::FRAGMENT,
+					ty: wgpu::BindingType::Texture {
+						sample_type: wgpu::TextureSampleType::Float { filterable: true },
+						view_dimension: wgpu::TextureViewDimension::D2,
+						multisampled: false,
+					},
+					count: None,
+				},
+				wgpu::BindGroupLayoutEntry {
+					binding: 2,
+					visibility: wgpu::ShaderStages::FRAGMENT,
+					ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+					count: None,
+				},
+			],
+			vec![],
+		);
+		composite_mat.set_render_pipeline(
+			&renderer.context.device,
+			Some("dof composite pipeline"),
+			wgpu::include_wgsl!("../assets/shaders/dof_composite.wgsl"),
+			wgpu::TextureFormat::Rgba16Float,
+			1,
+			None,
+		);
+
+		DofPass {
+			dof_mat,
+			composite_mat,
+			sample_count,
+		}
+	}
+
+	fn create_bind_group(
+		&self,
+		renderer: &Renderer,
+		input: &wgpu::TextureView,
+		output: &wgpu::TextureView,
+	) -> wgpu::BindGroup {
+		renderer
+			.context
+			.device
+			.create_bind_group(&wgpu::BindGroupDescriptor {
+				label: Some("dof bind group"),
+				layout: &self.dof_mat.bind_group_layout[0],
+				entries: &[
+					wgpu::BindGroupEntry {
+						binding: 0,
+						resource: wgpu::BindingResource::TextureView(input),
+					},
+					wgpu::BindGroupEntry {
+						binding: 1,
+						resource: wgpu::BindingResource::TextureView(&renderer.depth_texture.view),
+					},
+					wgpu::BindGroupEntry {
+						binding: 2,
+						resource: wgpu::BindingResource::TextureView(output),
+					},
+					wgpu::BindGroupEntry {
+						binding: 3,
+						resource: self.dof_mat.bind_groups_buffers[0].as_entire_binding(),
+					},
+					wgpu::BindGroupEntry {
+						binding: 4,
+						resource: self.dof_mat.bind_groups_buffers[1].as_entire_binding(),
+					},
+				],
+			})
+	}
+}
+
+impl PostProcessPass for DofPass {
+	fn name(&self) -> &'static str {
+		DOF_PASS_NAME
+	}
+
+	fn resize(&mut self, renderer: &mut Renderer) {
+		let size = half_size(renderer);
+		for texture in self.dof_mat.bind_groups_textures.iter_mut() {
+			texture.recreate(&renderer.context.device, size);
+		}
+	}
+
+	fn encode(
+		&mut self,
+		renderer: &Renderer,
+		encoder: &mut wgpu::CommandEncoder,
+		input: &Texture,
+		output: &Texture,
+	) {
+		const MODE_FOCUS: u32 = 0;
+		const MODE_PREPARE: u32 = 1;
+		const MODE_BLUR: u32 = 2;
+
+		// The depth texture follows the MSAA sample count
+		let sample_count = renderer.anti_aliasing.sample_count();
+		if sample_count != self.sample_count {
+			self.dof_mat = create_dof_material(renderer, sample_count);
+			self.sample_count = sample_count;
+		}
+
+		let camera = &renderer.camera;
+		let settings = &renderer.dof;
+		let (focus_x, focus_y) = match settings.focus_point {
+			Some((x, y)) => (x as i32, y as i32),
+			None => (-1, -1),
+		};
+		let param = [DofParam {
+			near: camera.near,
+			far: camera.far,
+			focus_distance: settings.focus_distance,
+			focal_length: 0.5 * SENSOR_HEIGHT / (0.5 * camera.fov).tan(),
+			aperture: camera.aperture,
+			pixels_per_meter: renderer.hdr_texture.size.height as f32 / SENSOR_HEIGHT,
+			max_coc: settings.max_coc,
+			_padding: 0.0,
+			focus_point: [focus_x, focus_y],
+		}];
+		renderer
+			.context
+			.queue
+			.write_buffer(&self.dof_mat.bind_groups_buffers[0], 0, unsafe {
+				param.align_to::<u8>().1
+			});
+
+		let prepared = &self.dof_mat.bind_groups_textures[0];
+		let blurred = &self.dof_mat.bind_groups_textures[1];
+		let prepare_bind_group = self.create_bind_group(renderer, &input.view, &prepared.view);
+		let blur_bind_group = self.create_bind_group(renderer, &prepared.view, &blurred.view);
+
+		{
+			let mut compute_pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+				label: Some("dof Compute Pass"),
+			});
+			compute_pass.set_pipeline(self.dof_mat.compute_pipeline.as_ref().unwrap());
+
+			compute_pass.set_push_constants(0, unsafe { [MODE_FOCUS].align_to::<u8>().1 });
+			compute_pass.set_bind_group(0, &prepare_bind_group, &[]);
+			compute_pass.dispatch(1, 1, 1);
+
+			compute_pass.set_push_constants(0, unsafe { [MODE_PREPARE].align_to::<u8>().1 });
+			compute_pass.dispatch(prepared.size.width / 8 + 1, prepared.size.height / 8 + 1, 1);
+
+			compute_pass.set_push_constants(0, unsafe { [MODE_BLUR].align_to::<u8>().1 });
+			compute_pass.set_bind_group(0, &blur_bind_group, &[]);
+			compute_pass.dispatch(blurred.size.width / 8 + 1, blurred.size.height / 8 + 1, 1);
+		}
+
+		let composite_bind_group =
+			renderer
+				.context
+				.device
+				.create_bind_group(&wgpu::BindGroupDescriptor {
+					label: Some("dof composite bind group"),
+					layout: &self.composite_mat.bind_group_layout[0],
+					entries: &[
+						wgpu::BindGroupEntry {
+							binding: 0,
+							resource: wgpu::BindingResource::TextureView(&input.view),
+						},
+						wgpu::BindGroupEntry {
+							binding: 1,
+							resource: wgpu::BindingResource::TextureView(&blurred.view),
+						},
+						wgpu::BindGroupEntry {
+							binding: 2,
+							resource: wgpu::BindingResource::Sampler(
+								renderer.hdr_texture.sampler.as_ref().unwrap(),
+							),
+						},
+					],
+				});
+
+		post_process::encode_fullscreen(
+			renderer,
+			encoder,
+			Some("dof composite Render Pass"),
+			self.composite_mat.render_pipeline.as_ref().unwrap(),
+			&composite_bind_group,
+			&output.view,
+		);
+	}
+}
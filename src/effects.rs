@@ -0,0 +1,169 @@
+use crate::renderer::texture;
+
+// Bits of `FinalParam::effects`, must match the EFFECT_* constants of final.wgsl
+pub const EFFECT_VIGNETTE: u32 = 1 << 0;
+pub const EFFECT_CHROMATIC_ABERRATION: u32 = 1 << 1;
+pub const EFFECT_FILM_GRAIN: u32 = 1 << 2;
+pub const EFFECT_DITHER: u32 = 1 << 3;
+
+/// Side of the tiled blue noise texture used by the dithering.
+const BLUE_NOISE_SIZE: usize = 64;
+/// Standard deviation of the energy function of the void and cluster method, in pixels.
+const BLUE_NOISE_SIGMA: f32 = 1.5;
+
+/// Weights of the chromatic aberration samples, from the inside to the outside of the fringe.
+const SPECTRAL_LUT: [[u8; 4]; 3] = [[255, 0, 0, 255], [0, 255, 0, 255], [0, 0, 255, 255]];
+
+pub fn create_spectral_lut(device: &wgpu::Device, queue: &wgpu::Queue) -> texture::Texture {
+	let lut_texture = texture::Texture::new(
+		device,
+		Some("spectral lut"),
+		SPECTRAL_LUT.len() as u32,
+		1,
+		1,
+		1,
+		1,
+		wgpu::TextureDimension::D2,
+		wgpu::TextureFormat::Rgba8Unorm,
+		wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+		wgpu::TextureAspect::All,
+	);
+
+	queue.write_texture(
+		wgpu::ImageCopyTexture {
+			texture: &lut_texture.texture,
+			mip_level: 0,
+			origin: wgpu::Origin3d::ZERO,
+			aspect: wgpu::TextureAspect::All,
+		},
+		unsafe { SPECTRAL_LUT.align_to::<u8>().1 },
+		wgpu::ImageDataLayout {
+			offset: 0,
+			bytes_per_row: std::num::NonZeroU32::new(SPECTRAL_LUT.len() as u32 * 4),
+			rows_per_image: None,
+		},
+		lut_texture.size,
+	);
+
+	lut_texture
+}
+
+/// Blue noise by the void and cluster method (Ulichney, 1993): every pixel is ranked by the
+/// order it is inserted in the largest void of the pattern, the ranks being evenly spread over
+/// 0 to 255. The energy wraps around so the texture tiles.
+fn generate_blue_noise(size: usize) -> Vec<u8> {
+	let n = size * size;
+	let toroidal = |d: usize| d.min(size - d) as f32;
+	let kernel = (0..n)
+		.map(|i| {
+			let (dx, dy) = (toroidal(i % size), toroidal(i / size));
+			(-(dx * dx + dy * dy) / (2.0 * BLUE_NOISE_SIGMA * BLUE_NOISE_SIGMA)).exp()
+		})
+		.collect::<Vec<_>>();
+	let splat = |energy: &mut [f32], pixel: usize, sign: f32| {
+		let (px, py) = (pixel % size, pixel / size);
+		for (i, e) in energy.iter_mut().enumerate() {
+			let dx = (i % size + size - px) % size;
+			let dy = (i / size + size - py) % size;
+			*e += sign * kernel[dy * size + dx];
+		}
+	};
+	// Pixel of the pattern with the highest energy, or the empty one with the lowest
+	let tightest_cluster = |pattern: &[bool], energy: &[f32]| {
+		(0..n)
+			.filter(|&i| pattern[i])
+			.max_by(|&a, &b| energy[a].partial_cmp(&energy[b]).unwrap())
+			.unwrap()
+	};
+	let largest_void = |pattern: &[bool], energy: &[f32]| {
+		(0..n)
+			.filter(|&i| !pattern[i])
+			.min_by(|&a, &b| energy[a].partial_cmp(&energy[b]).unwrap())
+			.unwrap()
+	};
+
+	// Initial pattern of a tenth of the pixels, from a xorshift so the texture is the same on
+	// every run, then relaxed until moving the tightest cluster does not change anything
+	let mut pattern = vec![false; n];
+	let mut energy = vec![0.0; n];
+	let mut state = 0x9e37_79b9_u32;
+	let mut ones = 0;
+	while ones < n / 10 {
+		state ^= state << 13;
+		state ^= state >> 17;
+		state ^= state << 5;
+		let pixel = state as usize % n;
+		if !pattern[pixel] {
+			pattern[pixel] = true;
+			splat(&mut energy, pixel, 1.0);
+			ones += 1;
+		}
+	}
+	loop {
+		let cluster = tightest_cluster(&pattern, &energy);
+		pattern[cluster] = false;
+		splat(&mut energy, cluster, -1.0);
+		let void = largest_void(&pattern, &energy);
+		pattern[void] = true;
+		splat(&mut energy, void, 1.0);
+		if void == cluster {
+			break;
+		}
+	}
+
+	let mut rank = vec![0; n];
+	{
+		let mut pattern = pattern.clone();
+		let mut energy = energy.clone();
+		for r in (0..ones).rev() {
+			let cluster = tightest_cluster(&pattern, &energy);
+			pattern[cluster] = false;
+			splat(&mut energy, cluster, -1.0);
+			rank[cluster] = r;
+		}
+	}
+	for r in ones..n {
+		let void = largest_void(&pattern, &energy);
+		pattern[void] = true;
+		splat(&mut energy, void, 1.0);
+		rank[void] = r;
+	}
+
+	rank.iter().map(|&r| (r * 256 / n) as u8).collect()
+}
+
+/// Tiled blue noise texture of the dithering, generated at startup.
+pub fn create_blue_noise(device: &wgpu::Device, queue: &wgpu::Queue) -> texture::Texture {
+	let noise = generate_blue_noise(BLUE_NOISE_SIZE);
+	let noise_texture = texture::Texture::new(
+		device,
+		Some("blue noise"),
+		BLUE_NOISE_SIZE as u32,
+		BLUE_NOISE_SIZE as u32,
+		1,
+		1,
+		1,
+		wgpu::TextureDimension::D2,
+		wgpu::TextureFormat::R8Unorm,
+		wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+		wgpu::TextureAspect::All,
+	);
+
+	queue.write_texture(
+		wgpu::ImageCopyTexture {
+			texture: &noise_texture.texture,
+			mip_level: 0,
+			origin: wgpu::Origin3d::ZERO,
+			aspect: wgpu::TextureAspect::All,
+		},
+		&noise,
+		wgpu::ImageDataLayout {
+			offset: 0,
+			bytes_per_row: std::num::NonZeroU32::new(BLUE_NOISE_SIZE as u32),
+			rows_per_image: None,
+		},
+		noise_texture.size,
+	);
+
+	noise_texture
+}
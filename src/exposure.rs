@@ -0,0 +1,397 @@
+use std::{
+	future::Future,
+	pin::Pin,
+	sync::{
+		atomic::{AtomicBool, Ordering},
+		Arc,
+	},
+	task::{Poll, Wake, Waker},
+};
+
+use wgpu::util::DeviceExt;
+
+use crate::renderer::{
+	buffer,
+	mesh::{self, Material},
+	Renderer,
+};
+
+pub const HISTOGRAM_BIN_COUNT: usize = 256;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum ExposureMode {
+	Fixed,
+	Auto,
+	Physical,
+}
+
+impl ExposureMode {
+	pub const ALL: [ExposureMode; 3] = [
+		ExposureMode::Fixed,
+		ExposureMode::Auto,
+		ExposureMode::Physical,
+	];
+
+	pub fn name(self) -> &'static str {
+		match self {
+			ExposureMode::Fixed => "Fixed",
+			ExposureMode::Auto => "Auto (histogram)",
+			ExposureMode::Physical => "Physical camera",
+		}
+	}
+
+	/// Value of `ExposureParam::mode`, matches the EXPOSURE_* constants of exposure.wgsl.
+	pub fn id(self) -> u32 {
+		self as u32
+	}
+
+	pub fn from_id(id: u32) -> Self {
+		ExposureMode::ALL
+			.get(id as usize)
+			.copied()
+			.unwrap_or(ExposureMode::Fixed)
+	}
+}
+
+#[repr(C, align(16))]
+#[derive(Clone, Copy)]
+pub struct ExposureParam {
+	pub min_ev: f32,       // log2 of the darkest luminance taken into account
+	pub max_ev: f32,       // log2 of the brightest luminance taken into account
+	pub speed_up: f32,     // adaptation speed when the scene gets brighter
+	pub speed_down: f32,   // adaptation speed when the scene gets darker
+	pub compensation: f32, // in EV, applied on top of every mode
+	pub mode: u32,
+	pub camera_exposure: f32, // PerspectiveCamera::exposure, used by the physical mode
+}
+
+impl Default for ExposureParam {
+	fn default() -> Self {
+		ExposureParam {
+			min_ev: -10.0,
+			max_ev: 6.0,
+			speed_up: 3.0,
+			speed_down: 1.0,
+			compensation: 0.0,
+			mode: ExposureMode::Fixed.id(),
+			camera_exposure: 1.0,
+		}
+	}
+}
+
+/// Content of `Renderer::exposure_buffer`, written by the average pass and read by the bloom
+/// composite and the tonemapping pass.
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct Exposure {
+	pub luminance: f32,
+	pub exposure: f32,
+}
+
+type MapFuture = Pin<Box<dyn Future<Output = Result<(), wgpu::BufferAsyncError>> + Send>>;
+
+/// Waker of the map future, set by `device.poll` once the buffer is mapped.
+#[derive(Default)]
+struct MapFlag(AtomicBool);
+
+impl Wake for MapFlag {
+	fn wake(self: Arc<Self>) {
+		self.0.store(true, Ordering::Release);
+	}
+}
+
+/// Copies the luminance histogram back to the CPU for display without stalling the frame:
+/// a copy is only recorded once the previous one has been read.
+pub struct HistogramReadback {
+	buffer: wgpu::Buffer,
+	copy_recorded: bool,
+	pending: Option<MapFuture>,
+	mapped: Arc<MapFlag>,
+	pub bins: Vec<u32>,
+}
+
+impl HistogramReadback {
+	pub fn new(device: &wgpu::Device) -> Self {
+		HistogramReadback {
+			buffer: device.create_buffer(&wgpu::BufferDescriptor {
+				label: Some("histogram readback buffer"),
+				size: (HISTOGRAM_BIN_COUNT * std::mem::size_of::<u32>()) as u64,
+				usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+				mapped_at_creation: false,
+			}),
+			copy_recorded: false,
+			pending: None,
+			mapped: Arc::default(),
+			bins: vec![0; HISTOGRAM_BIN_COUNT],
+		}
+	}
+
+	fn record_copy(&mut self, encoder: &mut wgpu::CommandEncoder, histogram: &wgpu::Buffer) {
+		if self.copy_recorded || self.pending.is_some() {
+			return;
+		}
+		encoder.copy_buffer_to_buffer(
+			histogram,
+			0,
+			&self.buffer,
+			0,
+			(HISTOGRAM_BIN_COUNT * std::mem::size_of::<u32>()) as u64,
+		);
+		self.copy_recorded = true;
+	}
+
+	/// Has to be called once the encoder holding the copy has been submitted.
+	pub fn request_map(&mut self) {
+		if self.copy_recorded {
+			self.pending = Some(Box::pin(
+				self.buffer.slice(..).map_async(wgpu::MapMode::Read),
+			));
+			self.copy_recorded = false;
+			self.mapped.0.store(false, Ordering::Release);
+			// The first poll registers the waker the mapping sets
+			self.read_if_mapped();
+		}
+	}
+
+	pub fn poll(&mut self, device: &wgpu::Device) {
+		if self.pending.is_none() {
+			return;
+		}
+		device.poll(wgpu::Maintain::Poll);
+		if self.mapped.0.swap(false, Ordering::Acquire) {
+			self.read_if_mapped();
+		}
+	}
+
+	fn read_if_mapped(&mut self) {
+		let pending = match self.pending.as_mut() {
+			Some(p) => p,
+			None => return,
+		};
+		let waker = Waker::from(self.mapped.clone());
+		let mut cx = std::task::Context::from_waker(&waker);
+		if let Poll::Ready(result) = pending.as_mut().poll(&mut cx) {
+			if result.is_ok() {
+				let data = self.buffer.slice(..).get_mapped_range();
+				let bins = unsafe { data.align_to::<u32>().1 };
+				self.bins.copy_from_slice(bins);
+				drop(data);
+				self.buffer.unmap();
+			}
+			self.pending = None;
+		}
+	}
+}
+
+pub fn create_exposure_buffer(device: &wgpu::Device) -> buffer::Buffer {
+	buffer::Buffer::new(
+		device,
+		Some("exposure buffer"),
+		vec![Exposure {
+			luminance: 1.0,
+			exposure: 1.0,
+		}],
+		wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
+	)
+}
+
+fn set_exposure_bind_group(renderer: &Renderer, exposure_mat: &mut Material) {
+	exposure_mat.bind_group.clear();
+	exposure_mat
+		.bind_group
+		.push(
+			renderer
+				.context
+				.device
+				.create_bind_group(&wgpu::BindGroupDescriptor {
+					label: Some("exposure bind group"),
+					layout: &exposure_mat.bind_group_layout[0],
+					entries: &[
+						wgpu::BindGroupEntry {
+							binding: 0,
+							resource: wgpu::BindingResource::TextureView(
+								&renderer.hdr_texture.view,
+							),
+						},
+						wgpu::BindGroupEntry {
+							binding: 1,
+							resource: exposure_mat.bind_groups_buffers[0].as_entire_binding(),
+						},
+						wgpu::BindGroupEntry {
+							binding: 2,
+							resource: renderer.exposure_buffer.buffer.as_entire_binding(),
+						},
+						wgpu::BindGroupEntry {
+							binding: 3,
+							resource: exposure_mat.bind_groups_buffers[1].as_entire_binding(),
+						},
+					],
+				}),
+		);
+}
+
+pub fn init_exposure(renderer: &Renderer) -> mesh::Mesh {
+	let mut exposure_mesh = mesh::Mesh::new::<u32>(
+		&renderer.context.device,
+		Some("exposureMesh"),
+		&vec![],
+		None,
+		None,
+	);
+
+	let mut exposure_mat = Material::new(1, 1);
+	exposure_mat.add_bind_group(
+		&renderer.context.device,
+		Some("exposure bind group layout"),
+		vec![
+			wgpu::BindGroupLayoutEntry {
+				binding: 0,
+				visibility: wgpu::ShaderStages::COMPUTE,
+				ty: wgpu::BindingType::Texture {
+					sample_type: wgpu::TextureSampleType::Float { filterable: true },
+					view_dimension: wgpu::TextureViewDimension::D2,
+					multisampled: false,
+				},
+				count: None,
+			},
+			wgpu::BindGroupLayoutEntry {
+				binding: 1,
+				visibility: wgpu::ShaderStages::COMPUTE,
+				ty: wgpu::BindingType::Buffer {
+					ty: wgpu::BufferBindingType::Storage { read_only: false },
+					has_dynamic_offset: false,
+					min_binding_size: std::num::NonZeroU64::new(
+						(HISTOGRAM_BIN_COUNT * std::mem::size_of::<u32>()) as u64,
+					),
+				},
+				count: None,
+			},
+			wgpu::BindGroupLayoutEntry {
+				binding: 2,
+				visibility: wgpu::ShaderStages::COMPUTE,
+				ty: wgpu::BindingType::Buffer {
+					ty: wgpu::BufferBindingType::Storage { read_only: false },
+					has_dynamic_offset: false,
+					min_binding_size: std::num::NonZeroU64::new(
+						std::mem::size_of::<Exposure>() as u64
+					),
+				},
+				count: None,
+			},
+			wgpu::BindGroupLayoutEntry {
+				binding: 3,
+				visibility: wgpu::ShaderStages::COMPUTE,
+				ty: wgpu::BindingType::Buffer {
+					ty: wgpu::BufferBindingType::Uniform,
+					has_dynamic_offset: false,
+					min_binding_size: std::num::NonZeroU64::new(
+						std::mem::size_of::<ExposureParam>() as u64,
+					),
+				},
+				count: None,
+			},
+		],
+		vec![],
+	);
+
+	exposure_mat.add_push_constant(wgpu::ShaderStages::COMPUTE, 0..8);
+
+	exposure_mat
+		.bind_groups_buffers
+		.push(
+			renderer
+				.context
+				.device
+				.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+					label: Some("luminance histogram buffer"),
+					contents: &[0u8; HISTOGRAM_BIN_COUNT * std::mem::size_of::<u32>()],
+					usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_SRC,
+				}),
+		);
+	exposure_mat
+		.bind_groups_buffers
+		.push(
+			renderer
+				.context
+				.device
+				.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+					label: Some("exposure parameters buffer"),
+					contents: unsafe { [ExposureParam::default()].align_to::<u8>().1 },
+					usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+				}),
+		);
+
+	exposure_mat.set_compute_pipeline(
+		&renderer.context.device,
+		Some("exposure compute pipeline"),
+		wgpu::include_wgsl!("../assets/shaders/exposure.wgsl"),
+	);
+
+	set_exposure_bind_group(renderer, &mut exposure_mat);
+
+	exposure_mesh.material = Some(exposure_mat);
+
+	exposure_mesh
+}
+
+/// Builds the luminance histogram of the hdr texture and writes the exposure of the current mode,
+/// adapting it toward the average luminance in auto mode.
+pub fn render_exposure(
+	renderer: &mut Renderer,
+	encoder: &mut wgpu::CommandEncoder,
+	delta_time: f32,
+) {
+	const MODE_HISTOGRAM: u32 = 0;
+	const MODE_AVERAGE: u32 = 1;
+
+	// Only read through its bytes by set_push_constants
+	#[allow(dead_code)]
+	struct PushConstant {
+		mode: u32,
+		delta_time: f32,
+	}
+
+	let mut exposure_mat =
+		std::mem::take(&mut renderer.meshes.get_mut("exposure").unwrap().material).unwrap();
+
+	if renderer.resized {
+		set_exposure_bind_group(renderer, &mut exposure_mat);
+	}
+
+	let mut pc = [PushConstant {
+		mode: MODE_HISTOGRAM,
+		delta_time,
+	}];
+
+	{
+		let mut compute_pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+			label: Some("histogram Compute Pass"),
+		});
+		compute_pass.set_pipeline(exposure_mat.compute_pipeline.as_ref().unwrap());
+		compute_pass.set_push_constants(0, unsafe { pc.align_to::<u8>().1 });
+		compute_pass.set_bind_group(0, &exposure_mat.bind_group[0], &[]);
+		compute_pass.dispatch(
+			renderer.hdr_texture.size.width / 16 + 1,
+			renderer.hdr_texture.size.height / 16 + 1,
+			1,
+		);
+	}
+
+	// The average pass clears the histogram, grab it before
+	renderer
+		.histogram
+		.record_copy(encoder, &exposure_mat.bind_groups_buffers[0]);
+
+	{
+		pc[0].mode = MODE_AVERAGE;
+		let mut compute_pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+			label: Some("exposure Compute Pass"),
+		});
+		compute_pass.set_pipeline(exposure_mat.compute_pipeline.as_ref().unwrap());
+		compute_pass.set_push_constants(0, unsafe { pc.align_to::<u8>().1 });
+		compute_pass.set_bind_group(0, &exposure_mat.bind_group[0], &[]);
+		compute_pass.dispatch(1, 1, 1);
+	}
+
+	renderer.meshes.get_mut("exposure").unwrap().material = Some(exposure_mat);
+}
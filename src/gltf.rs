@@ -0,0 +1,834 @@
+use std::path::{Path, PathBuf};
+
+use crate::lights;
+use crate::pbr_textures::MapKind;
+use crate::renderer::{json, mesh, png, Vertex};
+
+/// Directory scanned for `.gltf` and `.glb` scenes at startup.
+pub const SCENE_DIRECTORY: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/assets/scenes");
+
+/// Extensions the scenes can require, the others are rejected.
+const SUPPORTED_EXTENSIONS: [&str; 2] = ["KHR_lights_punctual", "KHR_materials_emissive_strength"];
+
+/// Metallic-roughness material, with the defaults of the specification.
+#[derive(Clone, Copy, Debug)]
+pub struct Material {
+	pub albedo: uv::Vec3,
+	pub metallic: f32,
+	pub roughness: f32,
+	pub emissive: uv::Vec3,
+	pub emissive_strength: f32,
+	pub normal_scale: f32,
+	pub occlusion_strength: f32,
+	pub maps: [Option<usize>; 5], // images of the maps, in the order of MapKind::ALL
+}
+
+impl Default for Material {
+	fn default() -> Self {
+		Material {
+			albedo: uv::Vec3::one(),
+			metallic: 1.0,
+			roughness: 1.0,
+			emissive: uv::Vec3::zero(),
+			emissive_strength: 1.0,
+			normal_scale: 1.0,
+			occlusion_strength: 1.0,
+			maps: [None; 5],
+		}
+	}
+}
+
+pub struct Primitive {
+	pub vertices: Vec<Vertex>,
+	pub indices: Vec<u32>,
+	pub model: uv::Mat4,
+	pub material: Option<usize>, // the default material when None
+}
+
+pub struct Camera {
+	pub position: uv::Vec3,
+	pub look_at: uv::Vec3,
+	pub up: uv::Vec3,
+	pub fov: f32, // vertical, in radians
+	pub near: f32,
+	pub far: Option<f32>, // infinite when None
+}
+
+/// Triangles of the default scene of a glTF 2.0 file, flattened with their world transform.
+pub struct Scene {
+	pub primitives: Vec<Primitive>,
+	pub materials: Vec<Material>,
+	pub images: Vec<Option<png::Image>>, // None when the format is not supported
+	pub lights: Vec<lights::Light>,
+	pub camera: Option<Camera>, // the first of the scene
+}
+
+impl Scene {
+	pub fn load(path: &Path) -> Result<Self, String> {
+		let source = std::fs::read(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+		let directory = path.parent().unwrap_or_else(|| Path::new("."));
+		Self::parse(&source, directory).map_err(|e| format!("{}: {}", path.display(), e))
+	}
+
+	/// `.gltf` or binary `.glb` file, external files are looked for in `directory`.
+	pub fn parse(source: &[u8], directory: &Path) -> Result<Self, String> {
+		let (document, binary) = if source.starts_with(b"glTF") {
+			let (json, binary) = parse_glb(source)?;
+			(json, binary)
+		} else {
+			(source, None)
+		};
+		let document = std::str::from_utf8(document).map_err(|_| "invalid utf-8")?;
+		let document = json::parse(document)?;
+
+		let version = document.get("asset").get("version").as_str().unwrap_or("");
+		if !version.starts_with("2.") {
+			return Err(format!("unsupported glTF version {}", version));
+		}
+		for extension in document.get("extensionsRequired").elements() {
+			let extension = extension.as_str().unwrap_or("");
+			if !SUPPORTED_EXTENSIONS.contains(&extension) {
+				return Err(format!("unsupported required extension {}", extension));
+			}
+		}
+
+		let buffers = document
+			.get("buffers")
+			.elements()
+			.iter()
+			.enumerate()
+			.map(|(i, buffer)| match buffer.get("uri").as_str() {
+				Some(uri) => load_uri(uri, directory),
+				None if i == 0 => binary
+					.map(|b| b.to_vec())
+					.ok_or_else(|| "missing binary chunk".to_owned()),
+				None => Err(format!("buffer {} has no uri", i)),
+			})
+			.collect::<Result<Vec<_>, _>>()?;
+		let loader = Loader {
+			document: &document,
+			buffers,
+		};
+
+		let images = document
+			.get("images")
+			.elements()
+			.iter()
+			.map(|image| loader.image(image, directory))
+			.collect::<Result<Vec<_>, _>>()?;
+		let materials = document
+			.get("materials")
+			.elements()
+			.iter()
+			.map(|material| loader.material(material))
+			.collect::<Result<Vec<_>, _>>()?;
+
+		let mut scene = Scene {
+			primitives: Vec::new(),
+			materials,
+			images,
+			lights: Vec::new(),
+			camera: None,
+		};
+
+		// Without scenes every node without a parent is drawn
+		let scene_index = document.get("scene").as_usize().unwrap_or(0);
+		let roots: Vec<usize> = match document.get("scenes").at(scene_index) {
+			json::Value::Null => {
+				let nodes = document.get("nodes").elements();
+				let children: Vec<usize> = nodes
+					.iter()
+					.flat_map(|n| {
+						n.get("children")
+							.elements()
+							.iter()
+							.filter_map(|c| c.as_usize())
+					})
+					.collect();
+				(0..nodes.len()).filter(|i| !children.contains(i)).collect()
+			}
+			scene => scene
+				.get("nodes")
+				.elements()
+				.iter()
+				.filter_map(|n| n.as_usize())
+				.collect(),
+		};
+		for root in roots {
+			loader.node(root, uv::Mat4::identity(), 0, &mut scene)?;
+		}
+		Ok(scene)
+	}
+
+	/// Center and radius of a sphere enclosing the primitives.
+	pub fn bounds(&self) -> (uv::Vec3, f32) {
+		let mut min = uv::Vec3::broadcast(f32::MAX);
+		let mut max = uv::Vec3::broadcast(f32::MIN);
+		for primitive in &self.primitives {
+			for vertex in &primitive.vertices {
+				let p = primitive.model.transform_point3(vertex.position);
+				min = min.min_by_component(p);
+				max = max.max_by_component(p);
+			}
+		}
+		if min.x > max.x {
+			return (uv::Vec3::zero(), 1.0);
+		}
+		((min + max) * 0.5, ((max - min) * 0.5).mag().max(0.001))
+	}
+}
+
+/// JSON and binary chunks of a `.glb` file.
+fn parse_glb(source: &[u8]) -> Result<(&[u8], Option<&[u8]>), String> {
+	let word = |offset: usize| {
+		source
+			.get(offset..offset + 4)
+			.map(|b| u32::from_le_bytes(b.try_into().unwrap()) as usize)
+			.ok_or_else(|| "unexpected end of the file".to_owned())
+	};
+	if word(4)? != 2 {
+		return Err("unsupported glb version".to_owned());
+	}
+	let length = word(8)?.min(source.len());
+	let mut offset = 12;
+	let (mut json, mut binary) = (None, None);
+	while offset + 8 <= length {
+		let (chunk_length, kind) = (word(offset)?, word(offset + 4)?);
+		let end = (offset + 8)
+			.checked_add(chunk_length)
+			.filter(|&end| end <= source.len())
+			.ok_or("unexpected end of the file")?;
+		let chunk = &source[offset + 8..end];
+		match kind {
+			0x4e4f534a if json.is_none() => json = Some(chunk),
+			0x004e4942 if binary.is_none() => binary = Some(chunk),
+			_ => {}
+		}
+		// Chunks are aligned to 4 bytes, as is the offset
+		offset = end.div_ceil(4) * 4;
+	}
+	Ok((json.ok_or("missing JSON chunk")?, binary))
+}
+
+/// Content of a relative file path or of a base64 data uri.
+fn load_uri(uri: &str, directory: &Path) -> Result<Vec<u8>, String> {
+	if let Some(data) = uri.strip_prefix("data:") {
+		let (_, encoded) = data
+			.split_once(";base64,")
+			.ok_or("only base64 data uris are supported")?;
+		return decode_base64(encoded);
+	}
+	let path = directory.join(percent_decode(uri));
+	std::fs::read(&path).map_err(|e| format!("{}: {}", path.display(), e))
+}
+
+fn percent_decode(uri: &str) -> String {
+	let bytes = uri.as_bytes();
+	let mut decoded = Vec::with_capacity(bytes.len());
+	let mut i = 0;
+	while i < bytes.len() {
+		let hex = bytes
+			.get(i + 1..i + 3)
+			.and_then(|h| std::str::from_utf8(h).ok())
+			.and_then(|h| u8::from_str_radix(h, 16).ok());
+		match (bytes[i], hex) {
+			(b'%', Some(byte)) => {
+				decoded.push(byte);
+				i += 3;
+			}
+			(byte, _) => {
+				decoded.push(byte);
+				i += 1;
+			}
+		}
+	}
+	String::from_utf8_lossy(&decoded).into_owned()
+}
+
+fn decode_base64(encoded: &str) -> Result<Vec<u8>, String> {
+	let mut decoded = Vec::with_capacity(encoded.len() * 3 / 4);
+	let (mut bits, mut bit_count) = (0u32, 0);
+	for byte in encoded.bytes() {
+		let value = match byte {
+			b'A'..=b'Z' => byte - b'A',
+			b'a'..=b'z' => byte - b'a' + 26,
+			b'0'..=b'9' => byte - b'0' + 52,
+			b'+' | b'-' => 62,
+			b'/' | b'_' => 63,
+			b'=' => break,
+			_ => return Err("invalid base64 data".to_owned()),
+		};
+		bits = bits << 6 | value as u32;
+		bit_count += 6;
+		if bit_count >= 8 {
+			bit_count -= 8;
+			decoded.push((bits >> bit_count) as u8);
+		}
+	}
+	Ok(decoded)
+}
+
+/// Column major 4x4 matrix of a node.
+fn node_transform(node: &json::Value) -> uv::Mat4 {
+	if let Some(matrix) = node.get("matrix").as_f32_array::<16>() {
+		return uv::Mat4::from(matrix);
+	}
+	let translation = node
+		.get("translation")
+		.as_f32_array::<3>()
+		.map_or(uv::Vec3::zero(), uv::Vec3::from);
+	let [x, y, z, w] = node
+		.get("rotation")
+		.as_f32_array::<4>()
+		.unwrap_or([0.0, 0.0, 0.0, 1.0]);
+	let scale = node
+		.get("scale")
+		.as_f32_array::<3>()
+		.map_or(uv::Vec3::one(), uv::Vec3::from);
+	let rotation = uv::Mat4::new(
+		uv::Vec4::new(
+			1.0 - 2.0 * (y * y + z * z),
+			2.0 * (x * y + z * w),
+			2.0 * (x * z - y * w),
+			0.0,
+		),
+		uv::Vec4::new(
+			2.0 * (x * y - z * w),
+			1.0 - 2.0 * (x * x + z * z),
+			2.0 * (y * z + x * w),
+			0.0,
+		),
+		uv::Vec4::new(
+			2.0 * (x * z + y * w),
+			2.0 * (y * z - x * w),
+			1.0 - 2.0 * (x * x + y * y),
+			0.0,
+		),
+		uv::Vec4::unit_w(),
+	);
+	uv::Mat4::from_translation(translation) * rotation * uv::Mat4::from_nonuniform_scale(scale)
+}
+
+/// Nodes deeper than this are assumed to be part of a cycle.
+const MAX_NODE_DEPTH: usize = 256;
+/// Elements of an accessor without a buffer view, which are zeros instead of data of the file.
+const MAX_ZERO_ACCESSOR_COUNT: usize = 1 << 24;
+
+struct Loader<'a> {
+	document: &'a json::Value,
+	buffers: Vec<Vec<u8>>,
+}
+
+impl Loader<'_> {
+	fn buffer_view(&self, index: usize) -> Result<(&[u8], Option<usize>), String> {
+		let view = self.document.get("bufferViews").at(index);
+		let buffer = view
+			.get("buffer")
+			.as_usize()
+			.and_then(|b| self.buffers.get(b))
+			.ok_or_else(|| format!("invalid buffer view {}", index))?;
+		let offset = view.get("byteOffset").as_usize().unwrap_or(0);
+		let length = view.get("byteLength").as_usize().unwrap_or(0);
+		let data = offset
+			.checked_add(length)
+			.and_then(|end| buffer.get(offset..end))
+			.ok_or_else(|| format!("buffer view {} out of its buffer", index))?;
+		Ok((data, view.get("byteStride").as_usize()))
+	}
+
+	/// Elements of an accessor as `f64`, normalized integers mapped to [0, 1] or [-1, 1].
+	/// Returns the values and the number of components of each element.
+	fn accessor(&self, index: usize) -> Result<(Vec<f64>, usize), String> {
+		let accessor = self.document.get("accessors").at(index);
+		let error = |message: &str| format!("accessor {}: {}", index, message);
+		let count = accessor
+			.get("count")
+			.as_usize()
+			.ok_or_else(|| error("missing count"))?;
+		let components = match accessor.get("type").as_str() {
+			Some("SCALAR") => 1,
+			Some("VEC2") => 2,
+			Some("VEC3") => 3,
+			Some("VEC4") => 4,
+			Some("MAT4") => 16,
+			_ => return Err(error("unsupported type")),
+		};
+		if !accessor.get("sparse").is_null() {
+			return Err(error("sparse accessors are not supported"));
+		}
+		let normalized = accessor.get("normalized").as_bool().unwrap_or(false);
+		let component_type = accessor.get("componentType").as_usize().unwrap_or(0);
+		let size = match component_type {
+			5120 | 5121 => 1,
+			5122 | 5123 => 2,
+			5125 | 5126 => 4,
+			_ => return Err(error("unsupported component type")),
+		};
+
+		// Without a buffer view the accessor is zeros, and nothing in the file bounds its count
+		let view = match accessor.get("bufferView").as_usize() {
+			Some(view) => view,
+			None if count <= MAX_ZERO_ACCESSOR_COUNT => {
+				return Ok((vec![0.0; count * components], components))
+			}
+			None => return Err(error("too many elements")),
+		};
+		let (data, stride) = self.buffer_view(view)?;
+		let offset = accessor.get("byteOffset").as_usize().unwrap_or(0);
+		let element_size = size * components;
+		let stride = stride.unwrap_or(element_size);
+		if stride < element_size {
+			return Err(error("stride smaller than an element"));
+		}
+		// The last element has to end in the view, which bounds the count by the size of the file
+		let end = count
+			.saturating_sub(1)
+			.checked_mul(stride)
+			.and_then(|end| end.checked_add(offset))
+			.and_then(|end| end.checked_add(element_size));
+		if count > 0 && end.is_none_or(|end| end > data.len()) {
+			return Err(error("out of its buffer view"));
+		}
+
+		let mut values = Vec::with_capacity(count * components);
+		for element in 0..count {
+			for component in 0..components {
+				let start = offset + element * stride + component * size;
+				let bytes = data
+					.get(start..start + size)
+					.ok_or_else(|| error("out of its buffer view"))?;
+				let value = match component_type {
+					5120 => {
+						let v = bytes[0] as i8 as f64;
+						if normalized {
+							(v / 127.0).max(-1.0)
+						} else {
+							v
+						}
+					}
+					5121 => {
+						let v = bytes[0] as f64;
+						if normalized {
+							v / 255.0
+						} else {
+							v
+						}
+					}
+					5122 => {
+						let v = i16::from_le_bytes([bytes[0], bytes[1]]) as f64;
+						if normalized {
+							(v / 32767.0).max(-1.0)
+						} else {
+							v
+						}
+					}
+					5123 => {
+						let v = u16::from_le_bytes([bytes[0], bytes[1]]) as f64;
+						if normalized {
+							v / 65535.0
+						} else {
+							v
+						}
+					}
+					5125 => u32::from_le_bytes(bytes.try_into().unwrap()) as f64,
+					_ => f32::from_le_bytes(bytes.try_into().unwrap()) as f64,
+				};
+				values.push(value);
+			}
+		}
+		Ok((values, components))
+	}
+
+	fn image(&self, image: &json::Value, directory: &Path) -> Result<Option<png::Image>, String> {
+		let (data, mime_type) = match image.get("uri").as_str() {
+			Some(uri) => {
+				let mime_type = if uri.starts_with("data:image/png") || uri.ends_with(".png") {
+					"image/png"
+				} else {
+					""
+				};
+				(load_uri(uri, directory)?, mime_type)
+			}
+			None => {
+				let view = image
+					.get("bufferView")
+					.as_usize()
+					.ok_or("image without uri nor buffer view")?;
+				(
+					self.buffer_view(view)?.0.to_vec(),
+					image.get("mimeType").as_str().unwrap_or(""),
+				)
+			}
+		};
+		if mime_type != "image/png" {
+			eprintln!("only png images are supported, the default map is used instead");
+			return Ok(None);
+		}
+		png::decode(&data).map(Some)
+	}
+
+	fn material(&self, material: &json::Value) -> Result<Material, String> {
+		let defaults = Material::default();
+		let pbr = material.get("pbrMetallicRoughness");
+		let color = pbr.get("baseColorFactor").as_f32_array::<4>();
+		// Image of a texture info, the texture coordinates other than the first are not supported
+		let map = |info: &json::Value| {
+			info.get("index")
+				.as_usize()
+				.and_then(|t| self.document.get("textures").at(t).get("source").as_usize())
+		};
+
+		let mut maps = [None; 5];
+		for (i, kind) in MapKind::ALL.iter().enumerate() {
+			maps[i] = match kind {
+				MapKind::Albedo => map(pbr.get("baseColorTexture")),
+				MapKind::Normal => map(material.get("normalTexture")),
+				MapKind::MetallicRoughness => map(pbr.get("metallicRoughnessTexture")),
+				MapKind::Occlusion => map(material.get("occlusionTexture")),
+				MapKind::Emissive => map(material.get("emissiveTexture")),
+			};
+		}
+
+		Ok(Material {
+			albedo: color.map_or(defaults.albedo, |[r, g, b, _]| uv::Vec3::new(r, g, b)),
+			metallic: pbr
+				.get("metallicFactor")
+				.as_f32()
+				.unwrap_or(defaults.metallic),
+			roughness: pbr
+				.get("roughnessFactor")
+				.as_f32()
+				.unwrap_or(defaults.roughness),
+			emissive: material
+				.get("emissiveFactor")
+				.as_f32_array::<3>()
+				.map_or(defaults.emissive, uv::Vec3::from),
+			emissive_strength: material
+				.get("extensions")
+				.get("KHR_materials_emissive_strength")
+				.get("emissiveStrength")
+				.as_f32()
+				.unwrap_or(defaults.emissive_strength),
+			normal_scale: material
+				.get("normalTexture")
+				.get("scale")
+				.as_f32()
+				.unwrap_or(defaults.normal_scale),
+			occlusion_strength: material
+				.get("occlusionTexture")
+				.get("strength")
+				.as_f32()
+				.unwrap_or(defaults.occlusion_strength),
+			maps,
+		})
+	}
+
+	fn primitive(
+		&self,
+		primitive: &json::Value,
+		model: uv::Mat4,
+	) -> Result<Option<Primitive>, String> {
+		// Points and lines are skipped
+		if primitive.get("mode").as_usize().unwrap_or(4) != 4 {
+			return Ok(None);
+		}
+		let attributes = primitive.get("attributes");
+		let attribute = |name: &str| attributes.get(name).as_usize().map(|a| self.accessor(a));
+
+		let (positions, _) = attribute("POSITION").ok_or("primitive without positions")??;
+		let mut vertices: Vec<Vertex> = positions
+			.chunks_exact(3)
+			.map(|p| Vertex {
+				position: uv::Vec3::new(p[0] as f32, p[1] as f32, p[2] as f32),
+				..Default::default()
+			})
+			.collect();
+		let indices: Vec<u32> = match primitive.get("indices").as_usize() {
+			Some(accessor) => self
+				.accessor(accessor)?
+				.0
+				.into_iter()
+				.map(|i| i as u32)
+				.collect(),
+			None => (0..vertices.len() as u32).collect(),
+		};
+		if indices.len() < 3 {
+			return Ok(None);
+		}
+		if indices.iter().any(|&i| i as usize >= vertices.len()) {
+			return Err("vertex index out of range".to_owned());
+		}
+
+		if let Some(uvs) = attribute("TEXCOORD_0") {
+			for (vertex, uv) in vertices.iter_mut().zip(uvs?.0.chunks_exact(2)) {
+				vertex.uv = uv::Vec2::new(uv[0] as f32, uv[1] as f32);
+			}
+		}
+		match attribute("NORMAL") {
+			Some(normals) => {
+				for (vertex, n) in vertices.iter_mut().zip(normals?.0.chunks_exact(3)) {
+					vertex.normal = uv::Vec3::new(n[0] as f32, n[1] as f32, n[2] as f32);
+				}
+			}
+			None => mesh::compute_normals(&mut vertices, &indices),
+		}
+		match attribute("TANGENT") {
+			Some(tangents) => {
+				for (vertex, t) in vertices.iter_mut().zip(tangents?.0.chunks_exact(4)) {
+					vertex.tangent =
+						uv::Vec4::new(t[0] as f32, t[1] as f32, t[2] as f32, t[3] as f32);
+				}
+			}
+			None => mesh::compute_tangents(&mut vertices, Some(&indices)),
+		}
+
+		Ok(Some(Primitive {
+			vertices,
+			indices,
+			model,
+			material: primitive.get("material").as_usize(),
+		}))
+	}
+
+	fn node(
+		&self,
+		index: usize,
+		parent: uv::Mat4,
+		depth: usize,
+		scene: &mut Scene,
+	) -> Result<(), String> {
+		if depth > MAX_NODE_DEPTH {
+			return Err("node hierarchy too deep".to_owned());
+		}
+		let node = self.document.get("nodes").at(index);
+		let world = parent * node_transform(node);
+
+		if let Some(mesh) = node.get("mesh").as_usize() {
+			for primitive in self
+				.document
+				.get("meshes")
+				.at(mesh)
+				.get("primitives")
+				.elements()
+			{
+				if let Some(primitive) = self.primitive(primitive, world)? {
+					scene.primitives.push(primitive);
+				}
+			}
+		}
+
+		let position = world.transform_point3(uv::Vec3::zero());
+		let forward = world.transform_vec3(-uv::Vec3::unit_z()).normalized();
+		if let Some(camera) = node.get("camera").as_usize() {
+			let perspective = self.document.get("cameras").at(camera).get("perspective");
+			if scene.camera.is_none() && !perspective.is_null() {
+				scene.camera = Some(Camera {
+					position,
+					look_at: position + forward,
+					up: world.transform_vec3(uv::Vec3::unit_y()).normalized(),
+					fov: perspective.get("yfov").as_f32().unwrap_or(0.8),
+					near: perspective.get("znear").as_f32().unwrap_or(0.1),
+					far: perspective.get("zfar").as_f32(),
+				});
+			}
+		}
+
+		let light = node
+			.get("extensions")
+			.get("KHR_lights_punctual")
+			.get("light")
+			.as_usize();
+		if let Some(light) = light {
+			let light = self
+				.document
+				.get("extensions")
+				.get("KHR_lights_punctual")
+				.get("lights")
+				.at(light);
+			let kind = match light.get("type").as_str() {
+				Some("directional") => Some(lights::LightKind::Directional),
+				Some("point") => Some(lights::LightKind::Point),
+				Some("spot") => Some(lights::LightKind::Spot),
+				_ => None,
+			};
+			if let Some(kind) = kind {
+				let spot = light.get("spot");
+				scene.lights.push(lights::Light {
+					kind,
+					position,
+					direction: forward,
+					color: light
+						.get("color")
+						.as_f32_array::<3>()
+						.map_or(uv::Vec3::one(), uv::Vec3::from),
+					intensity: light.get("intensity").as_f32().unwrap_or(1.0),
+					// Unlimited when absent
+					range: light.get("range").as_f32().unwrap_or(f32::MAX),
+					inner_angle: spot.get("innerConeAngle").as_f32().unwrap_or(0.0),
+					outer_angle: spot
+						.get("outerConeAngle")
+						.as_f32()
+						.unwrap_or(std::f32::consts::FRAC_PI_4),
+				});
+			}
+		}
+
+		for child in node.get("children").elements() {
+			let child = child.as_usize().ok_or("invalid child node")?;
+			self.node(child, world, depth + 1, scene)?;
+		}
+		Ok(())
+	}
+}
+
+/// `.gltf` and `.glb` files of `SCENE_DIRECTORY`, sorted by name.
+pub fn find_scenes() -> Vec<PathBuf> {
+	let mut scenes: Vec<PathBuf> = match std::fs::read_dir(SCENE_DIRECTORY) {
+		Ok(entries) => entries
+			.filter_map(|entry| entry.ok().map(|e| e.path()))
+			.filter(|path| {
+				path.extension().is_some_and(|ext| {
+					ext.eq_ignore_ascii_case("gltf") || ext.eq_ignore_ascii_case("glb")
+				})
+			})
+			.collect(),
+		Err(_) => Vec::new(),
+	};
+	scenes.sort();
+	scenes
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn base64() {
+		assert_eq!(decode_base64("TWFu").unwrap(), b"Man");
+		assert_eq!(decode_base64("TWE=").unwrap(), b"Ma");
+		assert_eq!(decode_base64("TQ==").unwrap(), b"M");
+		assert_eq!(decode_base64("").unwrap(), b"");
+		// Standard and url safe alphabets
+		assert_eq!(decode_base64("+/8=").unwrap(), [0xfb, 0xff]);
+		assert_eq!(decode_base64("-_8=").unwrap(), [0xfb, 0xff]);
+		assert!(decode_base64("TW!u").is_err());
+	}
+
+	fn glb(chunks: &[(u32, &[u8])]) -> Vec<u8> {
+		let mut glb = b"glTF".to_vec();
+		glb.extend_from_slice(&2u32.to_le_bytes());
+		glb.extend_from_slice(&[0; 4]);
+		for (kind, data) in chunks {
+			glb.extend_from_slice(&(data.len() as u32).to_le_bytes());
+			glb.extend_from_slice(&kind.to_le_bytes());
+			glb.extend_from_slice(data);
+			glb.resize(glb.len().div_ceil(4) * 4, 0);
+		}
+		let length = (glb.len() as u32).to_le_bytes();
+		glb[8..12].copy_from_slice(&length);
+		glb
+	}
+
+	#[test]
+	fn glb_chunks() {
+		const JSON: u32 = 0x4e4f534a;
+		const BIN: u32 = 0x004e4942;
+		// Unknown chunks are skipped, and the lengths do not have to be aligned
+		let source = glb(&[
+			(JSON, b"{\"a\":1}"),
+			(0x12345678, b"unknown"),
+			(BIN, &[1, 2, 3, 4, 5]),
+			(BIN, &[6]),
+		]);
+		assert_eq!(
+			parse_glb(&source).unwrap(),
+			(&b"{\"a\":1}"[..], Some(&[1u8, 2, 3, 4, 5][..]))
+		);
+		assert_eq!(
+			parse_glb(&glb(&[(JSON, b"{}")])).unwrap(),
+			(&b"{}"[..], None)
+		);
+
+		assert!(parse_glb(&glb(&[(BIN, &[1])])).is_err());
+		let mut version = glb(&[(JSON, b"{}")]);
+		version[4] = 1;
+		assert!(parse_glb(&version).is_err());
+		assert!(parse_glb(&source[..source.len() - 4]).is_err());
+		assert!(parse_glb(&source[..6]).is_err());
+		// A chunk length past the end of the file
+		let mut length = glb(&[(JSON, b"{}")]);
+		length[12..16].copy_from_slice(&u32::MAX.to_le_bytes());
+		assert!(parse_glb(&length).is_err());
+	}
+
+	#[test]
+	fn accessors_stay_in_their_buffer_view() {
+		let document = json::parse(
+			r#"{
+				"bufferViews": [
+					{ "buffer": 0, "byteLength": 8 },
+					{ "buffer": 0, "byteOffset": 1e30, "byteLength": 8 },
+					{ "buffer": 0, "byteLength": 8, "byteStride": 2 }
+				],
+				"accessors": [
+					{ "bufferView": 0, "count": 2, "type": "SCALAR", "componentType": 5126 },
+					{ "bufferView": 0, "count": 3, "type": "SCALAR", "componentType": 5126 },
+					{ "bufferView": 0, "count": 1e30, "type": "MAT4", "componentType": 5126 },
+					{ "bufferView": 0, "byteOffset": 1e30, "count": 1, "type": "SCALAR",
+						"componentType": 5126 },
+					{ "bufferView": 1, "count": 1, "type": "SCALAR", "componentType": 5126 },
+					{ "bufferView": 2, "count": 2, "type": "VEC2", "componentType": 5126 },
+					{ "count": 2, "type": "VEC3", "componentType": 5126 },
+					{ "count": 1e30, "type": "VEC3", "componentType": 5126 }
+				]
+			}"#,
+		)
+		.unwrap();
+		let loader = Loader {
+			document: &document,
+			buffers: vec![[1.0f32.to_le_bytes(), 2.0f32.to_le_bytes()].concat()],
+		};
+		assert_eq!(loader.accessor(0).unwrap(), (vec![1.0, 2.0], 1));
+		assert_eq!(loader.accessor(6).unwrap(), (vec![0.0; 6], 3));
+		for index in [1, 2, 3, 4, 5, 7] {
+			assert!(loader.accessor(index).is_err(), "accessor {}", index);
+		}
+	}
+
+	fn assert_mat4_eq(a: uv::Mat4, b: uv::Mat4) {
+		for (a, b) in a.as_array().iter().zip(b.as_array()) {
+			assert!((a - b).abs() < 1e-6, "{:?} != {:?}", a, b);
+		}
+	}
+
+	#[test]
+	fn node_transforms() {
+		assert_mat4_eq(
+			node_transform(&json::parse("{}").unwrap()),
+			uv::Mat4::identity(),
+		);
+
+		// Scaled, then rotated by 90 degrees around Z, then translated
+		let half = std::f32::consts::FRAC_1_SQRT_2;
+		let node = json::parse(&format!(
+			r#"{{ "translation": [1, 2, 3], "rotation": [0, 0, {0}, {0}], "scale": [2, 3, 4] }}"#,
+			half
+		))
+		.unwrap();
+		let transform = node_transform(&node);
+		let point = transform.transform_point3(uv::Vec3::new(1.0, 1.0, 1.0));
+		assert!(
+			(point - uv::Vec3::new(-2.0, 4.0, 7.0)).mag() < 1e-5,
+			"{:?}",
+			point
+		);
+
+		// The same matrix, column major
+		let node = json::parse(
+			r#"{ "matrix": [0, 2, 0, 0, -3, 0, 0, 0, 0, 0, 4, 0, 1, 2, 3, 1],
+				"translation": [9, 9, 9] }"#,
+		)
+		.unwrap();
+		assert_mat4_eq(node_transform(&node), transform);
+	}
+}
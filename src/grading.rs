@@ -0,0 +1,262 @@
+use std::path::{Path, PathBuf};
+
+use crate::renderer::texture;
+
+/// Directory scanned for `.cube` files at startup.
+pub const LUT_DIRECTORY: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/assets/luts");
+
+/// 3D color grading LUT in the Adobe `.cube` format.
+pub struct CubeLut {
+	pub size: u32,
+	pub domain_min: uv::Vec3,
+	pub domain_max: uv::Vec3,
+	// Red varies the fastest, then green, then blue
+	pub table: Vec<uv::Vec3>,
+}
+
+impl CubeLut {
+	/// A 2x2x2 identity LUT, exact under trilinear filtering.
+	pub fn identity() -> Self {
+		let mut table = Vec::with_capacity(8);
+		for b in 0..2 {
+			for g in 0..2 {
+				for r in 0..2 {
+					table.push(uv::Vec3::new(r as f32, g as f32, b as f32));
+				}
+			}
+		}
+		CubeLut {
+			size: 2,
+			domain_min: uv::Vec3::zero(),
+			domain_max: uv::Vec3::one(),
+			table,
+		}
+	}
+
+	pub fn load(path: &Path) -> Result<Self, String> {
+		let source =
+			std::fs::read_to_string(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+		Self::parse(&source).map_err(|e| format!("{}: {}", path.display(), e))
+	}
+
+	pub fn parse(source: &str) -> Result<Self, String> {
+		let mut size = None;
+		let mut domain_min = uv::Vec3::zero();
+		let mut domain_max = uv::Vec3::one();
+		let mut table = Vec::new();
+
+		for (i, line) in source.lines().enumerate() {
+			let line_number = i + 1;
+			let line = line.split('#').next().unwrap().trim();
+			if line.is_empty() {
+				continue;
+			}
+
+			let mut tokens = line.split_whitespace();
+			let keyword = tokens.next().unwrap();
+			match keyword {
+				"LUT_3D_SIZE" => {
+					let [n] = parse_floats::<1>(tokens, line_number)?;
+					if n.fract() != 0.0 || !(2.0..=256.0).contains(&n) {
+						return Err(format!("line {}: invalid LUT_3D_SIZE {}", line_number, n));
+					}
+					size = Some(n as u32);
+				}
+				"LUT_1D_SIZE" => {
+					return Err(format!("line {}: 1D LUTs are not supported", line_number));
+				}
+				"DOMAIN_MIN" => {
+					domain_min = parse_floats::<3>(tokens, line_number)?.into();
+				}
+				"DOMAIN_MAX" => {
+					domain_max = parse_floats::<3>(tokens, line_number)?.into();
+				}
+				// Resolve specific form of the domain
+				"LUT_3D_INPUT_RANGE" => {
+					let [min, max] = parse_floats::<2>(tokens, line_number)?;
+					domain_min = uv::Vec3::broadcast(min);
+					domain_max = uv::Vec3::broadcast(max);
+				}
+				// TITLE and unknown keywords are allowed by the specification
+				_ if keyword.starts_with(|c: char| c.is_ascii_alphabetic()) => {}
+				_ => {
+					if size.is_none() {
+						return Err(format!(
+							"line {}: table data before LUT_3D_SIZE",
+							line_number
+						));
+					}
+					let values = parse_floats::<3>(line.split_whitespace(), line_number)?;
+					table.push(values.into());
+				}
+			}
+		}
+
+		let size = size.ok_or_else(|| "missing LUT_3D_SIZE".to_owned())?;
+		let expected = (size * size * size) as usize;
+		if table.len() != expected {
+			return Err(format!(
+				"expected {} table entries, found {}",
+				expected,
+				table.len()
+			));
+		}
+		if domain_max.x <= domain_min.x
+			|| domain_max.y <= domain_min.y
+			|| domain_max.z <= domain_min.z
+		{
+			return Err("DOMAIN_MAX has to be greater than DOMAIN_MIN".to_owned());
+		}
+
+		Ok(CubeLut {
+			size,
+			domain_min,
+			domain_max,
+			table,
+		})
+	}
+
+	pub fn create_texture(&self, device: &wgpu::Device, queue: &wgpu::Queue) -> texture::Texture {
+		let lut_texture = texture::Texture::new(
+			device,
+			Some("grading lut"),
+			self.size,
+			self.size,
+			self.size,
+			1,
+			1,
+			wgpu::TextureDimension::D3,
+			wgpu::TextureFormat::Rgba16Float,
+			wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+			wgpu::TextureAspect::All,
+		);
+
+		let data: Vec<u16> = self
+			.table
+			.iter()
+			.flat_map(|c| [c.x, c.y, c.z, 1.0])
+			.map(texture::f32_to_f16)
+			.collect();
+
+		queue.write_texture(
+			wgpu::ImageCopyTexture {
+				texture: &lut_texture.texture,
+				mip_level: 0,
+				origin: wgpu::Origin3d::ZERO,
+				aspect: wgpu::TextureAspect::All,
+			},
+			unsafe { data.align_to::<u8>().1 },
+			wgpu::ImageDataLayout {
+				offset: 0,
+				bytes_per_row: std::num::NonZeroU32::new(self.size * 4 * 2),
+				rows_per_image: std::num::NonZeroU32::new(self.size),
+			},
+			lut_texture.size,
+		);
+
+		lut_texture
+	}
+}
+
+fn parse_floats<'a, const N: usize>(
+	mut tokens: impl Iterator<Item = &'a str>,
+	line_number: usize,
+) -> Result<[f32; N], String> {
+	let mut values = [0.0; N];
+	for value in values.iter_mut() {
+		let token = tokens
+			.next()
+			.ok_or_else(|| format!("line {}: expected {} values", line_number, N))?;
+		*value = token
+			.parse()
+			.map_err(|_| format!("line {}: invalid number {:?}", line_number, token))?;
+	}
+	if tokens.next().is_some() {
+		return Err(format!("line {}: expected {} values", line_number, N));
+	}
+	Ok(values)
+}
+
+/// `.cube` files of `LUT_DIRECTORY`, sorted by name.
+pub fn find_luts() -> Vec<PathBuf> {
+	let mut luts: Vec<PathBuf> = match std::fs::read_dir(LUT_DIRECTORY) {
+		Ok(entries) => entries
+			.filter_map(|entry| entry.ok().map(|e| e.path()))
+			.filter(|path| {
+				path.extension()
+					.is_some_and(|ext| ext.eq_ignore_ascii_case("cube"))
+			})
+			.collect(),
+		Err(_) => Vec::new(),
+	};
+	luts.sort();
+	luts
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn cube_with_domain() {
+		let lut = CubeLut::parse(
+			"# comment
+			TITLE \"test\"
+			LUT_3D_SIZE 2
+			DOMAIN_MIN 0 0 -1
+			DOMAIN_MAX 1 2 1
+
+			0 0 0
+			1 0 0 # red varies the fastest
+			0 1 0
+			1 1 0
+			0 0 1
+			1 0 1
+			0 1 1
+			1 1 1",
+		)
+		.unwrap();
+		assert_eq!(lut.size, 2);
+		assert_eq!(*lut.domain_min.as_array(), [0.0, 0.0, -1.0]);
+		assert_eq!(*lut.domain_max.as_array(), [1.0, 2.0, 1.0]);
+		assert_eq!(lut.table.len(), 8);
+		assert_eq!(*lut.table[1].as_array(), [1.0, 0.0, 0.0]);
+		assert_eq!(*lut.table[6].as_array(), [0.0, 1.0, 1.0]);
+
+		let identity = CubeLut::identity();
+		assert_eq!(lut.table, identity.table);
+		assert_eq!(*identity.domain_max.as_array(), [1.0; 3]);
+	}
+
+	#[test]
+	fn input_range_sets_the_domain() {
+		let table = "0 0 0\n".repeat(8);
+		let lut = CubeLut::parse(&format!(
+			"LUT_3D_INPUT_RANGE -0.5 2\nLUT_3D_SIZE 2\n{}",
+			table
+		))
+		.unwrap();
+		assert_eq!(*lut.domain_min.as_array(), [-0.5; 3]);
+		assert_eq!(*lut.domain_max.as_array(), [2.0; 3]);
+	}
+
+	#[test]
+	fn invalid_cubes_are_rejected() {
+		let table = "0 0 0\n".repeat(8);
+		for source in [
+			table.clone(),
+			format!("{}LUT_3D_SIZE 2", table),
+			format!("LUT_3D_SIZE 2\n{}0 0 0", table),
+			format!("LUT_3D_SIZE 2\n{}", "0 0 0\n".repeat(7)),
+			format!("LUT_3D_SIZE 2.5\n{}", table),
+			format!("LUT_3D_SIZE 1\n{}", "0 0 0\n"),
+			format!("LUT_3D_SIZE 2 2\n{}", table),
+			format!("LUT_3D_SIZE 2\n{}0 0", "0 0 0\n".repeat(7)),
+			format!("LUT_1D_SIZE 2\n{}", table),
+			format!("LUT_3D_SIZE 2\nDOMAIN_MIN 0 0 1\n{}", table),
+			format!("LUT_3D_SIZE 2\nDOMAIN_MAX 1 1\n{}", table),
+		] {
+			assert!(CubeLut::parse(&source).is_err(), "{}", source);
+		}
+	}
+}
@@ -0,0 +1,43 @@
+use crate::bloom::PbrParam;
+use crate::pbr_textures::PbrTextures;
+use crate::renderer::{mesh, Renderer};
+use crate::scene;
+
+/// Half of the side of the ground plane.
+const GROUND_EXTENT: f32 = 8.0;
+/// Height of the ground plane, under the unit sphere.
+const GROUND_HEIGHT: f32 = -1.0;
+
+/// Material of the ground, the camera position and environment intensity follow the sphere.
+pub fn ground_param(cam_pos: uv::Vec3, environment_intensity: f32) -> PbrParam {
+	PbrParam {
+		cam_pos,
+		metallic: 0.0,
+		albedo: uv::Vec3::broadcast(0.5),
+		roughness: 0.8,
+		emissive_color: uv::Vec3::zero(),
+		ao: 1.0,
+		emissive_intensity: 0.0,
+		environment_intensity,
+		normal_scale: 1.0,
+		occlusion_strength: 1.0,
+	}
+}
+
+/// Plane under the sphere to receive its shadow, drawn with the pipeline of `pbr_mesh`.
+pub fn init_ground(renderer: &Renderer, pbr_mesh: &mesh::Mesh) -> mesh::Mesh {
+	let device = &renderer.context.device;
+	let (vertices, indices) = mesh::primitives::plane::<u32>(GROUND_EXTENT * 2.0, 1);
+	let mut ground_mesh =
+		mesh::Mesh::new(device, Some("groundMesh"), &vertices, Some(&indices), None);
+	ground_mesh.model = uv::Mat4::from_translation(uv::Vec3::unit_y() * GROUND_HEIGHT);
+	ground_mesh.material = Some(scene::create_object_material(
+		renderer,
+		pbr_mesh.material.as_ref().unwrap(),
+		"ground",
+		ground_mesh.model,
+		&ground_param(renderer.camera.position, 1.0),
+		&PbrTextures::default_maps(device, &renderer.context.queue),
+	));
+	ground_mesh
+}
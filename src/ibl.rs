@@ -0,0 +1,633 @@
+use std::path::{Path, PathBuf};
+
+use crate::renderer::{
+	mesh::Material,
+	texture::{self, Texture},
+};
+
+/// Directory scanned for `.hdr` environment maps at startup.
+pub const ENVIRONMENT_DIRECTORY: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/assets/environments");
+
+const ENVIRONMENT_SIZE: u32 = 512;
+const IRRADIANCE_SIZE: u32 = 32;
+const PREFILTERED_SIZE: u32 = 128;
+/// Roughness 0 to 1 in steps of 0.25, must match PREFILTERED_MAX_LOD of pbr.wgsl.
+const PREFILTERED_MIP_COUNT: u32 = 5;
+const BRDF_LUT_SIZE: u32 = 256;
+
+/// Equirectangular environment map, in linear radiance.
+pub struct EquirectMap {
+	pub width: u32,
+	pub height: u32,
+	// Rows from the top, the top row looks up
+	pub pixels: Vec<uv::Vec3>,
+}
+
+impl EquirectMap {
+	/// Clear sky with a sun bright enough to bloom, used when no environment map is selected.
+	pub fn procedural_sky() -> Self {
+		let (width, height) = (1024, 512);
+		let sun = uv::Vec3::new(0.3, 0.25, 1.0).normalized();
+		let sun_cos = 1.5f32.to_radians().cos();
+		let zenith = uv::Vec3::new(0.15, 0.3, 0.75);
+		let horizon = uv::Vec3::new(0.7, 0.8, 0.95);
+		let ground = uv::Vec3::new(0.12, 0.1, 0.08);
+
+		let mut pixels = Vec::with_capacity((width * height) as usize);
+		for y in 0..height {
+			let theta = (y as f32 + 0.5) / height as f32 * std::f32::consts::PI;
+			for x in 0..width {
+				let phi = ((x as f32 + 0.5) / width as f32 - 0.5) * std::f32::consts::TAU;
+				let dir = uv::Vec3::new(
+					theta.sin() * phi.cos(),
+					theta.cos(),
+					theta.sin() * phi.sin(),
+				);
+				let mut color = if dir.y >= 0.0 {
+					horizon + (zenith - horizon) * dir.y.sqrt()
+				} else {
+					ground + (horizon - ground) * (1.0 + dir.y * 8.0).max(0.0)
+				};
+				if dir.dot(sun) > sun_cos {
+					color = uv::Vec3::new(2000.0, 1800.0, 1500.0);
+				}
+				pixels.push(color);
+			}
+		}
+
+		EquirectMap {
+			width,
+			height,
+			pixels,
+		}
+	}
+
+	pub fn load(path: &Path) -> Result<Self, String> {
+		let source = std::fs::read(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+		Self::parse(&source).map_err(|e| format!("{}: {}", path.display(), e))
+	}
+
+	/// Radiance `.hdr` (RGBE) image, flat or with the run length encoded scanlines of the
+	/// format. Only the usual `-Y height +X width` orientation is supported.
+	pub fn parse(source: &[u8]) -> Result<Self, String> {
+		let mut offset = 0;
+		let mut next_line = || {
+			let rest = &source[offset.min(source.len())..];
+			let end = rest
+				.iter()
+				.position(|&b| b == b'\n')
+				.ok_or("unexpected end of the header")?;
+			offset += end + 1;
+			Ok::<_, String>(String::from_utf8_lossy(&rest[..end]).trim().to_owned())
+		};
+
+		let magic = next_line()?;
+		if magic != "#?RADIANCE" && magic != "#?RGBE" {
+			return Err("not a Radiance HDR file".to_owned());
+		}
+		loop {
+			let line = next_line()?;
+			if line.is_empty() {
+				break;
+			}
+			if let Some(format) = line.strip_prefix("FORMAT=") {
+				if format != "32-bit_rle_rgbe" {
+					return Err(format!("unsupported format {}", format));
+				}
+			}
+		}
+		let resolution = next_line()?;
+		let (height, width) = match resolution.split_whitespace().collect::<Vec<_>>()[..] {
+			["-Y", height, "+X", width] => (
+				height.parse::<u32>().map_err(|e| e.to_string())?,
+				width.parse::<u32>().map_err(|e| e.to_string())?,
+			),
+			_ => return Err(format!("unsupported resolution line {}", resolution)),
+		};
+		if width == 0 || height == 0 {
+			return Err("empty image".to_owned());
+		}
+
+		let mut data = source[offset.min(source.len())..].iter().copied();
+		let mut next = || {
+			data.next()
+				.ok_or_else(|| "unexpected end of the data".to_owned())
+		};
+		let mut pixels = Vec::with_capacity((width * height) as usize);
+		let mut scanline = vec![[0u8; 4]; width as usize];
+		for _ in 0..height {
+			let first = [next()?, next()?, next()?, next()?];
+			let rle = (8..0x8000).contains(&width)
+				&& first[0] == 2
+				&& first[1] == 2
+				&& ((first[2] as u32) << 8 | first[3] as u32) == width;
+			if rle {
+				// Each channel of the scanline is run length encoded separately
+				for channel in 0..4 {
+					let mut x = 0;
+					while x < scanline.len() {
+						let count = next()? as usize;
+						let (count, run) = if count > 128 {
+							(count - 128, Some(next()?))
+						} else {
+							(count, None)
+						};
+						if count == 0 || x + count > scanline.len() {
+							return Err("invalid scanline".to_owned());
+						}
+						for pixel in &mut scanline[x..x + count] {
+							pixel[channel] = match run {
+								Some(value) => value,
+								None => next()?,
+							};
+						}
+						x += count;
+					}
+				}
+			} else {
+				scanline[0] = first;
+				for pixel in &mut scanline[1..] {
+					*pixel = [next()?, next()?, next()?, next()?];
+				}
+			}
+			pixels.extend(scanline.iter().map(|&[r, g, b, e]| {
+				if e == 0 {
+					uv::Vec3::zero()
+				} else {
+					let scale = 2f32.powi(e as i32 - (128 + 8));
+					uv::Vec3::new(r as f32 + 0.5, g as f32 + 0.5, b as f32 + 0.5) * scale
+				}
+			}));
+		}
+
+		Ok(EquirectMap {
+			width,
+			height,
+			pixels,
+		})
+	}
+
+	fn create_texture(&self, device: &wgpu::Device, queue: &wgpu::Queue) -> Texture {
+		let texture = Texture::new(
+			device,
+			Some("equirect environment"),
+			self.width,
+			self.height,
+			1,
+			1,
+			1,
+			wgpu::TextureDimension::D2,
+			wgpu::TextureFormat::Rgba16Float,
+			wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+			wgpu::TextureAspect::All,
+		);
+
+		let data = self
+			.pixels
+			.iter()
+			.flat_map(|c| {
+				[
+					texture::f32_to_f16(c.x),
+					texture::f32_to_f16(c.y),
+					texture::f32_to_f16(c.z),
+					texture::f32_to_f16(1.0),
+				]
+			})
+			.collect::<Vec<u16>>();
+		queue.write_texture(
+			wgpu::ImageCopyTexture {
+				texture: &texture.texture,
+				mip_level: 0,
+				origin: wgpu::Origin3d::ZERO,
+				aspect: wgpu::TextureAspect::All,
+			},
+			unsafe { data.align_to::<u8>().1 },
+			wgpu::ImageDataLayout {
+				offset: 0,
+				bytes_per_row: std::num::NonZeroU32::new(self.width * 8),
+				rows_per_image: None,
+			},
+			texture.size,
+		);
+
+		texture
+	}
+}
+
+/// Cubemap written by compute shaders, `view` is the cube and `mip_view` the faces of each mip
+/// as an array.
+fn create_cube_texture(device: &wgpu::Device, label: &str, size: u32, mip_count: u32) -> Texture {
+	let mut texture = Texture::new(
+		device,
+		Some(label),
+		size,
+		size,
+		6,
+		mip_count,
+		1,
+		wgpu::TextureDimension::D2,
+		wgpu::TextureFormat::Rgba16Float,
+		wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::STORAGE_BINDING,
+		wgpu::TextureAspect::All,
+	);
+	texture.view = texture.texture.create_view(&wgpu::TextureViewDescriptor {
+		label: Some(label),
+		dimension: Some(wgpu::TextureViewDimension::Cube),
+		..Default::default()
+	});
+	texture.mip_view = (0..mip_count)
+		.map(|mip| {
+			texture.texture.create_view(&wgpu::TextureViewDescriptor {
+				label: Some(label),
+				dimension: Some(wgpu::TextureViewDimension::D2Array),
+				base_mip_level: mip,
+				mip_level_count: std::num::NonZeroU32::new(1),
+				..Default::default()
+			})
+		})
+		.collect();
+	texture
+}
+
+fn texture_entry(
+	binding: u32,
+	view_dimension: wgpu::TextureViewDimension,
+	visibility: wgpu::ShaderStages,
+) -> wgpu::BindGroupLayoutEntry {
+	wgpu::BindGroupLayoutEntry {
+		binding,
+		visibility,
+		ty: wgpu::BindingType::Texture {
+			sample_type: wgpu::TextureSampleType::Float { filterable: true },
+			view_dimension,
+			multisampled: false,
+		},
+		count: None,
+	}
+}
+
+fn sampler_entry(binding: u32, visibility: wgpu::ShaderStages) -> wgpu::BindGroupLayoutEntry {
+	wgpu::BindGroupLayoutEntry {
+		binding,
+		visibility,
+		ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+		count: None,
+	}
+}
+
+fn storage_entry(
+	binding: u32,
+	view_dimension: wgpu::TextureViewDimension,
+) -> wgpu::BindGroupLayoutEntry {
+	wgpu::BindGroupLayoutEntry {
+		binding,
+		visibility: wgpu::ShaderStages::COMPUTE,
+		ty: wgpu::BindingType::StorageTexture {
+			access: wgpu::StorageTextureAccess::WriteOnly,
+			format: wgpu::TextureFormat::Rgba16Float,
+			view_dimension,
+		},
+		count: None,
+	}
+}
+
+fn create_compute_material(
+	device: &wgpu::Device,
+	label: &str,
+	entries: Vec<wgpu::BindGroupLayoutEntry>,
+	shader: wgpu::ShaderModuleDescriptor,
+	push_constant_size: u32,
+) -> Material {
+	let mut mat = Material::new(1, 1);
+	mat.add_bind_group(device, Some(label), entries, vec![]);
+	if push_constant_size > 0 {
+		mat.add_push_constant(wgpu::ShaderStages::COMPUTE, 0..push_constant_size);
+	}
+	mat.set_compute_pipeline(device, Some(label), shader);
+	mat
+}
+
+/// Dispatches one thread per texel of every face of `size`.
+fn dispatch(
+	device: &wgpu::Device,
+	encoder: &mut wgpu::CommandEncoder,
+	mat: &Material,
+	resources: Vec<wgpu::BindingResource>,
+	push_constant: Option<u32>,
+	size: u32,
+	layers: u32,
+) {
+	let entries = resources
+		.into_iter()
+		.enumerate()
+		.map(|(i, resource)| wgpu::BindGroupEntry {
+			binding: i as u32,
+			resource,
+		})
+		.collect::<Vec<_>>();
+	let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+		label: Some("ibl bind group"),
+		layout: &mat.bind_group_layout[0],
+		entries: &entries,
+	});
+
+	let mut compute_pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+		label: Some("ibl compute pass"),
+	});
+	compute_pass.set_pipeline(mat.compute_pipeline.as_ref().unwrap());
+	compute_pass.set_bind_group(0, &bind_group, &[]);
+	if let Some(value) = push_constant {
+		compute_pass.set_push_constants(0, &value.to_ne_bytes());
+	}
+	let groups = size / 8 + 1;
+	compute_pass.dispatch(groups, groups, layers);
+}
+
+/// Image based lighting of the pbr pass, precomputed from an equirectangular map: the
+/// environment cubemap, its diffuse irradiance, its GGX prefiltered radiance for increasing
+/// roughness and the integrated BRDF of the split sum approximation.
+pub struct Environment {
+	pub cubemap: Texture,
+	pub irradiance: Texture,
+	pub prefiltered: Texture,
+	pub brdf_lut: Texture,
+}
+
+impl Environment {
+	pub fn new(device: &wgpu::Device, queue: &wgpu::Queue, map: &EquirectMap) -> Self {
+		let compute = wgpu::ShaderStages::COMPUTE;
+		let equirect = map.create_texture(device, queue);
+
+		let environment_mip_count = 32 - ENVIRONMENT_SIZE.leading_zeros();
+		let mut cubemap = create_cube_texture(
+			device,
+			"environment cubemap",
+			ENVIRONMENT_SIZE,
+			environment_mip_count,
+		);
+		cubemap.set_sampler(
+			device,
+			Some("environment sampler"),
+			wgpu::AddressMode::ClampToEdge,
+			wgpu::FilterMode::Linear,
+			wgpu::FilterMode::Linear,
+			wgpu::FilterMode::Linear,
+			None,
+			None,
+			None,
+			None,
+			None,
+		);
+		let irradiance = create_cube_texture(device, "irradiance cubemap", IRRADIANCE_SIZE, 1);
+		let prefiltered = create_cube_texture(
+			device,
+			"prefiltered cubemap",
+			PREFILTERED_SIZE,
+			PREFILTERED_MIP_COUNT,
+		);
+		let brdf_lut = Texture::new(
+			device,
+			Some("brdf lut"),
+			BRDF_LUT_SIZE,
+			BRDF_LUT_SIZE,
+			1,
+			1,
+			1,
+			wgpu::TextureDimension::D2,
+			wgpu::TextureFormat::Rgba16Float,
+			wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::STORAGE_BINDING,
+			wgpu::TextureAspect::All,
+		);
+		let sampler = cubemap.sampler.as_ref().unwrap();
+
+		let equirect_mat = create_compute_material(
+			device,
+			"ibl equirect",
+			vec![
+				texture_entry(0, wgpu::TextureViewDimension::D2, compute),
+				sampler_entry(1, compute),
+				storage_entry(2, wgpu::TextureViewDimension::D2Array),
+			],
+			wgpu::include_wgsl!("../assets/shaders/ibl_equirect.wgsl"),
+			4,
+		);
+		let irradiance_mat = create_compute_material(
+			device,
+			"ibl irradiance",
+			vec![
+				texture_entry(0, wgpu::TextureViewDimension::Cube, compute),
+				sampler_entry(1, compute),
+				storage_entry(2, wgpu::TextureViewDimension::D2Array),
+			],
+			wgpu::include_wgsl!("../assets/shaders/ibl_irradiance.wgsl"),
+			0,
+		);
+		let prefilter_mat = create_compute_material(
+			device,
+			"ibl prefilter",
+			vec![
+				texture_entry(0, wgpu::TextureViewDimension::Cube, compute),
+				sampler_entry(1, compute),
+				storage_entry(2, wgpu::TextureViewDimension::D2Array),
+			],
+			wgpu::include_wgsl!("../assets/shaders/ibl_prefilter.wgsl"),
+			4,
+		);
+		let brdf_mat = create_compute_material(
+			device,
+			"ibl brdf",
+			vec![storage_entry(0, wgpu::TextureViewDimension::D2)],
+			wgpu::include_wgsl!("../assets/shaders/ibl_brdf.wgsl"),
+			0,
+		);
+
+		let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+			label: Some("ibl Encoder"),
+		});
+		// Every mip is converted from the equirect map, averaging the texels it covers
+		for mip in 0..environment_mip_count {
+			let size = ENVIRONMENT_SIZE >> mip;
+			let samples = (map.width / 4 / size).clamp(1, 16);
+			dispatch(
+				device,
+				&mut encoder,
+				&equirect_mat,
+				vec![
+					wgpu::BindingResource::TextureView(&equirect.view),
+					wgpu::BindingResource::Sampler(sampler),
+					wgpu::BindingResource::TextureView(&cubemap.mip_view[mip as usize]),
+				],
+				Some(samples),
+				size,
+				6,
+			);
+		}
+		dispatch(
+			device,
+			&mut encoder,
+			&irradiance_mat,
+			vec![
+				wgpu::BindingResource::TextureView(&cubemap.view),
+				wgpu::BindingResource::Sampler(sampler),
+				wgpu::BindingResource::TextureView(&irradiance.mip_view[0]),
+			],
+			None,
+			IRRADIANCE_SIZE,
+			6,
+		);
+		for mip in 0..PREFILTERED_MIP_COUNT {
+			let roughness = mip as f32 / (PREFILTERED_MIP_COUNT - 1) as f32;
+			dispatch(
+				device,
+				&mut encoder,
+				&prefilter_mat,
+				vec![
+					wgpu::BindingResource::TextureView(&cubemap.view),
+					wgpu::BindingResource::Sampler(sampler),
+					wgpu::BindingResource::TextureView(&prefiltered.mip_view[mip as usize]),
+				],
+				Some(roughness.to_bits()),
+				PREFILTERED_SIZE >> mip,
+				6,
+			);
+		}
+		dispatch(
+			device,
+			&mut encoder,
+			&brdf_mat,
+			vec![wgpu::BindingResource::TextureView(&brdf_lut.view)],
+			None,
+			BRDF_LUT_SIZE,
+			1,
+		);
+		queue.submit(std::iter::once(encoder.finish()));
+
+		Environment {
+			cubemap,
+			irradiance,
+			prefiltered,
+			brdf_lut,
+		}
+	}
+
+	/// Layout of the environment bind group of the pbr pass.
+	pub fn pbr_layout_entries() -> Vec<wgpu::BindGroupLayoutEntry> {
+		let fragment = wgpu::ShaderStages::FRAGMENT;
+		vec![
+			texture_entry(0, wgpu::TextureViewDimension::Cube, fragment),
+			texture_entry(1, wgpu::TextureViewDimension::Cube, fragment),
+			texture_entry(2, wgpu::TextureViewDimension::D2, fragment),
+			sampler_entry(3, fragment),
+		]
+	}
+
+	pub fn pbr_bindings(&self) -> Vec<wgpu::BindingResource<'_>> {
+		vec![
+			wgpu::BindingResource::TextureView(&self.irradiance.view),
+			wgpu::BindingResource::TextureView(&self.prefiltered.view),
+			wgpu::BindingResource::TextureView(&self.brdf_lut.view),
+			wgpu::BindingResource::Sampler(self.cubemap.sampler.as_ref().unwrap()),
+		]
+	}
+}
+
+/// `.hdr` files of `ENVIRONMENT_DIRECTORY`, sorted by name.
+pub fn find_environments() -> Vec<PathBuf> {
+	let mut maps: Vec<PathBuf> = match std::fs::read_dir(ENVIRONMENT_DIRECTORY) {
+		Ok(entries) => entries
+			.filter_map(|entry| entry.ok().map(|e| e.path()))
+			.filter(|path| {
+				path.extension()
+					.is_some_and(|ext| ext.eq_ignore_ascii_case("hdr"))
+			})
+			.collect(),
+		Err(_) => Vec::new(),
+	};
+	maps.sort();
+	maps
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn hdr(height: u32, width: u32, data: &[u8]) -> Vec<u8> {
+		let mut hdr = format!(
+			"#?RADIANCE\n# comment\nFORMAT=32-bit_rle_rgbe\n\n-Y {} +X {}\n",
+			height, width
+		)
+		.into_bytes();
+		hdr.extend_from_slice(data);
+		hdr
+	}
+
+	/// Header of a run length encoded scanline of 8 pixels.
+	const RLE: [u8; 4] = [2, 2, 0, 8];
+
+	fn assert_pixel(pixel: uv::Vec3, expected: [f32; 3]) {
+		assert!(
+			(pixel - uv::Vec3::from(expected)).mag() < 1e-6,
+			"{:?} != {:?}",
+			pixel,
+			expected
+		);
+	}
+
+	#[test]
+	fn flat_and_rle_scanlines() {
+		let mut data = Vec::new();
+		// Flat, a unit exponent of 128 scales the mantissas by 1/256
+		for x in 0..8 {
+			data.extend_from_slice(&[x * 16, 128, 255, if x == 7 { 0 } else { 128 }]);
+		}
+		// Runs of 8, 8 literals, then a run of 4 and 4 literals
+		data.extend_from_slice(&RLE);
+		data.extend_from_slice(&[128 + 8, 64]);
+		data.extend_from_slice(&[8, 0, 1, 2, 3, 4, 5, 6, 7]);
+		data.extend_from_slice(&[128 + 4, 100, 4, 10, 20, 30, 40]);
+		data.extend_from_slice(&[128 + 8, 129]);
+
+		let map = EquirectMap::parse(&hdr(2, 8, &data)).unwrap();
+		assert_eq!((map.width, map.height, map.pixels.len()), (8, 2, 16));
+		assert_pixel(map.pixels[1], [16.5 / 256.0, 128.5 / 256.0, 255.5 / 256.0]);
+		assert_pixel(map.pixels[7], [0.0; 3]);
+		assert_pixel(map.pixels[8], [64.5 / 128.0, 0.5 / 128.0, 100.5 / 128.0]);
+		assert_pixel(map.pixels[14], [64.5 / 128.0, 6.5 / 128.0, 30.5 / 128.0]);
+	}
+
+	#[test]
+	fn malformed_runs_are_rejected() {
+		let rest = [128 + 8, 1, 128 + 8, 1, 128 + 8, 1];
+		for channel in [
+			&[128 + 9, 1][..],       // longer than the scanline
+			&[0, 128 + 8, 1][..],    // empty
+			&[4, 1, 2, 3, 4, 5][..], // literals past the scanline
+		] {
+			let mut data = RLE.to_vec();
+			data.extend_from_slice(channel);
+			data.extend_from_slice(&rest);
+			assert!(EquirectMap::parse(&hdr(1, 8, &data)).is_err());
+		}
+
+		let mut data = RLE.to_vec();
+		data.extend_from_slice(&rest);
+		assert!(
+			EquirectMap::parse(&hdr(1, 8, &data)).is_err(),
+			"missing channel"
+		);
+		data.extend_from_slice(&[128 + 8, 1]);
+		assert!(EquirectMap::parse(&hdr(1, 8, &data)).is_ok());
+		assert!(
+			EquirectMap::parse(&hdr(2, 8, &data)).is_err(),
+			"missing scanline"
+		);
+	}
+
+	#[test]
+	fn unsupported_headers_are_rejected() {
+		assert!(EquirectMap::parse(b"P6\n").is_err());
+		assert!(EquirectMap::parse(b"#?RADIANCE\nFORMAT=32-bit_rle_xyze\n\n-Y 1 +X 1\n").is_err());
+		assert!(EquirectMap::parse(b"#?RADIANCE\n\n+Y 1 +X 1\n\0\0\0\0").is_err());
+		assert!(EquirectMap::parse(b"#?RADIANCE\n\n-Y 1 +X 1\n\0\0\0\0").is_ok());
+	}
+}
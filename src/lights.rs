@@ -0,0 +1,108 @@
+extern crate ultraviolet as uv;
+
+/// Size of the light array of pbr.wgsl.
+pub const MAX_LIGHTS: usize = 16;
+
+// Must match the LIGHT_* constants of pbr.wgsl
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LightKind {
+	Point,
+	Directional,
+	Spot,
+}
+
+impl LightKind {
+	pub const ALL: [LightKind; 3] = [LightKind::Point, LightKind::Directional, LightKind::Spot];
+
+	pub fn name(self) -> &'static str {
+		match self {
+			LightKind::Point => "Point",
+			LightKind::Directional => "Directional",
+			LightKind::Spot => "Spot",
+		}
+	}
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct Light {
+	pub kind: LightKind,
+	pub position: uv::Vec3,  // unused by directional lights
+	pub direction: uv::Vec3, // the light points toward it, unused by point lights
+	pub color: uv::Vec3,
+	pub intensity: f32,
+	pub range: f32,       // distance at which point and spot lights fade out
+	pub inner_angle: f32, // half angle of the full intensity cone of spot lights, in radians
+	pub outer_angle: f32, // half angle past which spot lights do not light, in radians
+}
+
+impl Default for Light {
+	fn default() -> Self {
+		Light {
+			kind: LightKind::Point,
+			position: uv::Vec3::new(-4.0, 5.0, -5.0),
+			direction: uv::Vec3::new(4.0, -5.0, 5.0).normalized(),
+			color: uv::Vec3::one(),
+			intensity: 25.0,
+			range: 50.0,
+			inner_angle: 20.0f32.to_radians(),
+			outer_angle: 30.0f32.to_radians(),
+		}
+	}
+}
+
+#[repr(C, align(16))]
+#[derive(Clone, Copy, Default)]
+pub struct GpuLight {
+	pub position: uv::Vec3,
+	pub kind: u32,
+	pub direction: uv::Vec3,
+	pub range: f32,
+	pub color: uv::Vec3, // multiplied by the intensity
+	pub spot_scale: f32, // the cone attenuation is saturate(cos * spot_scale + spot_offset)
+	pub spot_offset: f32,
+}
+
+impl Light {
+	fn to_gpu(self) -> GpuLight {
+		let cos_outer = self.outer_angle.cos();
+		let cos_inner = self.inner_angle.min(self.outer_angle).cos();
+		let spot_scale = 1.0 / (cos_inner - cos_outer).max(0.0001);
+		// A null direction can be typed in the gui
+		let direction = if self.direction.mag_sq() > 0.0 {
+			self.direction.normalized()
+		} else {
+			-uv::Vec3::unit_y()
+		};
+		GpuLight {
+			position: self.position,
+			kind: self.kind as u32,
+			direction,
+			range: self.range,
+			color: self.color * self.intensity,
+			spot_scale,
+			spot_offset: -cos_outer * spot_scale,
+		}
+	}
+}
+
+/// Content of the light storage buffer of the pbr pass.
+#[repr(C, align(16))]
+#[derive(Clone, Copy)]
+pub struct LightBuffer {
+	pub count: u32,
+	pub lights: [GpuLight; MAX_LIGHTS],
+}
+
+impl LightBuffer {
+	/// Only the first `MAX_LIGHTS` lights are kept.
+	pub fn new(lights: &[Light]) -> Self {
+		let mut buffer = LightBuffer {
+			count: lights.len().min(MAX_LIGHTS) as u32,
+			lights: [GpuLight::default(); MAX_LIGHTS],
+		};
+		for (gpu, light) in buffer.lights.iter_mut().zip(lights) {
+			*gpu = light.to_gpu();
+		}
+		buffer
+	}
+}
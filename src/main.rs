@@ -1,8 +1,25 @@
 extern crate ultraviolet as uv;
 
+mod antialiasing;
 mod bloom;
 mod context;
+mod dof;
+mod effects;
+mod exposure;
+mod gltf;
+mod grading;
+mod ground;
+mod ibl;
+mod lights;
+mod obj;
+mod output;
+mod pbr_textures;
 mod renderer;
+mod scene;
+mod shadow;
+mod skybox;
+mod sphere_grid;
+mod tonemap;
 
 use winit::{
 	event::VirtualKeyCode,
@@ -14,6 +31,20 @@ use context::Context;
 use renderer::Renderer;
 
 fn main() {
+	let mut scenes = gltf::find_scenes();
+	let mut scene_index: Option<usize> = None;
+	// A scene given on the command line is shown at startup and listed with the others
+	let mut startup_scene = std::env::args_os().nth(1).map(|path| {
+		let path = std::path::PathBuf::from(path);
+		let loaded = scene::load(&path).unwrap_or_else(|e| {
+			eprintln!("{}", e);
+			std::process::exit(1);
+		});
+		scenes.push(path);
+		scene_index = Some(scenes.len() - 1);
+		loaded
+	});
+
 	let event_loop = EventLoop::new();
 	let window = WindowBuilder::new()
 		.with_title("wgpu bloom")
@@ -28,6 +59,8 @@ fn main() {
 	let context = pollster::block_on(Context::new(
 		window,
 		Some(wgpu::Features::PUSH_CONSTANTS),
+		// Needed by the bloom formats other than the default one
+		wgpu::Features::TEXTURE_ADAPTER_SPECIFIC_FORMAT_FEATURES,
 		Some(wgpu::Limits {
 			max_push_constant_size: 128,
 			..Default::default()
@@ -35,29 +68,52 @@ fn main() {
 	));
 	let mut renderer = Renderer::new(context);
 
-	let mut pbr_param = bloom::PbrParam {
+	let pbr_param = bloom::PbrParam {
 		cam_pos: renderer.camera.position,
 		metallic: 0.0,
 		albedo: uv::Vec3::new(1.0, 0.0, 0.0),
 		roughness: 0.2,
 		emissive_color: uv::Vec3::new(0.0, 0.0, 0.0),
-		ao: 0.01,
-		light_position: uv::Vec3::new(-4.0, 5.0, -5.0),
+		ao: 1.0,
 		emissive_intensity: 0.0,
-		light_color: uv::Vec3::new(25.0, 25.0, 25.0),
+		environment_intensity: 1.0,
+		normal_scale: 1.0,
+		occlusion_strength: 1.0,
 	};
-	let mut bloom_threshold = 1.0f32;
-	let mut bloom_knee = 0.2f32;
-	let mut bloom_param = bloom::BloomParam {
-		parameters: uv::Vec4::new(
-			bloom_threshold,
-			bloom_threshold - bloom_knee,
-			bloom_knee * 2.0f32,
-			0.25f32 / bloom_knee,
-		), // (x) threshold, (y) threshold - knee, (z) knee * 2, (w) 0.25 / knee
-		combine_constant: 0.68,
+	let bloom_threshold = 1.0f32;
+	let bloom_knee = 0.2f32;
+	let mut state = renderer::gui::GuiState {
+		scenes,
+		scene: scene_index,
+		sphere_grid: false,
+		pbr_param,
+		lights: vec![lights::Light::default()],
+		environments: ibl::find_environments(),
+		environment: None,
+		materials: pbr_textures::find_materials(),
+		material: None,
+		bloom_threshold,
+		bloom_knee,
+		bloom_param: bloom::BloomParam {
+			parameters: uv::Vec4::new(
+				bloom_threshold,
+				bloom_threshold - bloom_knee,
+				bloom_knee * 2.0f32,
+				0.25f32 / bloom_knee,
+			), // (x) threshold, (y) threshold - knee, (z) knee * 2, (w) 0.25 / knee
+			combine_constant: 0.68,
+			intensity: 4.0,
+		},
+		bloom_intensity: 1.0,
+		final_param: bloom::FinalParam::default(),
+		grading_luts: grading::find_luts(),
+		grading_lut: None,
+		tonemap_param: tonemap::TonemapParam::default(),
+		exposure_param: exposure::ExposureParam::default(),
+		bloom_format: renderer.bloom_format,
+		output_mode: renderer.output_mode,
+		anti_aliasing: renderer.anti_aliasing,
 	};
-	let mut bloom_intensity = 1.0f32;
 
 	let start_time = std::time::Instant::now();
 
@@ -79,15 +135,41 @@ fn main() {
 
 			renderer.gui.platform.begin_frame();
 
-			let (pbr, final_composite, bloom) = renderer::gui::create_gui(
+			let mut changes = renderer::gui::create_gui(
 				&renderer.gui.platform.context(),
-				&mut pbr_param,
-				&mut bloom_threshold,
-				&mut bloom_knee,
-				&mut bloom_param,
-				&mut bloom_intensity,
+				&mut state,
+				&mut renderer,
 			);
-			if pbr {
+			if changes.scene || startup_scene.is_some() {
+				let loaded = startup_scene.take().or_else(|| {
+					scene::load(&state.scenes[state.scene?])
+						.map_err(|e| {
+							eprintln!("{}", e);
+							state.scene = None;
+						})
+						.ok()
+				});
+				renderer.set_scene(loaded.as_ref());
+				if let Some(loaded) = loaded.as_ref().filter(|s| !s.lights.is_empty()) {
+					state.lights = loaded.lights.clone();
+					state.lights.truncate(lights::MAX_LIGHTS);
+					changes.lights = true;
+				}
+				changes.shadow = true;
+				state.pbr_param.cam_pos = renderer.camera.position;
+				changes.pbr_param = true;
+			}
+			if changes.sphere_grid {
+				renderer.set_sphere_grid(state.sphere_grid);
+				changes.shadow = true;
+				state.pbr_param.cam_pos = renderer.camera.position;
+				changes.pbr_param = true;
+			}
+			if changes.pbr_param {
+				renderer.update_scene_params(
+					state.pbr_param.cam_pos,
+					state.pbr_param.environment_intensity,
+				);
 				renderer
 					.meshes
 					.get_mut("pbr")
@@ -100,10 +182,73 @@ fn main() {
 						&renderer.context.queue,
 						1,
 						0,
-						vec![pbr_param.clone()],
+						vec![state.pbr_param.clone()],
+					);
+				renderer
+					.meshes
+					.get_mut("ground")
+					.unwrap()
+					.material
+					.as_mut()
+					.unwrap()
+					.copy_to_buffer(
+						&renderer.context.device,
+						&renderer.context.queue,
+						1,
+						0,
+						vec![ground::ground_param(
+							state.pbr_param.cam_pos,
+							state.pbr_param.environment_intensity,
+						)],
+					);
+			}
+			if changes.lights {
+				renderer
+					.meshes
+					.get_mut("pbr")
+					.unwrap()
+					.material
+					.as_mut()
+					.unwrap()
+					.copy_to_buffer(
+						&renderer.context.device,
+						&renderer.context.queue,
+						2,
+						0,
+						vec![lights::LightBuffer::new(&state.lights)],
 					)
 			}
-			if bloom {
+			if changes.lights || changes.shadow {
+				renderer.update_shadow(&state.lights);
+			}
+			if renderer.dof.pick_focus
+				&& input.mouse_pressed(0)
+				&& !renderer.gui.platform.context().wants_pointer_input()
+			{
+				if let Some((x, y)) = input.mouse() {
+					renderer.dof.focus_point = Some((x as u32, y as u32));
+					renderer.dof.pick_focus = false;
+				}
+			}
+			let comparison_drag = state.final_param.compare != 0
+				&& !renderer.dof.pick_focus
+				&& input.mouse_held(0)
+				&& !renderer.gui.platform.context().wants_pointer_input();
+			if comparison_drag {
+				if let Some((x, _)) = input.mouse() {
+					state.final_param.compare_split =
+						(x / renderer.context.size.width as f32).clamp(0.0, 1.0);
+				}
+			}
+			renderer.comparison = state.final_param.compare != 0;
+			if changes.anti_aliasing {
+				renderer.set_anti_aliasing(state.anti_aliasing);
+			}
+			if changes.bloom_format {
+				renderer.set_bloom_format(state.bloom_format);
+				state.bloom_format = renderer.bloom_format;
+			}
+			if changes.bloom_param || changes.bloom_format {
 				renderer
 					.meshes
 					.get_mut("bloom")
@@ -116,15 +261,101 @@ fn main() {
 						&renderer.context.queue,
 						0,
 						0,
-						vec![bloom_param.clone()],
+						vec![state.bloom_param.clone()],
 					)
 			}
-			if final_composite {
+			if changes.tonemap {
+				renderer
+					.meshes
+					.get_mut("tonemap")
+					.unwrap()
+					.material
+					.as_mut()
+					.unwrap()
+					.copy_to_buffer(
+						&renderer.context.device,
+						&renderer.context.queue,
+						0,
+						0,
+						vec![state.tonemap_param],
+					);
+				renderer.tonemap_changed = true;
+			}
+			if changes.exposure {
+				renderer
+					.meshes
+					.get_mut("exposure")
+					.unwrap()
+					.material
+					.as_mut()
+					.unwrap()
+					.copy_to_buffer(
+						&renderer.context.device,
+						&renderer.context.queue,
+						1,
+						0,
+						vec![state.exposure_param],
+					);
+			}
+			if changes.output && !renderer.set_output_mode(state.output_mode) {
+				eprintln!(
+					"{} output is not supported by the surface",
+					state.output_mode.name()
+				);
+				state.output_mode = renderer.output_mode;
+			}
+			if changes.environment {
+				let map = match state.environment {
+					Some(i) => ibl::EquirectMap::load(&state.environments[i]).unwrap_or_else(|e| {
+						eprintln!("{}", e);
+						state.environment = None;
+						ibl::EquirectMap::procedural_sky()
+					}),
+					None => ibl::EquirectMap::procedural_sky(),
+				};
+				renderer.set_environment(&map);
+			}
+			if changes.material {
+				let device = &renderer.context.device;
+				let queue = &renderer.context.queue;
+				let textures = match state.material {
+					Some(i) => pbr_textures::PbrTextures::load(device, queue, &state.materials[i])
+						.unwrap_or_else(|e| {
+							eprintln!("{}", e);
+							state.material = None;
+							pbr_textures::PbrTextures::default_maps(device, queue)
+						}),
+					None => pbr_textures::PbrTextures::default_maps(device, queue),
+				};
+				renderer.set_material_textures(textures);
+			}
+			if changes.skybox {
+				renderer.update_skybox_buffer();
+			}
+			if changes.grading {
+				let lut = match state.grading_lut {
+					Some(i) => grading::CubeLut::load(&state.grading_luts[i]).unwrap_or_else(|e| {
+						eprintln!("{}", e);
+						state.grading_lut = None;
+						grading::CubeLut::identity()
+					}),
+					None => grading::CubeLut::identity(),
+				};
+				renderer.set_grading_lut(&lut);
+				state.final_param.grading_domain_min = lut.domain_min;
+				state.final_param.grading_domain_max = lut.domain_max;
+			}
+			// The grain is animated, it needs the time of every frame
+			let grain = state.final_param.effects & effects::EFFECT_FILM_GRAIN != 0;
+			if grain {
+				state.final_param.grain_time = start_time.elapsed().as_secs_f32();
+			}
+			if changes.final_composite || changes.grading || grain || comparison_drag {
 				renderer.final_buffer.copy_to_buffer(
 					&renderer.context.device,
 					&renderer.context.queue,
 					0,
-					vec![bloom_intensity * 4.0],
+					vec![state.final_param],
 				)
 			}
 
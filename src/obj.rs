@@ -0,0 +1,408 @@
+use std::path::Path;
+
+use crate::gltf;
+use crate::pbr_textures::MapKind;
+use crate::renderer::{mesh, png, Vertex};
+
+/// Faces of a Wavefront `.obj` file, as a scene of one primitive per material of its `.mtl`
+/// libraries.
+pub fn load(path: &Path) -> Result<gltf::Scene, String> {
+	let source = std::fs::read_to_string(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+	let directory = path.parent().unwrap_or_else(|| Path::new("."));
+	parse(&source, directory).map_err(|e| format!("{}: {}", path.display(), e))
+}
+
+/// Triangles of the faces using one material, deduplicated on their position, uv and normal.
+struct Group {
+	material: Option<String>,
+	vertices: Vec<Vertex>,
+	indices: Vec<u32>,
+	lookup: hashbrown::HashMap<(usize, Option<usize>, Option<usize>), u32>,
+	has_normals: bool, // false when a face has none
+}
+
+/// `.obj` file, the material libraries are looked for in `directory`. Points, lines and free
+/// form geometry are ignored, polygons are triangulated as fans.
+pub fn parse(source: &str, directory: &Path) -> Result<gltf::Scene, String> {
+	let mut positions: Vec<uv::Vec3> = Vec::new();
+	let mut uvs: Vec<uv::Vec2> = Vec::new();
+	let mut normals: Vec<uv::Vec3> = Vec::new();
+	let mut groups: Vec<Group> = Vec::new();
+	let mut current = None;
+	let mut libraries = Vec::new();
+
+	for (number, line) in source.lines().enumerate() {
+		let line_error = |message: &str| format!("line {}: {}", number + 1, message);
+		let line = line.split('#').next().unwrap();
+		let mut tokens = line.split_whitespace();
+		let statement = match tokens.next() {
+			Some(statement) => statement,
+			None => continue,
+		};
+		let args: Vec<&str> = tokens.collect();
+		let numbers = || {
+			args.iter()
+				.map(|a| a.parse::<f32>().map_err(|_| line_error("invalid number")))
+				.collect::<Result<Vec<_>, _>>()
+		};
+		match statement {
+			"v" => match numbers()?[..] {
+				[x, y, z, ..] => positions.push(uv::Vec3::new(x, y, z)),
+				_ => return Err(line_error("expected 3 coordinates")),
+			},
+			// The v axis of the textures points up
+			"vt" => match numbers()?[..] {
+				[u] => uvs.push(uv::Vec2::new(u, 1.0)),
+				[u, v, ..] => uvs.push(uv::Vec2::new(u, 1.0 - v)),
+				_ => return Err(line_error("expected a texture coordinate")),
+			},
+			"vn" => match numbers()?[..] {
+				[x, y, z] => normals.push(uv::Vec3::new(x, y, z)),
+				_ => return Err(line_error("expected 3 coordinates")),
+			},
+			"mtllib" => libraries.extend(args.iter().map(|a| a.to_string())),
+			"usemtl" => {
+				let material = args.first().map(|a| a.to_string());
+				current = groups.iter().position(|g| g.material == material);
+				if current.is_none() {
+					groups.push(Group {
+						material,
+						vertices: Vec::new(),
+						indices: Vec::new(),
+						lookup: hashbrown::HashMap::new(),
+						has_normals: true,
+					});
+					current = Some(groups.len() - 1);
+				}
+			}
+			"f" | "fo" => {
+				if args.len() < 3 {
+					return Err(line_error("a face needs 3 vertices"));
+				}
+				// Faces before any usemtl use the default material
+				let group = match current {
+					Some(group) => &mut groups[group],
+					None => {
+						groups.push(Group {
+							material: None,
+							vertices: Vec::new(),
+							indices: Vec::new(),
+							lookup: hashbrown::HashMap::new(),
+							has_normals: true,
+						});
+						current = Some(groups.len() - 1);
+						groups.last_mut().unwrap()
+					}
+				};
+				let mut face = Vec::with_capacity(args.len());
+				for arg in &args {
+					let mut references = arg.split('/');
+					let mut reference = |count: usize| -> Result<Option<usize>, String> {
+						match references.next() {
+							None | Some("") => Ok(None),
+							Some(r) => resolve_index(r, count)
+								.map(Some)
+								.ok_or_else(|| line_error(&format!("invalid index {}", r))),
+						}
+					};
+					let key = (
+						reference(positions.len())?
+							.ok_or_else(|| line_error("missing position index"))?,
+						reference(uvs.len())?,
+						reference(normals.len())?,
+					);
+					group.has_normals &= key.2.is_some();
+					let index = *group.lookup.entry(key).or_insert_with(|| {
+						group.vertices.push(Vertex {
+							position: positions[key.0],
+							normal: key.2.map_or(uv::Vec3::zero(), |n| normals[n]),
+							uv: key.1.map_or(uv::Vec2::zero(), |t| uvs[t]),
+							..Default::default()
+						});
+						group.vertices.len() as u32 - 1
+					});
+					face.push(index);
+				}
+				for i in 1..face.len() - 1 {
+					group.indices.extend([face[0], face[i], face[i + 1]]);
+				}
+			}
+			_ => {}
+		}
+	}
+
+	let mut images = Vec::new();
+	let mut named_materials: Vec<(String, gltf::Material)> = Vec::new();
+	for library in libraries {
+		let path = directory.join(&library);
+		// The geometry is still usable without its materials
+		match std::fs::read_to_string(&path) {
+			Ok(source) => named_materials.extend(
+				parse_mtl(&source, directory, &mut images)
+					.map_err(|e| format!("{}: {}", path.display(), e))?,
+			),
+			Err(e) => eprintln!("{}: {}", path.display(), e),
+		}
+	}
+
+	let mut primitives = Vec::with_capacity(groups.len());
+	for mut group in groups {
+		if group.indices.is_empty() {
+			continue;
+		}
+		if !group.has_normals {
+			mesh::compute_normals(&mut group.vertices, &group.indices);
+		}
+		mesh::compute_tangents(&mut group.vertices, Some(&group.indices));
+		let material = group
+			.material
+			.and_then(|name| named_materials.iter().position(|(n, _)| *n == name));
+		primitives.push(gltf::Primitive {
+			vertices: group.vertices,
+			indices: group.indices,
+			model: uv::Mat4::identity(),
+			material,
+		});
+	}
+
+	Ok(gltf::Scene {
+		primitives,
+		materials: named_materials.into_iter().map(|(_, m)| m).collect(),
+		images,
+		lights: Vec::new(),
+		camera: None,
+	})
+}
+
+/// Index of a vertex reference among `count` elements, negative ones are relative to the end.
+fn resolve_index(reference: &str, count: usize) -> Option<usize> {
+	let index: i64 = reference.parse().ok()?;
+	let index = match index {
+		i if i > 0 => i - 1,
+		i if i < 0 => count as i64 + i,
+		_ => return None,
+	};
+	(0..count as i64).contains(&index).then_some(index as usize)
+}
+
+/// Values of a `newmtl` statement, None when absent.
+#[derive(Default)]
+struct MtlValues {
+	diffuse: Option<uv::Vec3>,      // Kd
+	specular: Option<uv::Vec3>,     // Ks
+	emissive: Option<uv::Vec3>,     // Ke
+	specular_exponent: Option<f32>, // Ns
+	roughness: Option<f32>,         // Pr
+	metallic: Option<f32>,          // Pm
+	maps: [Option<usize>; 5],       // in the order of MapKind::ALL
+}
+
+impl MtlValues {
+	/// Pr and Pm of the PBR extension of the format take precedence. Otherwise the roughness
+	/// follows the specular exponent, a black specular color being fully rough, and the material
+	/// is a dielectric.
+	fn material(&self) -> gltf::Material {
+		let roughness = self.roughness.unwrap_or_else(|| {
+			if self.specular.is_some_and(|ks| ks.component_max() <= 0.0) {
+				1.0
+			} else {
+				// Roughness whose GGX lobe matches the Blinn-Phong one of the exponent
+				self.specular_exponent
+					.map_or(1.0, |ns| (2.0 / (ns.max(0.0) + 2.0)).powf(0.25))
+			}
+		});
+		gltf::Material {
+			albedo: self.diffuse.unwrap_or_else(uv::Vec3::one),
+			metallic: self.metallic.unwrap_or(0.0).clamp(0.0, 1.0),
+			roughness: roughness.clamp(0.0, 1.0),
+			emissive: self.emissive.unwrap_or_else(uv::Vec3::zero),
+			maps: self.maps,
+			..Default::default()
+		}
+	}
+}
+
+/// Materials of a `.mtl` library, the maps are looked for in `directory` and appended to
+/// `images`. Only the diffuse, emissive and normal maps are read, and only as PNG.
+fn parse_mtl(
+	source: &str,
+	directory: &Path,
+	images: &mut Vec<Option<png::Image>>,
+) -> Result<Vec<(String, gltf::Material)>, String> {
+	let mut materials: Vec<(String, MtlValues)> = Vec::new();
+
+	for (number, line) in source.lines().enumerate() {
+		let line_error = |message: &str| format!("line {}: {}", number + 1, message);
+		let line = line.split('#').next().unwrap();
+		let mut tokens = line.split_whitespace();
+		let statement = match tokens.next() {
+			Some(statement) => statement,
+			None => continue,
+		};
+		let args: Vec<&str> = tokens.collect();
+		if statement == "newmtl" {
+			let name = args
+				.first()
+				.ok_or_else(|| line_error("missing material name"))?;
+			materials.push((name.to_string(), MtlValues::default()));
+			continue;
+		}
+		let values = match materials.last_mut() {
+			Some((_, values)) => values,
+			None => continue,
+		};
+		let color = || match args[..] {
+			// Colors can also be given in CIE XYZ or as a spectral curve
+			[r] => r.parse().map(uv::Vec3::broadcast).ok(),
+			[r, g, b] => Some(uv::Vec3::new(
+				r.parse().ok()?,
+				g.parse().ok()?,
+				b.parse().ok()?,
+			)),
+			_ => None,
+		};
+		let number = || match args[..] {
+			[n] => n.parse::<f32>().ok(),
+			_ => None,
+		};
+		// Options come before the file name
+		let map = |kind: MapKind, images: &mut Vec<Option<png::Image>>| {
+			let path = directory.join(args.last()?);
+			let image = png::Image::load(&path)
+				.map_err(|e| eprintln!("{} map: {}", kind.file_stem(), e))
+				.ok();
+			images.push(image);
+			Some(images.len() - 1)
+		};
+		match statement {
+			"Kd" => values.diffuse = color(),
+			"Ks" => values.specular = color(),
+			"Ke" => values.emissive = color(),
+			"Ns" => values.specular_exponent = number(),
+			"Pr" => values.roughness = number(),
+			"Pm" => values.metallic = number(),
+			"map_Kd" => values.maps[MapKind::Albedo as usize] = map(MapKind::Albedo, images),
+			"map_Ke" => values.maps[MapKind::Emissive as usize] = map(MapKind::Emissive, images),
+			"norm" | "map_Kn" => {
+				values.maps[MapKind::Normal as usize] = map(MapKind::Normal, images)
+			}
+			_ => {}
+		}
+	}
+
+	Ok(materials
+		.into_iter()
+		.map(|(name, values)| (name, values.material()))
+		.collect())
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn primitive(source: &str) -> gltf::Primitive {
+		let mut scene = parse(source, Path::new(".")).unwrap();
+		assert_eq!(scene.primitives.len(), 1);
+		scene.primitives.remove(0)
+	}
+
+	fn positions(primitive: &gltf::Primitive) -> Vec<[f32; 3]> {
+		primitive
+			.indices
+			.iter()
+			.map(|&i| *primitive.vertices[i as usize].position.as_array())
+			.collect()
+	}
+
+	const SQUARE: &str = "
+		v 0 0 0
+		v 1 0 0
+		v 1 1 0
+		v 0 1 0
+		vt 0 0
+		vt 1 1
+		vn 0 0 1
+	";
+
+	#[test]
+	fn relative_indices_match_absolute_ones() {
+		let absolute = primitive(&format!("{}\nf 1/1 2/2 3/1", SQUARE));
+		let relative = primitive(
+			&format!("{}\nf -3/-2 -2/-1 -1/-2", SQUARE).replace("v 0 1 0", "# no fourth vertex"),
+		);
+		assert_eq!(positions(&absolute), positions(&relative));
+		assert_eq!(
+			positions(&absolute),
+			[[0.0, 0.0, 0.0], [1.0, 0.0, 0.0], [1.0, 1.0, 0.0]]
+		);
+		// The v axis is flipped
+		assert_eq!(*absolute.vertices[1].uv.as_array(), [1.0, 0.0]);
+
+		assert!(parse(&format!("{}\nf 1 2 5", SQUARE), Path::new(".")).is_err());
+		assert!(parse(&format!("{}\nf 1 2 -5", SQUARE), Path::new(".")).is_err());
+		assert!(parse(&format!("{}\nf 0 1 2", SQUARE), Path::new(".")).is_err());
+	}
+
+	#[test]
+	fn normals_without_uvs() {
+		let square = primitive(&format!("{}\nf 1//1 2//1 3//1", SQUARE));
+		for vertex in &square.vertices {
+			assert_eq!(*vertex.normal.as_array(), [0.0, 0.0, 1.0]);
+			assert_eq!(*vertex.uv.as_array(), [0.0, 0.0]);
+		}
+
+		// Computed when missing, facing the counter clockwise side
+		let square = primitive(&format!("{}\nf 1 2 3", SQUARE));
+		for vertex in &square.vertices {
+			assert!((vertex.normal - uv::Vec3::unit_z()).mag() < 1e-6);
+		}
+	}
+
+	#[test]
+	fn polygons_are_triangulated_as_fans() {
+		let square = primitive(&format!("{}\nf 1 2 3 4\nf 1 3 4", SQUARE));
+		assert_eq!(square.vertices.len(), 4);
+		assert_eq!(square.indices, [0, 1, 2, 0, 2, 3, 0, 2, 3]);
+		assert!(parse(&format!("{}\nf 1 2", SQUARE), Path::new(".")).is_err());
+	}
+
+	#[test]
+	fn faces_are_grouped_by_material() {
+		let source = format!(
+			"{}\nf 1 2 3\nusemtl red\nf 1 3 4\nusemtl blue\nf 2 3 4\nusemtl red\nf 1 2 4",
+			SQUARE
+		);
+		let scene = parse(&source, Path::new(".")).unwrap();
+		// Without libraries, the materials are unknown
+		let counts: Vec<usize> = scene.primitives.iter().map(|p| p.indices.len()).collect();
+		assert_eq!(counts, [3, 6, 3]);
+		assert!(scene.primitives.iter().all(|p| p.material.is_none()));
+	}
+
+	#[test]
+	fn mtl_materials() {
+		let source = "
+			newmtl rough
+			Kd 0.5 0.25 1
+			Ks 0 0 0
+			Ns 1000
+			newmtl shiny
+			Ks 1 1 1
+			Ns 1000
+			Ke 2
+			newmtl metal
+			Pr 0.3
+			Pm 2
+		";
+		let materials = parse_mtl(source, Path::new("."), &mut Vec::new()).unwrap();
+		let names: Vec<&str> = materials.iter().map(|(n, _)| n.as_str()).collect();
+		assert_eq!(names, ["rough", "shiny", "metal"]);
+		let (rough, shiny, metal) = (materials[0].1, materials[1].1, materials[2].1);
+
+		assert_eq!(*rough.albedo.as_array(), [0.5, 0.25, 1.0]);
+		assert_eq!(rough.roughness, 1.0);
+		assert!(shiny.roughness < 0.25);
+		assert_eq!(*shiny.emissive.as_array(), [2.0; 3]);
+		assert_eq!((metal.roughness, metal.metallic), (0.3, 1.0));
+	}
+}
@@ -0,0 +1,135 @@
+use crate::context::Context;
+
+/// Reference luminance of scRGB, a value of 1.0 is displayed at 80 nits.
+const SCRGB_REFERENCE_NITS: f32 = 80.0;
+/// Peak luminance of the PQ curve.
+const PQ_MAX_NITS: f32 = 10000.0;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum OutputMode {
+	Sdr,
+	SdrGamma22,
+	/// Linear extended range `Rgba16Float` swapchain, composed as scRGB by the platforms that
+	/// support it (Windows).
+	HdrScRgb,
+	/// PQ encoded `Rgb10a2Unorm` swapchain. wgpu does not expose the swapchain color space yet,
+	/// the platform has to present 10 bits surfaces as HDR10 for this mode to look right.
+	HdrPq,
+}
+
+impl OutputMode {
+	pub const ALL: [OutputMode; 4] = [
+		OutputMode::Sdr,
+		OutputMode::SdrGamma22,
+		OutputMode::HdrScRgb,
+		OutputMode::HdrPq,
+	];
+
+	pub fn name(self) -> &'static str {
+		match self {
+			OutputMode::Sdr => "SDR (sRGB)",
+			OutputMode::SdrGamma22 => "SDR (gamma 2.2)",
+			OutputMode::HdrScRgb => "HDR (scRGB)",
+			OutputMode::HdrPq => "HDR (PQ)",
+		}
+	}
+
+	pub fn is_hdr(self) -> bool {
+		matches!(self, OutputMode::HdrScRgb | OutputMode::HdrPq)
+	}
+
+	/// Whether the surface accepts the format of the mode.
+	pub fn is_available(self, context: &Context) -> bool {
+		context
+			.surface_formats
+			.contains(&self.surface_format(context.preferred_format))
+	}
+
+	/// Swapchain format of the mode, `preferred` being the surface preferred format.
+	pub fn surface_format(self, preferred: wgpu::TextureFormat) -> wgpu::TextureFormat {
+		match self {
+			OutputMode::Sdr => preferred,
+			// The encoding is done by the shader, the surface must not apply its own
+			OutputMode::SdrGamma22 => match preferred {
+				wgpu::TextureFormat::Bgra8UnormSrgb => wgpu::TextureFormat::Bgra8Unorm,
+				wgpu::TextureFormat::Rgba8UnormSrgb => wgpu::TextureFormat::Rgba8Unorm,
+				format => format,
+			},
+			OutputMode::HdrScRgb => wgpu::TextureFormat::Rgba16Float,
+			OutputMode::HdrPq => wgpu::TextureFormat::Rgb10a2Unorm,
+		}
+	}
+}
+
+/// Encoding applied by the final pass, matches the TRANSFER_* constants of final.wgsl.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum OutputTransfer {
+	/// Linear output, for `*Srgb` surfaces that encode on write and for scRGB.
+	None,
+	Srgb,
+	Gamma22,
+	Pq,
+}
+
+impl OutputTransfer {
+	pub fn new(mode: OutputMode, surface_format: wgpu::TextureFormat) -> Self {
+		match mode {
+			OutputMode::Sdr | OutputMode::SdrGamma22 if surface_format.describe().srgb => {
+				OutputTransfer::None
+			}
+			OutputMode::Sdr => OutputTransfer::Srgb,
+			OutputMode::SdrGamma22 => OutputTransfer::Gamma22,
+			OutputMode::HdrScRgb => OutputTransfer::None,
+			OutputMode::HdrPq => OutputTransfer::Pq,
+		}
+	}
+
+	pub fn id(self) -> u32 {
+		self as u32
+	}
+}
+
+/// Push constants of the final pass.
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct OutputParam {
+	pub transfer: u32,
+	pub scale: f32,        // applied to the linear display color before the transfer
+	pub quantization: f32, // step of the surface format, 0 for float formats
+	pub frame: u32,        // animates the dithering
+}
+
+impl OutputParam {
+	/// `paper_white` is the luminance in nits of a display white of 1.0 in the hdr modes.
+	pub fn new(
+		mode: OutputMode,
+		surface_format: wgpu::TextureFormat,
+		paper_white: f32,
+		frame: u32,
+	) -> Self {
+		let scale = match mode {
+			OutputMode::Sdr | OutputMode::SdrGamma22 => 1.0,
+			OutputMode::HdrScRgb => paper_white / SCRGB_REFERENCE_NITS,
+			OutputMode::HdrPq => paper_white / PQ_MAX_NITS,
+		};
+		OutputParam {
+			transfer: OutputTransfer::new(mode, surface_format).id(),
+			scale,
+			quantization: quantization_step(surface_format),
+			frame,
+		}
+	}
+}
+
+/// Difference between two consecutive values of the color channels of `format`, the amplitude
+/// of the dithering. Float formats do not band and are not dithered.
+fn quantization_step(format: wgpu::TextureFormat) -> f32 {
+	match format {
+		wgpu::TextureFormat::Rgb10a2Unorm => 1.0 / 1023.0,
+		wgpu::TextureFormat::Rgba8Unorm
+		| wgpu::TextureFormat::Rgba8UnormSrgb
+		| wgpu::TextureFormat::Bgra8Unorm
+		| wgpu::TextureFormat::Bgra8UnormSrgb => 1.0 / 255.0,
+		_ => 0.0,
+	}
+}
@@ -0,0 +1,292 @@
+use std::path::{Path, PathBuf};
+
+use crate::renderer::{png, texture::Texture};
+
+/// Directory scanned at startup for materials, each a directory of `.png` maps named after
+/// `MapKind::file_stem`.
+pub const MATERIAL_DIRECTORY: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/assets/materials");
+
+/// Maps of a material, in the binding order of pbr.wgsl. Each is multiplied by its factor of
+/// `PbrParam`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MapKind {
+	Albedo,
+	Normal,
+	MetallicRoughness, // roughness in green and metallic in blue, as glTF
+	Occlusion,         // in red
+	Emissive,
+}
+
+impl MapKind {
+	pub const ALL: [MapKind; 5] = [
+		MapKind::Albedo,
+		MapKind::Normal,
+		MapKind::MetallicRoughness,
+		MapKind::Occlusion,
+		MapKind::Emissive,
+	];
+
+	pub fn file_stem(self) -> &'static str {
+		match self {
+			MapKind::Albedo => "albedo",
+			MapKind::Normal => "normal",
+			MapKind::MetallicRoughness => "metallic_roughness",
+			MapKind::Occlusion => "occlusion",
+			MapKind::Emissive => "emissive",
+		}
+	}
+
+	fn is_srgb(self) -> bool {
+		matches!(self, MapKind::Albedo | MapKind::Emissive)
+	}
+
+	/// Texel of the 1x1 texture used when the map is absent, leaving the factor unchanged.
+	fn default_texel(self) -> [u8; 4] {
+		match self {
+			MapKind::Normal => [128, 128, 255, 255],
+			_ => [255; 4],
+		}
+	}
+}
+
+pub struct PbrTextures {
+	pub maps: Vec<Texture>, // in the order of MapKind::ALL, the first holds the sampler
+}
+
+impl PbrTextures {
+	/// 1x1 textures of `MapKind::default_texel`, for meshes without maps.
+	pub fn default_maps(device: &wgpu::Device, queue: &wgpu::Queue) -> Self {
+		Self::from_images(device, queue, [None; 5])
+	}
+
+	/// Loads the maps of the material directory `directory`, the absent ones use the default
+	/// textures.
+	pub fn load(
+		device: &wgpu::Device,
+		queue: &wgpu::Queue,
+		directory: &Path,
+	) -> Result<Self, String> {
+		let mut images = MapKind::ALL.map(|_| None);
+		for (image, kind) in images.iter_mut().zip(MapKind::ALL) {
+			let path = directory.join(kind.file_stem()).with_extension("png");
+			if path.exists() {
+				*image = Some(png::Image::load(&path)?);
+			}
+		}
+		Ok(Self::from_images(
+			device,
+			queue,
+			images.each_ref().map(Option::as_ref),
+		))
+	}
+
+	/// Maps in the order of `MapKind::ALL`, the absent ones use the default textures.
+	pub fn from_images(
+		device: &wgpu::Device,
+		queue: &wgpu::Queue,
+		images: [Option<&png::Image>; 5],
+	) -> Self {
+		let mut maps: Vec<Texture> = MapKind::ALL
+			.iter()
+			.zip(images)
+			.map(|(&kind, image)| match image {
+				Some(image) => create_map_texture(device, queue, kind, image),
+				None => {
+					let image = png::Image {
+						width: 1,
+						height: 1,
+						pixels: vec![kind.default_texel()],
+					};
+					create_map_texture(device, queue, kind, &image)
+				}
+			})
+			.collect();
+		maps[0].set_sampler(
+			device,
+			Some("material sampler"),
+			wgpu::AddressMode::Repeat,
+			wgpu::FilterMode::Linear,
+			wgpu::FilterMode::Linear,
+			wgpu::FilterMode::Linear,
+			None,
+			None,
+			None,
+			None,
+			None,
+		);
+		PbrTextures { maps }
+	}
+
+	/// Layout of the material bind group of the pbr pass, the maps then the sampler.
+	pub fn pbr_layout_entries() -> Vec<wgpu::BindGroupLayoutEntry> {
+		let mut entries: Vec<wgpu::BindGroupLayoutEntry> = (0..MapKind::ALL.len() as u32)
+			.map(|binding| wgpu::BindGroupLayoutEntry {
+				binding,
+				visibility: wgpu::ShaderStages::FRAGMENT,
+				ty: wgpu::BindingType::Texture {
+					sample_type: wgpu::TextureSampleType::Float { filterable: true },
+					view_dimension: wgpu::TextureViewDimension::D2,
+					multisampled: false,
+				},
+				count: None,
+			})
+			.collect();
+		entries.push(wgpu::BindGroupLayoutEntry {
+			binding: MapKind::ALL.len() as u32,
+			visibility: wgpu::ShaderStages::FRAGMENT,
+			ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+			count: None,
+		});
+		entries
+	}
+
+	pub fn pbr_bindings(&self) -> Vec<wgpu::BindingResource<'_>> {
+		let mut bindings: Vec<wgpu::BindingResource> = self
+			.maps
+			.iter()
+			.map(|map| wgpu::BindingResource::TextureView(&map.view))
+			.collect();
+		bindings.push(wgpu::BindingResource::Sampler(
+			self.maps[0].sampler.as_ref().unwrap(),
+		));
+		bindings
+	}
+}
+
+/// Texture with the full mip chain of `image`, downsampled on the cpu.
+fn create_map_texture(
+	device: &wgpu::Device,
+	queue: &wgpu::Queue,
+	kind: MapKind,
+	image: &png::Image,
+) -> Texture {
+	let mip_count = 32 - image.width.max(image.height).leading_zeros();
+	let texture = Texture::new(
+		device,
+		Some(kind.file_stem()),
+		image.width,
+		image.height,
+		1,
+		mip_count,
+		1,
+		wgpu::TextureDimension::D2,
+		if kind.is_srgb() {
+			wgpu::TextureFormat::Rgba8UnormSrgb
+		} else {
+			wgpu::TextureFormat::Rgba8Unorm
+		},
+		wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+		wgpu::TextureAspect::All,
+	);
+
+	let (mut width, mut height) = (image.width, image.height);
+	let mut pixels = image.pixels.clone();
+	for mip in 0..mip_count {
+		if mip > 0 {
+			let (next_width, next_height) = ((width / 2).max(1), (height / 2).max(1));
+			pixels = downsample(
+				&pixels,
+				width,
+				height,
+				next_width,
+				next_height,
+				kind.is_srgb(),
+			);
+			width = next_width;
+			height = next_height;
+		}
+		queue.write_texture(
+			wgpu::ImageCopyTexture {
+				texture: &texture.texture,
+				mip_level: mip,
+				origin: wgpu::Origin3d::ZERO,
+				aspect: wgpu::TextureAspect::All,
+			},
+			unsafe { pixels.align_to::<u8>().1 },
+			wgpu::ImageDataLayout {
+				offset: 0,
+				bytes_per_row: std::num::NonZeroU32::new(width * 4),
+				rows_per_image: None,
+			},
+			wgpu::Extent3d {
+				width,
+				height,
+				depth_or_array_layers: 1,
+			},
+		);
+	}
+
+	texture
+}
+
+/// 2x2 box filter, averaging the color of srgb maps in linear space.
+fn downsample(
+	pixels: &[[u8; 4]],
+	width: u32,
+	height: u32,
+	next_width: u32,
+	next_height: u32,
+	srgb: bool,
+) -> Vec<[u8; 4]> {
+	let to_linear = |v: u8| {
+		let v = v as f32 / 255.0;
+		if srgb {
+			if v <= 0.04045 {
+				v / 12.92
+			} else {
+				((v + 0.055) / 1.055).powf(2.4)
+			}
+		} else {
+			v
+		}
+	};
+	let from_linear = |v: f32| {
+		let v = if srgb {
+			if v <= 0.0031308 {
+				v * 12.92
+			} else {
+				1.055 * v.powf(1.0 / 2.4) - 0.055
+			}
+		} else {
+			v
+		};
+		(v * 255.0 + 0.5).clamp(0.0, 255.0) as u8
+	};
+
+	let mut next = Vec::with_capacity((next_width * next_height) as usize);
+	for y in 0..next_height {
+		for x in 0..next_width {
+			let mut sum = [0.0f32; 4];
+			for (dx, dy) in [(0, 0), (1, 0), (0, 1), (1, 1)] {
+				let sx = (x * 2 + dx).min(width - 1);
+				let sy = (y * 2 + dy).min(height - 1);
+				let texel = pixels[(sy * width + sx) as usize];
+				for c in 0..3 {
+					sum[c] += to_linear(texel[c]);
+				}
+				// Alpha is always linear
+				sum[3] += texel[3] as f32 / 255.0;
+			}
+			next.push([
+				from_linear(sum[0] / 4.0),
+				from_linear(sum[1] / 4.0),
+				from_linear(sum[2] / 4.0),
+				(sum[3] / 4.0 * 255.0 + 0.5) as u8,
+			]);
+		}
+	}
+	next
+}
+
+/// Directories of `MATERIAL_DIRECTORY`, sorted by name.
+pub fn find_materials() -> Vec<PathBuf> {
+	let mut materials: Vec<PathBuf> = match std::fs::read_dir(MATERIAL_DIRECTORY) {
+		Ok(entries) => entries
+			.filter_map(|entry| entry.ok().map(|e| e.path()))
+			.filter(|path| path.is_dir())
+			.collect(),
+		Err(_) => Vec::new(),
+	};
+	materials.sort();
+	materials
+}
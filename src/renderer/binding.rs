@@ -0,0 +1,243 @@
+/// The resource a slot of a bind group expects.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SlotType {
+	Texture2d,
+	Texture3d,
+	Sampler,
+	/// Uniform buffer of the given size in bytes.
+	Uniform(u64),
+	/// Read only storage buffer of the given size in bytes.
+	ReadOnlyStorage(u64),
+}
+
+/// A named binding of a bind group, the name is the one of the variable in the shader.
+#[derive(Debug, Clone, Copy)]
+pub struct Slot {
+	pub name: &'static str,
+	pub binding: u32,
+	pub ty: SlotType,
+}
+
+impl Slot {
+	pub const fn new(name: &'static str, binding: u32, ty: SlotType) -> Self {
+		Slot { name, binding, ty }
+	}
+
+	fn layout_entry(&self, visibility: wgpu::ShaderStages) -> wgpu::BindGroupLayoutEntry {
+		let texture = |view_dimension| wgpu::BindingType::Texture {
+			multisampled: false,
+			view_dimension,
+			sample_type: wgpu::TextureSampleType::Float { filterable: true },
+		};
+		let ty = match self.ty {
+			SlotType::Texture2d => texture(wgpu::TextureViewDimension::D2),
+			SlotType::Texture3d => texture(wgpu::TextureViewDimension::D3),
+			SlotType::Sampler => wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+			SlotType::Uniform(size) => wgpu::BindingType::Buffer {
+				ty: wgpu::BufferBindingType::Uniform,
+				has_dynamic_offset: false,
+				min_binding_size: std::num::NonZeroU64::new(size),
+			},
+			SlotType::ReadOnlyStorage(size) => wgpu::BindingType::Buffer {
+				ty: wgpu::BufferBindingType::Storage { read_only: true },
+				has_dynamic_offset: false,
+				min_binding_size: std::num::NonZeroU64::new(size),
+			},
+		};
+		wgpu::BindGroupLayoutEntry {
+			binding: self.binding,
+			visibility,
+			ty,
+			count: None,
+		}
+	}
+
+	fn matches(&self, module: &naga::Module, var: &naga::GlobalVariable) -> bool {
+		match (self.ty, &module.types[var.ty].inner) {
+			(SlotType::Texture2d, naga::TypeInner::Image { dim, .. }) => {
+				*dim == naga::ImageDimension::D2
+			}
+			(SlotType::Texture3d, naga::TypeInner::Image { dim, .. }) => {
+				*dim == naga::ImageDimension::D3
+			}
+			(SlotType::Sampler, naga::TypeInner::Sampler { comparison }) => !comparison,
+			// The span includes the padding of the struct, as the size of the rust type does
+			(SlotType::Uniform(size), inner) => {
+				var.class == naga::StorageClass::Uniform
+					&& inner.span(&module.constants) as u64 == size
+			}
+			(SlotType::ReadOnlyStorage(size), inner) => {
+				var.class
+					== naga::StorageClass::Storage {
+						access: naga::StorageAccess::LOAD,
+					} && inner.span(&module.constants) as u64 == size
+			}
+			_ => false,
+		}
+	}
+}
+
+/// Checks the slots against the bindings of `group` reflected from the wgsl `source`, panics
+/// naming the mismatched binding so a swapped or renamed resource is caught at startup.
+pub fn validate(label: &str, source: &str, group: u32, slots: &[Slot]) {
+	let module = naga::front::wgsl::parse_str(source)
+		.unwrap_or_else(|e| panic!("{}: {}", label, e.emit_to_string(source)));
+
+	let mut reflected = module
+		.global_variables
+		.iter()
+		.filter_map(|(_, var)| match &var.binding {
+			Some(b) if b.group == group => Some((b.binding, var)),
+			_ => None,
+		})
+		.collect::<Vec<_>>();
+	reflected.sort_by_key(|(binding, _)| *binding);
+
+	for slot in slots {
+		let var = reflected
+			.iter()
+			.find(|(binding, _)| *binding == slot.binding)
+			.map(|(_, var)| *var)
+			.unwrap_or_else(|| {
+				panic!(
+					"{}: slot `{}` is bound at binding {} which the shader does not declare",
+					label, slot.name, slot.binding
+				)
+			});
+		let name = var.name.as_deref().unwrap_or("<unnamed>");
+		if name != slot.name {
+			panic!(
+				"{}: binding {} is `{}` in the shader but `{}` in the bind group",
+				label, slot.binding, name, slot.name
+			);
+		}
+		if !slot.matches(&module, var) {
+			panic!(
+				"{}: `{}` at binding {} is not a {:?} in the shader, its type is {} bytes",
+				label,
+				slot.name,
+				slot.binding,
+				slot.ty,
+				module.types[var.ty].inner.span(&module.constants)
+			);
+		}
+	}
+	for (binding, var) in &reflected {
+		if !slots.iter().any(|slot| slot.binding == *binding) {
+			panic!(
+				"{}: the shader declares `{}` at binding {} which has no slot",
+				label,
+				var.name.as_deref().unwrap_or("<unnamed>"),
+				binding
+			);
+		}
+	}
+}
+
+pub fn create_layout(
+	device: &wgpu::Device,
+	label: Option<&str>,
+	slots: &[Slot],
+	visibility: wgpu::ShaderStages,
+) -> wgpu::BindGroupLayout {
+	let entries = slots
+		.iter()
+		.map(|slot| slot.layout_entry(visibility))
+		.collect::<Vec<_>>();
+	device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+		label,
+		entries: &entries,
+	})
+}
+
+/// Every slot has to be given a resource, looked up by its name.
+pub fn create_bind_group(
+	device: &wgpu::Device,
+	label: Option<&str>,
+	layout: &wgpu::BindGroupLayout,
+	slots: &[Slot],
+	resources: &[(&str, wgpu::BindingResource)],
+) -> wgpu::BindGroup {
+	for (name, _) in resources {
+		if !slots.iter().any(|slot| slot.name == *name) {
+			panic!("{:?}: no slot named `{}`", label, name);
+		}
+	}
+	let entries = slots
+		.iter()
+		.map(|slot| {
+			let (_, resource) = resources
+				.iter()
+				.find(|(name, _)| *name == slot.name)
+				.unwrap_or_else(|| panic!("{:?}: no resource for slot `{}`", label, slot.name));
+			wgpu::BindGroupEntry {
+				binding: slot.binding,
+				resource: resource.clone(),
+			}
+		})
+		.collect::<Vec<_>>();
+	device.create_bind_group(&wgpu::BindGroupDescriptor {
+		label,
+		layout,
+		entries: &entries,
+	})
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	const SHADER: &str = "
+		struct Param {
+			color: vec3<f32>;
+			scale: f32;
+			offset: vec2<f32>;
+		};
+		[[group(0), binding(0)]] var input_texture: texture_2d<f32>;
+		[[group(0), binding(1)]] var samp: sampler;
+		[[group(0), binding(2)]] var<uniform> param: Param;
+	";
+
+	fn slots(param_size: u64) -> [Slot; 3] {
+		[
+			Slot::new("input_texture", 0, SlotType::Texture2d),
+			Slot::new("samp", 1, SlotType::Sampler),
+			Slot::new("param", 2, SlotType::Uniform(param_size)),
+		]
+	}
+
+	#[test]
+	fn matching_slots() {
+		// Padded to the alignment of the vec3
+		validate("test", SHADER, 0, &slots(32));
+	}
+
+	#[test]
+	#[should_panic(expected = "`param` at binding 2 is not a Uniform(24)")]
+	fn uniform_size_mismatch() {
+		validate("test", SHADER, 0, &slots(24));
+	}
+
+	#[test]
+	#[should_panic(expected = "binding 1 is `samp` in the shader but `sampler`")]
+	fn renamed_slot() {
+		let mut slots = slots(32);
+		slots[1].name = "sampler";
+		validate("test", SHADER, 0, &slots);
+	}
+
+	#[test]
+	#[should_panic(expected = "`exposure` at binding 0 is not a ReadOnlyStorage(8)")]
+	fn writable_storage() {
+		let shader = "
+			struct Exposure {
+				luminance: f32;
+				exposure: f32;
+			};
+			[[group(0), binding(0)]] var<storage, read_write> exposure: Exposure;
+		";
+		let slots = [Slot::new("exposure", 0, SlotType::ReadOnlyStorage(8))];
+		validate("test", &shader.replace("read_write", "read"), 0, &slots);
+		validate("test", shader, 0, &slots);
+	}
+}
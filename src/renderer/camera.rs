@@ -8,9 +8,14 @@ pub struct PerspectiveCamera {
 	pub aspect_ratio: f32,
 	pub near: f32,
 	pub far: f32,
+	pub aperture: f32,      // f-number
+	pub shutter_speed: f32, // in seconds
+	pub iso: f32,
+	pub jitter: uv::Vec2, // sub-pixel offset of the projection, in NDC
 	pub view: uv::Mat4,
 	pub proj: uv::Mat4,
 	pub view_proj: uv::Mat4,
+	pub unjittered_view_proj: uv::Mat4,
 }
 
 pub struct OrthographicCamera {
@@ -49,9 +54,15 @@ impl PerspectiveCamera {
 			aspect_ratio,
 			near,
 			far,
+			// Exposes the scene close to an exposure of 1
+			aperture: 1.4,
+			shutter_speed: 1.0 / 30.0,
+			iso: 6400.0,
+			jitter: uv::Vec2::zero(),
 			view,
 			proj,
 			view_proj: proj * view,
+			unjittered_view_proj: proj * view,
 		}
 	}
 
@@ -59,10 +70,28 @@ impl PerspectiveCamera {
 		let view = uv::Mat4::look_at(self.position, self.look_at, self.up);
 		let proj =
 			uv::projection::perspective_wgpu_dx(self.fov, self.aspect_ratio, self.near, self.far);
+		let jitter = uv::Mat4::from_translation(uv::Vec3::new(self.jitter.x, self.jitter.y, 0.0));
 
 		self.view = view;
-		self.proj = proj;
-		self.view_proj = proj * view;
+		self.proj = jitter * proj;
+		self.view_proj = self.proj * view;
+		self.unjittered_view_proj = proj * view;
+	}
+
+	pub fn set_jitter(&mut self, jitter: uv::Vec2) {
+		self.jitter = jitter;
+		self.recreate_matrices();
+	}
+
+	/// Exposure value of the aperture, shutter speed and ISO settings, normalized to ISO 100.
+	pub fn ev100(&self) -> f32 {
+		((self.aperture * self.aperture) / self.shutter_speed * 100.0 / self.iso).log2()
+	}
+
+	/// Scale applied to the scene luminance, based on the saturation of the sensor
+	/// (Lagarde & de Rousiers, Moving Frostbite to PBR).
+	pub fn exposure(&self) -> f32 {
+		1.0 / (1.2 * 2.0f32.powf(self.ev100()))
 	}
 }
 
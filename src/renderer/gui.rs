@@ -1,7 +1,9 @@
 use egui_wgpu_backend::RenderPass;
 use egui_winit_platform::{Platform, PlatformDescriptor};
 
-use crate::bloom;
+use crate::{antialiasing, bloom, dof, effects, exposure, lights, output, shadow, skybox, tonemap};
+
+use super::{camera, post_process, Renderer};
 
 pub struct Gui {
 	pub platform: Platform,
@@ -34,21 +36,96 @@ impl Gui {
 	}
 }
 
+/// State edited by the gui, owned by the event loop. The `Vec`s are the files listed by the
+/// combo boxes, and the `Option<usize>` the selected ones.
+pub struct GuiState {
+	pub scenes: Vec<std::path::PathBuf>,
+	pub scene: Option<usize>,
+	pub sphere_grid: bool,
+	pub pbr_param: bloom::PbrParam,
+	pub lights: Vec<lights::Light>,
+	pub environments: Vec<std::path::PathBuf>,
+	pub environment: Option<usize>,
+	pub materials: Vec<std::path::PathBuf>,
+	pub material: Option<usize>,
+	pub bloom_threshold: f32,
+	pub bloom_knee: f32,
+	pub bloom_param: bloom::BloomParam,
+	pub bloom_intensity: f32,
+	pub final_param: bloom::FinalParam,
+	pub grading_luts: Vec<std::path::PathBuf>,
+	pub grading_lut: Option<usize>,
+	pub tonemap_param: tonemap::TonemapParam,
+	pub exposure_param: exposure::ExposureParam,
+	pub bloom_format: bloom::BloomFormat,
+	pub output_mode: output::OutputMode,
+	pub anti_aliasing: antialiasing::AntiAliasing,
+}
+
+/// What the gui changed during the frame, to be uploaded or applied to the renderer.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct GuiChanges {
+	pub pbr_param: bool,
+	pub final_composite: bool,
+	pub bloom_param: bool,
+	pub bloom_format: bool,
+	pub tonemap: bool,
+	pub exposure: bool,
+	pub grading: bool,
+	pub output: bool,
+	pub anti_aliasing: bool,
+	pub lights: bool,
+	pub environment: bool,
+	pub skybox: bool,
+	pub shadow: bool,
+	pub material: bool,
+	pub scene: bool,
+	pub sphere_grid: bool,
+}
+
+/// Edits `state` and the settings owned by `renderer`.
 pub fn create_gui(
 	ctx: &egui::Context,
-	pbr_param: &mut bloom::PbrParam,
-	bloom_threshold: &mut f32,
-	bloom_knee: &mut f32,
-	bloom_param: &mut bloom::BloomParam,
-	bloom_intensity: &mut f32,
-) -> (bool, bool, bool) {
+	state: &mut GuiState,
+	renderer: &mut Renderer,
+) -> GuiChanges {
+	let GuiState {
+		scenes,
+		scene,
+		sphere_grid,
+		pbr_param,
+		lights,
+		environments,
+		environment,
+		materials,
+		material,
+		bloom_threshold,
+		bloom_knee,
+		bloom_param,
+		bloom_intensity,
+		final_param,
+		grading_luts,
+		grading_lut,
+		tonemap_param,
+		exposure_param,
+		bloom_format,
+		output_mode,
+		anti_aliasing,
+	} = state;
+	let skybox = &mut renderer.skybox;
+	let shadow = &mut renderer.shadow.settings;
+	let camera = &mut renderer.camera;
+	let histogram = &renderer.histogram.bins;
+	let context = &renderer.context;
+	let paper_white = &mut renderer.paper_white;
+	let post_process = &mut renderer.post_process.as_mut().unwrap().passes;
+	let dof = &mut renderer.dof;
+
 	egui::Window::new("Parameters")
 		.resizable(false)
 		.auto_sized()
 		.show(ctx, |ui| {
-			let mut pbr_param_ret = false;
-			let mut final_composite_ret = false;
-			let mut bloom_param_ret = false;
+			let mut changes = GuiChanges::default();
 			let mut albedo = [0.0f32; 3];
 			let mut emissive_color = [0.0f32; 3];
 
@@ -60,7 +137,16 @@ pub fn create_gui(
 			emissive_color[1] = pbr_param.emissive_color.y;
 			emissive_color[2] = pbr_param.emissive_color.z;
 
-			pbr_param_ret |= ui
+			changes.scene |= scene_gui(ui, scenes, scene);
+			// The grid replaces the sphere, a loaded scene replaces both
+			changes.sphere_grid |= ui
+				.add_enabled(
+					scene.is_none(),
+					egui::Checkbox::new(sphere_grid, "Sphere grid"),
+				)
+				.on_hover_text("Roughness grows to the right and metallic upward")
+				.changed();
+			changes.pbr_param |= ui
 				.add(
 					egui::Slider::new(&mut pbr_param.roughness, 0.0..=1.0)
 						.step_by(0.001)
@@ -68,7 +154,7 @@ pub fn create_gui(
 						.text("Roughness"),
 				)
 				.changed();
-			pbr_param_ret |= ui
+			changes.pbr_param |= ui
 				.add(
 					egui::Slider::new(&mut pbr_param.metallic, 0.0..=1.0)
 						.step_by(0.001)
@@ -76,7 +162,7 @@ pub fn create_gui(
 						.text("Metallic"),
 				)
 				.changed();
-			pbr_param_ret |= ui
+			changes.pbr_param |= ui
 				.add(
 					egui::Slider::new(&mut pbr_param.ao, 0.0..=1.0)
 						.step_by(0.001)
@@ -84,21 +170,21 @@ pub fn create_gui(
 						.text("Ao"),
 				)
 				.changed();
-			pbr_param_ret |= ui
+			changes.pbr_param |= ui
 				.horizontal(|ui| {
 					let ret = ui.color_edit_button_rgb(&mut albedo);
 					ui.label("Albedo");
 					ret.changed()
 				})
 				.inner;
-			pbr_param_ret |= ui
+			changes.pbr_param |= ui
 				.horizontal(|ui| {
 					let ret = ui.color_edit_button_rgb(&mut emissive_color);
 					ui.label("Emissive");
 					ret.changed()
 				})
 				.inner;
-			pbr_param_ret |= ui
+			changes.pbr_param |= ui
 				.add(
 					egui::Slider::new(&mut pbr_param.emissive_intensity, 0.0..=20.0)
 						.step_by(0.001)
@@ -106,7 +192,16 @@ pub fn create_gui(
 						.text("Emissive intensity"),
 				)
 				.changed();
-			final_composite_ret |= ui
+			changes.material |= material_gui(ui, materials, material);
+			changes.pbr_param |=
+				param_slider(ui, &mut pbr_param.normal_scale, 0.0..=2.0, "Normal scale");
+			changes.pbr_param |= param_slider(
+				ui,
+				&mut pbr_param.occlusion_strength,
+				0.0..=1.0,
+				"Occlusion strength",
+			);
+			changes.bloom_param |= ui
 				.add(
 					egui::Slider::new(bloom_intensity, 1.0..=100.0)
 						.step_by(0.001)
@@ -114,7 +209,7 @@ pub fn create_gui(
 						.text("Bloom intensity"),
 				)
 				.changed();
-			bloom_param_ret |= ui
+			changes.bloom_param |= ui
 				.add(
 					egui::Slider::new(bloom_threshold, 0.0..=50.0)
 						.step_by(0.001)
@@ -122,7 +217,7 @@ pub fn create_gui(
 						.text("Bloom threshold"),
 				)
 				.changed();
-			bloom_param_ret |= ui
+			changes.bloom_param |= ui
 				.add(
 					egui::Slider::new(bloom_knee, 0.01..=50.0)
 						.step_by(0.001)
@@ -130,6 +225,98 @@ pub fn create_gui(
 						.text("Bloom knee"),
 				)
 				.changed();
+			let mut compare = final_param.compare != 0;
+			if ui
+				.checkbox(&mut compare, "Compare without bloom")
+				.on_hover_text("Drag in the scene to move the divider")
+				.changed()
+			{
+				final_param.compare = compare as u32;
+				changes.final_composite = true;
+			}
+			post_process_gui(ui, post_process);
+			changes.tonemap |= tonemap_gui(ui, tonemap_param);
+			changes.lights |= lights_gui(ui, lights);
+			changes.shadow |= shadow_gui(ui, shadow);
+			changes.environment |= environment_gui(ui, environments, environment);
+			changes.pbr_param |= param_slider(
+				ui,
+				&mut pbr_param.environment_intensity,
+				0.0..=4.0,
+				"Environment intensity",
+			);
+			changes.skybox |= skybox_gui(ui, skybox);
+			// Before the exposure, which derives the camera exposure from the aperture
+			changes.exposure |= dof_gui(ui, dof, camera);
+			changes.exposure |= exposure_gui(ui, exposure_param, camera, histogram);
+			changes.grading |= grading_gui(ui, grading_luts, grading_lut);
+			changes.final_composite |= param_slider(
+				ui,
+				&mut final_param.grading_strength,
+				0.0..=1.0,
+				"Color grading strength",
+			);
+			changes.final_composite |= effects_gui(ui, final_param);
+			egui::ComboBox::from_label("Bloom format")
+				.selected_text(bloom_format.name())
+				.show_ui(ui, |ui| {
+					for format in bloom::BloomFormat::ALL {
+						let selected = *bloom_format == format;
+						if ui
+							.add_enabled(
+								format.is_supported(context),
+								egui::SelectableLabel::new(selected, format.name()),
+							)
+							.clicked() && !selected
+						{
+							*bloom_format = format;
+							changes.bloom_format = true;
+						}
+					}
+				});
+
+			egui::ComboBox::from_label("Anti-aliasing")
+				.selected_text(anti_aliasing.name())
+				.show_ui(ui, |ui| {
+					for mode in antialiasing::AntiAliasing::ALL {
+						let selected = *anti_aliasing == mode;
+						if ui
+							.add_enabled(
+								mode.is_supported(context),
+								egui::SelectableLabel::new(selected, mode.name()),
+							)
+							.clicked() && !selected
+						{
+							*anti_aliasing = mode;
+							changes.anti_aliasing = true;
+						}
+					}
+				});
+
+			egui::ComboBox::from_label("Output")
+				.selected_text(output_mode.name())
+				.show_ui(ui, |ui| {
+					for mode in output::OutputMode::ALL {
+						let selected = *output_mode == mode;
+						if ui
+							.add_enabled(
+								mode.is_available(context),
+								egui::SelectableLabel::new(selected, mode.name()),
+							)
+							.clicked() && !selected
+						{
+							*output_mode = mode;
+							changes.output = true;
+						}
+					}
+				});
+			if output_mode.is_hdr() {
+				ui.add(
+					egui::Slider::new(paper_white, 80.0..=1000.0)
+						.smart_aim(false)
+						.text("Paper white (nits)"),
+				);
+			}
 
 			pbr_param.albedo.x = albedo[0];
 			pbr_param.albedo.y = albedo[1];
@@ -139,18 +326,598 @@ pub fn create_gui(
 			pbr_param.emissive_color.y = emissive_color[1];
 			pbr_param.emissive_color.z = emissive_color[2];
 
-			if bloom_param_ret {
+			if changes.bloom_param {
 				bloom_param.parameters = uv::Vec4::new(
 					*bloom_threshold,
 					*bloom_threshold - *bloom_knee,
 					*bloom_knee * 2.0f32,
 					0.25f32 / *bloom_knee,
 				); // (x) threshold, (y) threshold - knee, (z) knee * 2, (w) 0.25 / knee
+				bloom_param.intensity = *bloom_intensity * 4.0;
 			}
 
-			(pbr_param_ret, final_composite_ret, bloom_param_ret)
+			changes
 		})
 		.unwrap()
 		.inner
 		.unwrap()
 }
+
+fn param_slider(
+	ui: &mut egui::Ui,
+	value: &mut f32,
+	range: std::ops::RangeInclusive<f32>,
+	text: &str,
+) -> bool {
+	ui.add(
+		egui::Slider::new(value, range)
+			.step_by(0.001)
+			.smart_aim(false)
+			.text(text),
+	)
+	.changed()
+}
+
+fn tonemap_gui(ui: &mut egui::Ui, param: &mut tonemap::TonemapParam) -> bool {
+	let mut ret = false;
+	let current = tonemap::Tonemapper::from_id(param.tonemapper);
+
+	egui::ComboBox::from_label("Tonemapper")
+		.selected_text(current.name())
+		.show_ui(ui, |ui| {
+			for tonemapper in tonemap::Tonemapper::ALL {
+				ret |= ui
+					.selectable_value(&mut param.tonemapper, tonemapper.id(), tonemapper.name())
+					.changed();
+			}
+		});
+
+	match current {
+		tonemap::Tonemapper::Clamp | tonemap::Tonemapper::Reinhard => {}
+		tonemap::Tonemapper::ReinhardExtended => {
+			ret |= param_slider(ui, &mut param.white_point, 0.1..=50.0, "White point");
+		}
+		tonemap::Tonemapper::Aces => {
+			ret |= param_slider(ui, &mut param.aces_a, 0.0..=5.0, "a");
+			ret |= param_slider(ui, &mut param.aces_b, 0.0..=1.0, "b");
+			ret |= param_slider(ui, &mut param.aces_c, 0.0..=5.0, "c");
+			ret |= param_slider(ui, &mut param.aces_d, 0.0..=2.0, "d");
+			ret |= param_slider(ui, &mut param.aces_e, 0.01..=1.0, "e");
+		}
+		tonemap::Tonemapper::Uchimura => {
+			ret |= param_slider(
+				ui,
+				&mut param.gt_max_brightness,
+				0.1..=10.0,
+				"Max brightness (P)",
+			);
+			ret |= param_slider(ui, &mut param.gt_contrast, 0.01..=5.0, "Contrast (a)");
+			ret |= param_slider(
+				ui,
+				&mut param.gt_linear_start,
+				0.01..=1.0,
+				"Linear start (m)",
+			);
+			ret |= param_slider(
+				ui,
+				&mut param.gt_linear_length,
+				0.0..=1.0,
+				"Linear length (l)",
+			);
+			ret |= param_slider(
+				ui,
+				&mut param.gt_black_tightness,
+				0.01..=3.0,
+				"Black tightness (c)",
+			);
+		}
+		tonemap::Tonemapper::AgX => {
+			ret |= param_slider(ui, &mut param.agx_offset, -0.5..=0.5, "Offset");
+			ret |= param_slider(ui, &mut param.agx_slope, 0.0..=3.0, "Slope");
+			ret |= param_slider(ui, &mut param.agx_power, 0.1..=3.0, "Power");
+			ret |= param_slider(ui, &mut param.agx_saturation, 0.0..=3.0, "Saturation");
+		}
+		tonemap::Tonemapper::Uncharted2 => {
+			ret |= param_slider(
+				ui,
+				&mut param.uc2_shoulder_strength,
+				0.0..=1.0,
+				"Shoulder strength (A)",
+			);
+			ret |= param_slider(
+				ui,
+				&mut param.uc2_linear_strength,
+				0.0..=1.0,
+				"Linear strength (B)",
+			);
+			ret |= param_slider(
+				ui,
+				&mut param.uc2_linear_angle,
+				0.0..=1.0,
+				"Linear angle (C)",
+			);
+			ret |= param_slider(
+				ui,
+				&mut param.uc2_toe_strength,
+				0.0..=1.0,
+				"Toe strength (D)",
+			);
+			ret |= param_slider(
+				ui,
+				&mut param.uc2_toe_numerator,
+				0.0..=0.1,
+				"Toe numerator (E)",
+			);
+			ret |= param_slider(
+				ui,
+				&mut param.uc2_toe_denominator,
+				0.01..=1.0,
+				"Toe denominator (F)",
+			);
+			ret |= param_slider(
+				ui,
+				&mut param.uc2_linear_white,
+				0.1..=50.0,
+				"Linear white (W)",
+			);
+			ret |= param_slider(
+				ui,
+				&mut param.uc2_exposure_bias,
+				0.1..=10.0,
+				"Exposure bias",
+			);
+		}
+	}
+
+	ret
+}
+
+/// Passes are applied from top to bottom, a disabled pass forwards its input untouched.
+fn post_process_gui(ui: &mut egui::Ui, post_process: &mut [post_process::PostProcessEntry]) {
+	ui.collapsing("Post processing", |ui| {
+		for entry in post_process.iter_mut() {
+			ui.checkbox(&mut entry.enabled, entry.pass.name());
+		}
+	});
+}
+
+fn effect_checkbox(ui: &mut egui::Ui, effects: &mut u32, effect: u32, text: &str) -> bool {
+	let mut enabled = *effects & effect != 0;
+	let ret = ui.checkbox(&mut enabled, text).changed();
+	if enabled {
+		*effects |= effect;
+	} else {
+		*effects &= !effect;
+	}
+	ret
+}
+
+fn effects_gui(ui: &mut egui::Ui, param: &mut bloom::FinalParam) -> bool {
+	let mut ret = false;
+
+	ret |= effect_checkbox(ui, &mut param.effects, effects::EFFECT_VIGNETTE, "Vignette");
+	if param.effects & effects::EFFECT_VIGNETTE != 0 {
+		ret |= param_slider(
+			ui,
+			&mut param.vignette_intensity,
+			0.0..=3.0,
+			"Vignette intensity",
+		);
+		ret |= param_slider(
+			ui,
+			&mut param.vignette_smoothness,
+			0.01..=1.0,
+			"Vignette smoothness",
+		);
+		ret |= param_slider(
+			ui,
+			&mut param.vignette_roundness,
+			0.0..=1.0,
+			"Vignette roundness",
+		);
+	}
+	ret |= effect_checkbox(
+		ui,
+		&mut param.effects,
+		effects::EFFECT_CHROMATIC_ABERRATION,
+		"Chromatic aberration",
+	);
+	if param.effects & effects::EFFECT_CHROMATIC_ABERRATION != 0 {
+		ret |= param_slider(
+			ui,
+			&mut param.chromatic_aberration,
+			0.0..=1.0,
+			"Aberration strength",
+		);
+	}
+	ret |= effect_checkbox(
+		ui,
+		&mut param.effects,
+		effects::EFFECT_FILM_GRAIN,
+		"Film grain",
+	);
+	if param.effects & effects::EFFECT_FILM_GRAIN != 0 {
+		ret |= param_slider(ui, &mut param.grain_intensity, 0.0..=1.0, "Grain intensity");
+		ret |= param_slider(ui, &mut param.grain_response, 0.0..=1.0, "Grain response");
+	}
+	ret |= effect_checkbox(ui, &mut param.effects, effects::EFFECT_DITHER, "Dithering");
+
+	ret
+}
+
+/// `None` is the procedural sky.
+fn environment_gui(
+	ui: &mut egui::Ui,
+	maps: &[std::path::PathBuf],
+	selected: &mut Option<usize>,
+) -> bool {
+	let map_name = |i: usize| maps[i].file_stem().unwrap().to_string_lossy().into_owned();
+	let mut ret = false;
+
+	egui::ComboBox::from_label("Environment")
+		.selected_text(selected.map_or("Procedural sky".to_owned(), map_name))
+		.show_ui(ui, |ui| {
+			ret |= ui
+				.selectable_value(selected, None, "Procedural sky")
+				.changed();
+			for i in 0..maps.len() {
+				ret |= ui
+					.selectable_value(selected, Some(i), map_name(i))
+					.changed();
+			}
+		});
+
+	ret
+}
+
+fn scene_gui(
+	ui: &mut egui::Ui,
+	scenes: &[std::path::PathBuf],
+	selected: &mut Option<usize>,
+) -> bool {
+	let scene_name = |i: usize| {
+		scenes[i]
+			.file_name()
+			.unwrap()
+			.to_string_lossy()
+			.into_owned()
+	};
+	let mut ret = false;
+
+	egui::ComboBox::from_label("Scene")
+		.selected_text(selected.map_or("Sphere".to_owned(), scene_name))
+		.show_ui(ui, |ui| {
+			ret |= ui.selectable_value(selected, None, "Sphere").changed();
+			for i in 0..scenes.len() {
+				ret |= ui
+					.selectable_value(selected, Some(i), scene_name(i))
+					.changed();
+			}
+		});
+
+	ret
+}
+
+fn material_gui(
+	ui: &mut egui::Ui,
+	materials: &[std::path::PathBuf],
+	selected: &mut Option<usize>,
+) -> bool {
+	let material_name = |i: usize| {
+		materials[i]
+			.file_name()
+			.unwrap()
+			.to_string_lossy()
+			.into_owned()
+	};
+	let mut ret = false;
+
+	egui::ComboBox::from_label("Material textures")
+		.selected_text(selected.map_or("None".to_owned(), material_name))
+		.show_ui(ui, |ui| {
+			ret |= ui.selectable_value(selected, None, "None").changed();
+			for i in 0..materials.len() {
+				ret |= ui
+					.selectable_value(selected, Some(i), material_name(i))
+					.changed();
+			}
+		});
+
+	ret
+}
+
+fn skybox_gui(ui: &mut egui::Ui, skybox: &mut skybox::SkyboxParam) -> bool {
+	let mut ret = false;
+	ui.collapsing("Skybox", |ui| {
+		let mut rotation = skybox.rotation.to_degrees();
+		if ui
+			.add(egui::Slider::new(&mut rotation, -180.0..=180.0).text("Rotation (deg)"))
+			.changed()
+		{
+			skybox.rotation = rotation.to_radians();
+			ret = true;
+		}
+		ret |= ui
+			.add(
+				egui::Slider::new(&mut skybox.intensity, 0.0..=10.0)
+					.logarithmic(true)
+					.text("Intensity"),
+			)
+			.changed();
+	});
+	ret
+}
+
+fn shadow_gui(ui: &mut egui::Ui, shadow: &mut shadow::ShadowSettings) -> bool {
+	let mut ret = false;
+	ui.collapsing("Shadows", |ui| {
+		ret |= ui
+			.checkbox(&mut shadow.enabled, "Shadow of the first light")
+			.changed();
+		egui::ComboBox::from_label("Resolution")
+			.selected_text(shadow.resolution.to_string())
+			.show_ui(ui, |ui| {
+				for resolution in shadow::SHADOW_RESOLUTIONS {
+					ret |= ui
+						.selectable_value(
+							&mut shadow.resolution,
+							resolution,
+							resolution.to_string(),
+						)
+						.changed();
+				}
+			});
+		ret |= ui
+			.add(
+				egui::Slider::new(&mut shadow.depth_bias, 0.0..=0.01)
+					.logarithmic(true)
+					.text("Depth bias"),
+			)
+			.changed();
+		ret |= ui
+			.add(egui::Slider::new(&mut shadow.normal_bias, 0.0..=0.2).text("Normal bias"))
+			.changed();
+	});
+	ret
+}
+
+fn grading_gui(
+	ui: &mut egui::Ui,
+	luts: &[std::path::PathBuf],
+	selected: &mut Option<usize>,
+) -> bool {
+	let lut_name = |i: usize| luts[i].file_stem().unwrap().to_string_lossy().into_owned();
+	let mut ret = false;
+
+	egui::ComboBox::from_label("Color grading LUT")
+		.selected_text(selected.map_or("None".to_owned(), lut_name))
+		.show_ui(ui, |ui| {
+			ret |= ui.selectable_value(selected, None, "None").changed();
+			for i in 0..luts.len() {
+				ret |= ui
+					.selectable_value(selected, Some(i), lut_name(i))
+					.changed();
+			}
+		});
+
+	ret
+}
+
+fn vec3_drag(ui: &mut egui::Ui, value: &mut uv::Vec3, text: &str) -> bool {
+	ui.horizontal(|ui| {
+		let mut ret = false;
+		for v in value.as_mut_slice() {
+			ret |= ui.add(egui::DragValue::new(v).speed(0.05)).changed();
+		}
+		ui.label(text);
+		ret
+	})
+	.inner
+}
+
+fn lights_gui(ui: &mut egui::Ui, lights: &mut Vec<lights::Light>) -> bool {
+	let mut ret = false;
+	ui.collapsing("Lights", |ui| {
+		let mut removed = None;
+		for (i, light) in lights.iter_mut().enumerate() {
+			ui.separator();
+			egui::ComboBox::from_label(format!("Light {}", i))
+				.selected_text(light.kind.name())
+				.show_ui(ui, |ui| {
+					for kind in lights::LightKind::ALL {
+						ret |= ui
+							.selectable_value(&mut light.kind, kind, kind.name())
+							.changed();
+					}
+				});
+			if light.kind != lights::LightKind::Directional {
+				ret |= vec3_drag(ui, &mut light.position, "Position");
+			}
+			if light.kind != lights::LightKind::Point {
+				ret |= vec3_drag(ui, &mut light.direction, "Direction");
+			}
+			let mut color = [light.color.x, light.color.y, light.color.z];
+			ret |= ui
+				.horizontal(|ui| {
+					let ret = ui.color_edit_button_rgb(&mut color);
+					ui.label("Color");
+					ret.changed()
+				})
+				.inner;
+			light.color = uv::Vec3::from(color);
+			ret |= ui
+				.add(
+					egui::Slider::new(&mut light.intensity, 0.0..=1000.0)
+						.logarithmic(true)
+						.text("Intensity"),
+				)
+				.changed();
+			if light.kind != lights::LightKind::Directional {
+				ret |= ui
+					.add(
+						egui::Slider::new(&mut light.range, 0.1..=1000.0)
+							.logarithmic(true)
+							.text("Range"),
+					)
+					.changed();
+			}
+			if light.kind == lights::LightKind::Spot {
+				let mut inner = light.inner_angle.to_degrees();
+				let mut outer = light.outer_angle.to_degrees();
+				ret |= ui
+					.add(egui::Slider::new(&mut inner, 0.0..=89.0).text("Inner angle (deg)"))
+					.changed();
+				ret |= ui
+					.add(egui::Slider::new(&mut outer, 0.0..=89.0).text("Outer angle (deg)"))
+					.changed();
+				light.inner_angle = inner.to_radians();
+				light.outer_angle = outer.to_radians();
+			}
+			if ui.button("Remove").clicked() {
+				removed = Some(i);
+			}
+		}
+		if let Some(i) = removed {
+			lights.remove(i);
+			ret = true;
+		}
+		ui.separator();
+		if ui
+			.add_enabled(
+				lights.len() < lights::MAX_LIGHTS,
+				egui::Button::new("Add light"),
+			)
+			.clicked()
+		{
+			lights.push(lights::Light::default());
+			ret = true;
+		}
+	});
+	ret
+}
+
+/// Returns true when the camera aperture changed.
+fn dof_gui(
+	ui: &mut egui::Ui,
+	dof: &mut dof::DofSettings,
+	camera: &mut camera::PerspectiveCamera,
+) -> bool {
+	let mut ret = false;
+	ui.collapsing("Depth of field", |ui| {
+		ret |= ui
+			.add(
+				egui::Slider::new(&mut camera.aperture, 1.0..=22.0)
+					.logarithmic(true)
+					.text("Aperture (f-number)"),
+			)
+			.changed();
+		if ui
+			.add(
+				egui::Slider::new(&mut dof.focus_distance, camera.near..=100.0)
+					.logarithmic(true)
+					.text("Focus distance (m)"),
+			)
+			.changed()
+		{
+			dof.focus_point = None;
+		}
+		ui.add(egui::Slider::new(&mut dof.max_coc, 1.0..=64.0).text("Max blur radius (px)"));
+		ui.horizontal(|ui| {
+			ui.checkbox(&mut dof.pick_focus, "Click to focus");
+			if let Some((x, y)) = dof.focus_point {
+				ui.label(format!("Focused on pixel {}, {}", x, y));
+			}
+		});
+	});
+	ret
+}
+
+fn exposure_gui(
+	ui: &mut egui::Ui,
+	param: &mut exposure::ExposureParam,
+	camera: &mut camera::PerspectiveCamera,
+	histogram: &[u32],
+) -> bool {
+	let mut ret = false;
+	let current = exposure::ExposureMode::from_id(param.mode);
+
+	egui::ComboBox::from_label("Exposure")
+		.selected_text(current.name())
+		.show_ui(ui, |ui| {
+			for mode in exposure::ExposureMode::ALL {
+				ret |= ui
+					.selectable_value(&mut param.mode, mode.id(), mode.name())
+					.changed();
+			}
+		});
+
+	match current {
+		exposure::ExposureMode::Fixed => {}
+		exposure::ExposureMode::Auto => {
+			ret |= param_slider(ui, &mut param.min_ev, -16.0..=param.max_ev - 1.0, "Min EV");
+			ret |= param_slider(ui, &mut param.max_ev, param.min_ev + 1.0..=16.0, "Max EV");
+			ret |= param_slider(ui, &mut param.speed_up, 0.01..=10.0, "Adaptation speed up");
+			ret |= param_slider(
+				ui,
+				&mut param.speed_down,
+				0.01..=10.0,
+				"Adaptation speed down",
+			);
+		}
+		exposure::ExposureMode::Physical => {
+			ret |= ui
+				.add(
+					egui::Slider::new(&mut camera.aperture, 1.0..=22.0)
+						.logarithmic(true)
+						.text("Aperture (f-number)"),
+				)
+				.changed();
+			let mut shutter_speed = 1.0 / camera.shutter_speed;
+			if ui
+				.add(
+					egui::Slider::new(&mut shutter_speed, 1.0..=8000.0)
+						.logarithmic(true)
+						.prefix("1/")
+						.text("Shutter speed (s)"),
+				)
+				.changed()
+			{
+				camera.shutter_speed = 1.0 / shutter_speed;
+				ret = true;
+			}
+			ret |= ui
+				.add(
+					egui::Slider::new(&mut camera.iso, 50.0..=25600.0)
+						.logarithmic(true)
+						.text("ISO"),
+				)
+				.changed();
+			ui.label(format!("EV100: {:.2}", camera.ev100()));
+		}
+	}
+	ret |= param_slider(
+		ui,
+		&mut param.compensation,
+		-5.0..=5.0,
+		"Exposure compensation",
+	);
+	param.camera_exposure = camera.exposure();
+
+	// Bin 0 only counts black pixels, it would flatten the rest of the chart
+	let bars = histogram
+		.iter()
+		.enumerate()
+		.skip(1)
+		.map(|(i, &count)| egui::plot::Bar::new(i as f64, count as f64).width(1.0))
+		.collect();
+	egui::plot::Plot::new("luminance histogram")
+		.height(80.0)
+		.allow_drag(false)
+		.allow_zoom(false)
+		.show_axes([false, false])
+		.show(ui, |plot_ui| {
+			plot_ui.bar_chart(egui::plot::BarChart::new(bars).name("Luminance histogram"))
+		});
+
+	ret
+}
@@ -0,0 +1,338 @@
+/// JSON document, objects keep the order of their members.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Value {
+	Null,
+	Bool(bool),
+	Number(f64),
+	String(String),
+	Array(Vec<Value>),
+	Object(Vec<(String, Value)>),
+}
+
+static NULL: Value = Value::Null;
+
+impl Value {
+	/// Member `key` of an object, `Null` when absent or not an object.
+	pub fn get(&self, key: &str) -> &Value {
+		match self {
+			Value::Object(members) => members
+				.iter()
+				.find(|(k, _)| k == key)
+				.map_or(&NULL, |(_, v)| v),
+			_ => &NULL,
+		}
+	}
+
+	/// Element `index` of an array, `Null` when absent or not an array.
+	pub fn at(&self, index: usize) -> &Value {
+		match self {
+			Value::Array(elements) => elements.get(index).unwrap_or(&NULL),
+			_ => &NULL,
+		}
+	}
+
+	pub fn is_null(&self) -> bool {
+		matches!(self, Value::Null)
+	}
+
+	pub fn as_f64(&self) -> Option<f64> {
+		match self {
+			Value::Number(n) => Some(*n),
+			_ => None,
+		}
+	}
+
+	pub fn as_f32(&self) -> Option<f32> {
+		self.as_f64().map(|n| n as f32)
+	}
+
+	/// Numbers that are non negative integers.
+	pub fn as_usize(&self) -> Option<usize> {
+		match self {
+			Value::Number(n) if *n >= 0.0 && n.fract() == 0.0 => Some(*n as usize),
+			_ => None,
+		}
+	}
+
+	pub fn as_bool(&self) -> Option<bool> {
+		match self {
+			Value::Bool(b) => Some(*b),
+			_ => None,
+		}
+	}
+
+	pub fn as_str(&self) -> Option<&str> {
+		match self {
+			Value::String(s) => Some(s),
+			_ => None,
+		}
+	}
+
+	/// Elements of an array, empty when not an array.
+	pub fn elements(&self) -> &[Value] {
+		match self {
+			Value::Array(elements) => elements,
+			_ => &[],
+		}
+	}
+
+	/// Array of `N` numbers.
+	pub fn as_f32_array<const N: usize>(&self) -> Option<[f32; N]> {
+		let elements = self.elements();
+		if elements.len() != N {
+			return None;
+		}
+		let mut array = [0.0; N];
+		for (value, element) in array.iter_mut().zip(elements) {
+			*value = element.as_f32()?;
+		}
+		Some(array)
+	}
+}
+
+pub fn parse(source: &str) -> Result<Value, String> {
+	let mut parser = Parser {
+		source: source.as_bytes(),
+		position: 0,
+	};
+	let value = parser.value(0)?;
+	parser.skip_whitespace();
+	if parser.position != parser.source.len() {
+		return Err(parser.error("trailing characters"));
+	}
+	Ok(value)
+}
+
+/// Nesting depth past which the document is rejected instead of overflowing the stack.
+const MAX_DEPTH: usize = 512;
+
+struct Parser<'a> {
+	source: &'a [u8],
+	position: usize,
+}
+
+impl Parser<'_> {
+	fn error(&self, message: &str) -> String {
+		format!("{} at byte {}", message, self.position)
+	}
+
+	fn skip_whitespace(&mut self) {
+		while let Some(b' ' | b'\t' | b'\n' | b'\r') = self.source.get(self.position) {
+			self.position += 1;
+		}
+	}
+
+	fn peek(&self) -> Option<u8> {
+		self.source.get(self.position).copied()
+	}
+
+	fn expect(&mut self, literal: &str) -> Result<(), String> {
+		if self.source[self.position..].starts_with(literal.as_bytes()) {
+			self.position += literal.len();
+			Ok(())
+		} else {
+			Err(self.error(&format!("expected {}", literal)))
+		}
+	}
+
+	fn value(&mut self, depth: usize) -> Result<Value, String> {
+		if depth > MAX_DEPTH {
+			return Err(self.error("too deeply nested"));
+		}
+		self.skip_whitespace();
+		match self.peek() {
+			Some(b'n') => self.expect("null").map(|_| Value::Null),
+			Some(b't') => self.expect("true").map(|_| Value::Bool(true)),
+			Some(b'f') => self.expect("false").map(|_| Value::Bool(false)),
+			Some(b'"') => self.string().map(Value::String),
+			Some(b'[') => {
+				self.position += 1;
+				let mut elements = Vec::new();
+				self.skip_whitespace();
+				if self.peek() == Some(b']') {
+					self.position += 1;
+					return Ok(Value::Array(elements));
+				}
+				loop {
+					elements.push(self.value(depth + 1)?);
+					self.skip_whitespace();
+					match self.peek() {
+						Some(b',') => self.position += 1,
+						Some(b']') => {
+							self.position += 1;
+							return Ok(Value::Array(elements));
+						}
+						_ => return Err(self.error("expected , or ]")),
+					}
+				}
+			}
+			Some(b'{') => {
+				self.position += 1;
+				let mut members = Vec::new();
+				self.skip_whitespace();
+				if self.peek() == Some(b'}') {
+					self.position += 1;
+					return Ok(Value::Object(members));
+				}
+				loop {
+					self.skip_whitespace();
+					let key = self.string()?;
+					self.skip_whitespace();
+					self.expect(":")?;
+					members.push((key, self.value(depth + 1)?));
+					self.skip_whitespace();
+					match self.peek() {
+						Some(b',') => self.position += 1,
+						Some(b'}') => {
+							self.position += 1;
+							return Ok(Value::Object(members));
+						}
+						_ => return Err(self.error("expected , or }")),
+					}
+				}
+			}
+			Some(b'-' | b'0'..=b'9') => self.number(),
+			Some(_) => Err(self.error("unexpected character")),
+			None => Err(self.error("unexpected end of the document")),
+		}
+	}
+
+	fn number(&mut self) -> Result<Value, String> {
+		let start = self.position;
+		while let Some(b'-' | b'+' | b'.' | b'e' | b'E' | b'0'..=b'9') = self.peek() {
+			self.position += 1;
+		}
+		let text = std::str::from_utf8(&self.source[start..self.position]).unwrap();
+		text.parse::<f64>()
+			.map(Value::Number)
+			.map_err(|_| self.error("invalid number"))
+	}
+
+	fn hex4(&mut self) -> Result<u32, String> {
+		let digits = self
+			.source
+			.get(self.position..self.position + 4)
+			.and_then(|d| std::str::from_utf8(d).ok())
+			.and_then(|d| u32::from_str_radix(d, 16).ok())
+			.ok_or_else(|| self.error("invalid unicode escape"))?;
+		self.position += 4;
+		Ok(digits)
+	}
+
+	fn string(&mut self) -> Result<String, String> {
+		self.expect("\"")?;
+		let mut bytes = Vec::new();
+		loop {
+			let byte = self
+				.peek()
+				.ok_or_else(|| self.error("unterminated string"))?;
+			self.position += 1;
+			match byte {
+				b'"' => break,
+				b'\\' => {
+					let escape = self
+						.peek()
+						.ok_or_else(|| self.error("unterminated string"))?;
+					self.position += 1;
+					let c = match escape {
+						b'"' => '"',
+						b'\\' => '\\',
+						b'/' => '/',
+						b'b' => '\u{8}',
+						b'f' => '\u{c}',
+						b'n' => '\n',
+						b'r' => '\r',
+						b't' => '\t',
+						b'u' => {
+							let mut code = self.hex4()?;
+							// Characters outside of the basic plane are surrogate pairs
+							if (0xd800..0xdc00).contains(&code) {
+								self.expect("\\u")?;
+								let low = self.hex4()?;
+								if !(0xdc00..0xe000).contains(&low) {
+									return Err(self.error("invalid surrogate pair"));
+								}
+								code = 0x10000 + ((code - 0xd800) << 10) + (low - 0xdc00);
+							}
+							char::from_u32(code).ok_or_else(|| self.error("invalid character"))?
+						}
+						_ => return Err(self.error("invalid escape")),
+					};
+					bytes.extend_from_slice(c.encode_utf8(&mut [0; 4]).as_bytes());
+				}
+				_ => bytes.push(byte),
+			}
+		}
+		String::from_utf8(bytes).map_err(|_| self.error("invalid utf-8"))
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn values_keep_their_order() {
+		let value = parse(r#" { "b": [1, -2.5e1, true, null], "a": { "c": "d" } } "#).unwrap();
+		assert_eq!(
+			value,
+			Value::Object(vec![
+				(
+					"b".to_owned(),
+					Value::Array(vec![
+						Value::Number(1.0),
+						Value::Number(-25.0),
+						Value::Bool(true),
+						Value::Null,
+					]),
+				),
+				(
+					"a".to_owned(),
+					Value::Object(vec![("c".to_owned(), Value::String("d".to_owned()))]),
+				),
+			])
+		);
+		assert_eq!(value.get("b").at(1).as_f32(), Some(-25.0));
+		assert!(value.get("missing").at(3).is_null());
+	}
+
+	#[test]
+	fn escapes_and_surrogates() {
+		let value = parse(r#""\"\\\/\b\f\n\r\t é 😀 é""#).unwrap();
+		assert_eq!(value.as_str(), Some("\"\\/\u{8}\u{c}\n\r\t é 😀 é"));
+
+		// Lone and reversed surrogates
+		assert!(parse(r#""\ud83d""#).is_err());
+		assert!(parse(r#""\ud83dA""#).is_err());
+		assert!(parse(r#""\ude00\ud83d""#).is_err());
+		assert!(parse(r#""\x""#).is_err());
+		assert!(parse(r#""\u12""#).is_err());
+		assert!(parse(r#""unterminated"#).is_err());
+	}
+
+	#[test]
+	fn malformed_documents_are_rejected() {
+		for source in [
+			"[1, 2,]",
+			r#"{"a": 1,}"#,
+			"[1 2]",
+			r#"{"a" 1}"#,
+			r#"{a: 1}"#,
+			"nul",
+			"1 2",
+			"",
+			"-",
+		] {
+			assert!(parse(source).is_err(), "{}", source);
+		}
+	}
+
+	#[test]
+	fn depth_limit() {
+		// The innermost array is at depth MAX_DEPTH
+		let nested = |depth: usize| "[".repeat(depth + 1) + &"]".repeat(depth + 1);
+		assert!(parse(&nested(MAX_DEPTH)).is_ok());
+		assert!(parse(&nested(MAX_DEPTH + 1)).is_err());
+		assert!(parse(&"[".repeat(100_000)).is_err());
+	}
+}
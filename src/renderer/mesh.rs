@@ -1,7 +1,11 @@
+use std::ops::Range;
+
 use wgpu::util::DeviceExt;
 
 use super::{texture::Texture, Vertex};
 
+pub mod primitives;
+
 pub struct Material {
 	pub render_pipeline: Option<wgpu::RenderPipeline>,
 	pub compute_pipeline: Option<wgpu::ComputePipeline>,
@@ -10,19 +14,38 @@ pub struct Material {
 	pub bind_groups_buffers: Vec<wgpu::Buffer>,
 	pub bind_groups_textures: Vec<Texture>,
 	pub push_constant: Vec<wgpu::PushConstantRange>,
+	pub vertex_layouts: Vec<wgpu::VertexBufferLayout<'static>>, // the first is the one of Vertex
 }
 
 pub struct Mesh {
 	pub vertex_buffer: Option<wgpu::Buffer>,
 	pub index_buffer: Option<wgpu::Buffer>,
+	pub index_format: wgpu::IndexFormat,
 	pub draw_count: u32,
 	pub model: ultraviolet::Mat4,
 	pub material: Option<Material>,
 }
 
-pub trait IndexType {}
-impl IndexType for u16 {}
-impl IndexType for u32 {}
+pub trait IndexType: Copy {
+	const FORMAT: wgpu::IndexFormat;
+
+	/// Index of a vertex, which has to fit in the type.
+	fn from_u32(index: u32) -> Self;
+}
+impl IndexType for u16 {
+	const FORMAT: wgpu::IndexFormat = wgpu::IndexFormat::Uint16;
+
+	fn from_u32(index: u32) -> Self {
+		u16::try_from(index).expect("vertex index does not fit in 16 bits")
+	}
+}
+impl IndexType for u32 {
+	const FORMAT: wgpu::IndexFormat = wgpu::IndexFormat::Uint32;
+
+	fn from_u32(index: u32) -> Self {
+		index
+	}
+}
 
 impl Mesh {
 	#![allow(unused)]
@@ -65,11 +88,103 @@ impl Mesh {
 		Mesh {
 			vertex_buffer,
 			index_buffer,
+			index_format: T::FORMAT,
 			draw_count,
 			material,
 			model: ultraviolet::Mat4::identity(),
 		}
 	}
+
+	/// Draws `instances` of the mesh with the vertex buffer in the first slot, indexed when it
+	/// has indices. The pipeline and bind groups have to be set.
+	pub fn draw<'a>(&'a self, render_pass: &mut wgpu::RenderPass<'a>, instances: Range<u32>) {
+		if let Some(vertex_buffer) = &self.vertex_buffer {
+			render_pass.set_vertex_buffer(0, vertex_buffer.slice(..));
+		}
+		match &self.index_buffer {
+			Some(index_buffer) => {
+				render_pass.set_index_buffer(index_buffer.slice(..), self.index_format);
+				render_pass.draw_indexed(0..self.draw_count, 0, instances);
+			}
+			None => render_pass.draw(0..self.draw_count, instances),
+		}
+	}
+}
+
+/// Smooth normals, averaged over the triangles sharing a vertex and weighted by their area.
+pub fn compute_normals(vertices: &mut [Vertex], indices: &[u32]) {
+	for triangle in indices.chunks_exact(3) {
+		let [a, b, c] = [
+			triangle[0] as usize,
+			triangle[1] as usize,
+			triangle[2] as usize,
+		];
+		let normal = (vertices[b].position - vertices[a].position)
+			.cross(vertices[c].position - vertices[a].position);
+		for vertex in [a, b, c] {
+			vertices[vertex].normal += normal;
+		}
+	}
+	for vertex in vertices.iter_mut() {
+		vertex.normal = if vertex.normal.mag_sq() > 0.0 {
+			vertex.normal.normalized()
+		} else {
+			ultraviolet::Vec3::unit_y()
+		};
+	}
+}
+
+/// Tangents of a triangle list from its uvs, averaged over the triangles sharing a vertex and
+/// orthogonalized against the normals.
+pub fn compute_tangents(vertices: &mut [Vertex], indices: Option<&[u32]>) {
+	let count = indices.map_or(vertices.len(), |i| i.len());
+	let index = |i: usize| indices.map_or(i, |indices| indices[i] as usize);
+
+	let mut tangents = vec![ultraviolet::Vec3::zero(); vertices.len()];
+	let mut bitangents = vec![ultraviolet::Vec3::zero(); vertices.len()];
+	for triangle in 0..count / 3 {
+		let [a, b, c] = [
+			index(triangle * 3),
+			index(triangle * 3 + 1),
+			index(triangle * 3 + 2),
+		];
+		let edge1 = vertices[b].position - vertices[a].position;
+		let edge2 = vertices[c].position - vertices[a].position;
+		let duv1 = vertices[b].uv - vertices[a].uv;
+		let duv2 = vertices[c].uv - vertices[a].uv;
+		let determinant = duv1.x * duv2.y - duv2.x * duv1.y;
+		if determinant.abs() < 1e-12 {
+			continue;
+		}
+		let tangent = (edge1 * duv2.y - edge2 * duv1.y) / determinant;
+		let bitangent = (edge2 * duv1.x - edge1 * duv2.x) / determinant;
+		for vertex in [a, b, c] {
+			tangents[vertex] += tangent;
+			bitangents[vertex] += bitangent;
+		}
+	}
+
+	for (i, vertex) in vertices.iter_mut().enumerate() {
+		let normal = vertex.normal;
+		let mut tangent = tangents[i] - normal * normal.dot(tangents[i]);
+		// Without uvs any direction orthogonal to the normal is as good
+		if tangent.mag_sq() < 1e-12 {
+			let axis = if normal.x.abs() < 0.9 {
+				ultraviolet::Vec3::unit_x()
+			} else {
+				ultraviolet::Vec3::unit_y()
+			};
+			tangent = axis - normal * normal.dot(axis);
+		}
+		let tangent = tangent.normalized();
+		// The bitangent of pbr.wgsl points toward the top of the textures, where v decreases
+		let sign = if normal.cross(tangent).dot(bitangents[i]) > 0.0 {
+			-1.0
+		} else {
+			1.0
+		};
+		vertex.tangent = ultraviolet::Vec4::new(tangent.x, tangent.y, tangent.z, sign);
+	}
 }
 
 impl Material {
@@ -83,6 +198,7 @@ impl Material {
 			bind_groups_buffers: vec![],
 			bind_groups_textures: vec![],
 			push_constant: Vec::with_capacity(number_of_push_constant),
+			vertex_layouts: vec![Vertex::layout()],
 		}
 	}
 
@@ -120,6 +236,28 @@ impl Material {
 		self.bind_group_layout.push(layout);
 	}
 
+	/// Recreates the bind group `index` with new resources, its layout is kept.
+	pub fn set_bind_group(
+		&mut self,
+		device: &wgpu::Device,
+		label: Option<&str>,
+		index: usize,
+		bindings: Vec<wgpu::BindingResource>,
+	) {
+		let mut binding_entries = Vec::with_capacity(bindings.len());
+		for (i, resource) in bindings.into_iter().enumerate() {
+			binding_entries.push(wgpu::BindGroupEntry {
+				binding: i as u32,
+				resource,
+			});
+		}
+		self.bind_group[index] = device.create_bind_group(&wgpu::BindGroupDescriptor {
+			label,
+			layout: &self.bind_group_layout[index],
+			entries: &binding_entries,
+		});
+	}
+
 	pub fn add_push_constant(&mut self, stage: wgpu::ShaderStages, range: std::ops::Range<u32>) {
 		self.push_constant.push(wgpu::PushConstantRange {
 			stages: stage,
@@ -127,6 +265,11 @@ impl Material {
 		});
 	}
 
+	/// Adds a vertex buffer to the pipeline, after the one of `Vertex`.
+	pub fn add_vertex_layout(&mut self, layout: wgpu::VertexBufferLayout<'static>) {
+		self.vertex_layouts.push(layout);
+	}
+
 	pub fn set_render_pipeline(
 		&mut self,
 		device: &wgpu::Device,
@@ -161,7 +304,7 @@ impl Material {
 			vertex: wgpu::VertexState {
 				module: &shader,
 				entry_point: "vs_main",
-				buffers: &[Vertex::layout()],
+				buffers: &self.vertex_layouts,
 			},
 			primitive: wgpu::PrimitiveState {
 				topology: wgpu::PrimitiveTopology::TriangleList,
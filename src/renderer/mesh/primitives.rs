@@ -0,0 +1,423 @@
+use std::f32::consts::{PI, TAU};
+
+use super::{compute_tangents, IndexType};
+use crate::renderer::Vertex;
+
+// The meshes are centered on the origin with y up. Seen from the outside the triangles are
+// counter clockwise, u goes right and v down, from the top left corner of the textures.
+
+fn vertex(position: uv::Vec3, normal: uv::Vec3, uv: uv::Vec2) -> Vertex {
+	Vertex {
+		position,
+		normal,
+		uv,
+		..Default::default()
+	}
+}
+
+fn finish<T: IndexType>(mut vertices: Vec<Vertex>, indices: Vec<u32>) -> (Vec<Vertex>, Vec<T>) {
+	compute_tangents(&mut vertices, Some(&indices));
+	(vertices, indices.into_iter().map(T::from_u32).collect())
+}
+
+/// Direction of longitude `phi` and colatitude `theta`, `phi` grows eastward.
+fn sphere_direction(theta: f32, phi: f32) -> uv::Vec3 {
+	uv::Vec3::new(
+		theta.sin() * phi.cos(),
+		theta.cos(),
+		-theta.sin() * phi.sin(),
+	)
+}
+
+/// Sphere of `sectors` meridians and `stacks` parallels, the first and last meridians are
+/// duplicated for the seam of the uvs.
+pub fn uv_sphere<T: IndexType>(radius: f32, sectors: u32, stacks: u32) -> (Vec<Vertex>, Vec<T>) {
+	assert!(sectors >= 3 && stacks >= 2);
+	let mut vertices = Vec::with_capacity(((sectors + 1) * (stacks + 1)) as usize);
+	for i in 0..=stacks {
+		for j in 0..=sectors {
+			// The longitude of the poles is the one of the middle of their triangle
+			let u = if i == 0 || i == stacks {
+				(j as f32 + 0.5) / sectors as f32
+			} else {
+				j as f32 / sectors as f32
+			};
+			let v = i as f32 / stacks as f32;
+			let normal = sphere_direction(v * PI, u * TAU);
+			vertices.push(vertex(normal * radius, normal, uv::Vec2::new(u, v)));
+		}
+	}
+
+	let mut indices = Vec::with_capacity((6 * sectors * (stacks - 1)) as usize);
+	for i in 0..stacks {
+		for j in 0..sectors {
+			let top = i * (sectors + 1) + j;
+			let bottom = top + sectors + 1;
+			if i != 0 {
+				indices.extend([top, bottom, top + 1]);
+			}
+			if i != stacks - 1 {
+				indices.extend([top + 1, bottom, bottom + 1]);
+			}
+		}
+	}
+	finish(vertices, indices)
+}
+
+/// Icosahedron whose faces are split in 4 `subdivisions` times. The vertices on the seam of the
+/// uvs and at the poles are duplicated.
+#[allow(dead_code)]
+pub fn icosphere<T: IndexType>(radius: f32, subdivisions: u32) -> (Vec<Vertex>, Vec<T>) {
+	let t = (1.0 + 5f32.sqrt()) / 2.0;
+	let mut positions: Vec<uv::Vec3> = [
+		(-1.0, t, 0.0),
+		(1.0, t, 0.0),
+		(-1.0, -t, 0.0),
+		(1.0, -t, 0.0),
+		(0.0, -1.0, t),
+		(0.0, 1.0, t),
+		(0.0, -1.0, -t),
+		(0.0, 1.0, -t),
+		(t, 0.0, -1.0),
+		(t, 0.0, 1.0),
+		(-t, 0.0, -1.0),
+		(-t, 0.0, 1.0),
+	]
+	.iter()
+	.map(|&(x, y, z)| uv::Vec3::new(x, y, z).normalized())
+	.collect();
+	let mut triangles: Vec<[u32; 3]> = vec![
+		[0, 11, 5],
+		[0, 5, 1],
+		[0, 1, 7],
+		[0, 7, 10],
+		[0, 10, 11],
+		[1, 5, 9],
+		[5, 11, 4],
+		[11, 10, 2],
+		[10, 7, 6],
+		[7, 1, 8],
+		[3, 9, 4],
+		[3, 4, 2],
+		[3, 2, 6],
+		[3, 6, 8],
+		[3, 8, 9],
+		[4, 9, 5],
+		[2, 4, 11],
+		[6, 2, 10],
+		[8, 6, 7],
+		[9, 8, 1],
+	];
+
+	for _ in 0..subdivisions {
+		let mut midpoints = hashbrown::HashMap::new();
+		let mut midpoint = |a: u32, b: u32| {
+			*midpoints.entry((a.min(b), a.max(b))).or_insert_with(|| {
+				let position = (positions[a as usize] + positions[b as usize]).normalized();
+				positions.push(position);
+				positions.len() as u32 - 1
+			})
+		};
+		triangles = triangles
+			.iter()
+			.flat_map(|&[a, b, c]| {
+				let [ab, bc, ca] = [midpoint(a, b), midpoint(b, c), midpoint(c, a)];
+				[[a, ab, ca], [b, bc, ab], [c, ca, bc], [ab, bc, ca]]
+			})
+			.collect();
+	}
+
+	let mut vertices: Vec<Vertex> = positions
+		.iter()
+		.map(|&normal| {
+			let u = ((-normal.z).atan2(normal.x) / TAU).rem_euclid(1.0);
+			let v = normal.y.clamp(-1.0, 1.0).acos() / PI;
+			vertex(normal * radius, normal, uv::Vec2::new(u, v))
+		})
+		.collect();
+	let mut wrapped = hashbrown::HashMap::new();
+	let mut indices = Vec::with_capacity(triangles.len() * 3);
+	for mut triangle in triangles {
+		// Triangles across the seam use copies of their vertices on the left of the textures
+		let us = triangle.map(|i| vertices[i as usize].uv.x);
+		let max_u = us.iter().fold(0.0f32, |m, &u| m.max(u));
+		for (index, u) in triangle.iter_mut().zip(us) {
+			if max_u - u > 0.5 {
+				*index = *wrapped.entry(*index).or_insert_with(|| {
+					let mut copy = vertices[*index as usize];
+					copy.uv.x += 1.0;
+					vertices.push(copy);
+					vertices.len() as u32 - 1
+				});
+			}
+		}
+		// The poles take the longitude of the rest of their triangle
+		let is_pole = |v: &Vertex| v.normal.x.abs() < 1e-6 && v.normal.z.abs() < 1e-6;
+		if let Some(pole) = triangle
+			.iter()
+			.position(|&i| is_pole(&vertices[i as usize]))
+		{
+			let mut copy = vertices[triangle[pole] as usize];
+			copy.uv.x = (0..3)
+				.filter(|&i| i != pole)
+				.map(|i| vertices[triangle[i] as usize].uv.x)
+				.sum::<f32>()
+				/ 2.0;
+			vertices.push(copy);
+			triangle[pole] = vertices.len() as u32 - 1;
+		}
+		indices.extend(triangle);
+	}
+	finish(vertices, indices)
+}
+
+/// Cube of side `size`, each face has its own vertices and the whole textures.
+#[allow(dead_code)]
+pub fn cube<T: IndexType>(size: f32) -> (Vec<Vertex>, Vec<T>) {
+	let half = size / 2.0;
+	// Normal, then the directions of u and v on the face
+	let faces = [
+		(uv::Vec3::unit_x(), -uv::Vec3::unit_z(), -uv::Vec3::unit_y()),
+		(-uv::Vec3::unit_x(), uv::Vec3::unit_z(), -uv::Vec3::unit_y()),
+		(uv::Vec3::unit_y(), uv::Vec3::unit_x(), uv::Vec3::unit_z()),
+		(-uv::Vec3::unit_y(), uv::Vec3::unit_x(), -uv::Vec3::unit_z()),
+		(uv::Vec3::unit_z(), uv::Vec3::unit_x(), -uv::Vec3::unit_y()),
+		(
+			-uv::Vec3::unit_z(),
+			-uv::Vec3::unit_x(),
+			-uv::Vec3::unit_y(),
+		),
+	];
+	let mut vertices = Vec::with_capacity(24);
+	let mut indices = Vec::with_capacity(36);
+	for (normal, u_axis, v_axis) in faces {
+		let first = vertices.len() as u32;
+		for (u, v) in [(0.0, 0.0), (1.0, 0.0), (1.0, 1.0), (0.0, 1.0)] {
+			let position = (normal + u_axis * (u * 2.0 - 1.0) + v_axis * (v * 2.0 - 1.0)) * half;
+			vertices.push(vertex(position, normal, uv::Vec2::new(u, v)));
+		}
+		indices.extend([first, first + 3, first + 1, first + 1, first + 3, first + 2]);
+	}
+	finish(vertices, indices)
+}
+
+/// Square of side `size` in the xz plane facing up, split in `subdivisions` squares along each
+/// side. u follows x and v follows z.
+pub fn plane<T: IndexType>(size: f32, subdivisions: u32) -> (Vec<Vertex>, Vec<T>) {
+	assert!(subdivisions >= 1);
+	let mut vertices = Vec::with_capacity(((subdivisions + 1) * (subdivisions + 1)) as usize);
+	for row in 0..=subdivisions {
+		for column in 0..=subdivisions {
+			let uv = uv::Vec2::new(column as f32, row as f32) / subdivisions as f32;
+			let position = uv::Vec3::new(uv.x - 0.5, 0.0, uv.y - 0.5) * size;
+			vertices.push(vertex(position, uv::Vec3::unit_y(), uv));
+		}
+	}
+
+	let mut indices = Vec::with_capacity((6 * subdivisions * subdivisions) as usize);
+	for row in 0..subdivisions {
+		for column in 0..subdivisions {
+			let top_left = row * (subdivisions + 1) + column;
+			let bottom_left = top_left + subdivisions + 1;
+			indices.extend([top_left, bottom_left, top_left + 1]);
+			indices.extend([top_left + 1, bottom_left, bottom_left + 1]);
+		}
+	}
+	finish(vertices, indices)
+}
+
+/// Capped cylinder along y of `sectors` sides. The side is mapped like a uv sphere, the caps
+/// like planes seen from the outside.
+#[allow(dead_code)]
+pub fn cylinder<T: IndexType>(radius: f32, height: f32, sectors: u32) -> (Vec<Vertex>, Vec<T>) {
+	assert!(sectors >= 3);
+	let half = height / 2.0;
+	let mut vertices = Vec::with_capacity((4 * sectors + 4) as usize);
+	let mut indices = Vec::with_capacity((12 * sectors) as usize);
+
+	for v in [0.0, 1.0] {
+		for j in 0..=sectors {
+			let u = j as f32 / sectors as f32;
+			let normal = sphere_direction(PI / 2.0, u * TAU);
+			let position = normal * radius + uv::Vec3::unit_y() * (half - v * height);
+			vertices.push(vertex(position, normal, uv::Vec2::new(u, v)));
+		}
+	}
+	for j in 0..sectors {
+		let top = j;
+		let bottom = top + sectors + 1;
+		indices.extend([top, bottom, top + 1, top + 1, bottom, bottom + 1]);
+	}
+
+	for normal in [uv::Vec3::unit_y(), -uv::Vec3::unit_y()] {
+		let center = vertices.len() as u32;
+		vertices.push(vertex(normal * half, normal, uv::Vec2::new(0.5, 0.5)));
+		for j in 0..sectors {
+			let direction = sphere_direction(PI / 2.0, j as f32 / sectors as f32 * TAU);
+			// v follows z on the top cap and -z on the bottom one
+			let uv =
+				uv::Vec2::new(direction.x, direction.z * normal.y) * 0.5 + uv::Vec2::broadcast(0.5);
+			vertices.push(vertex(direction * radius + normal * half, normal, uv));
+		}
+		for j in 0..sectors {
+			let (a, b) = (center + 1 + j, center + 1 + (j + 1) % sectors);
+			if normal.y > 0.0 {
+				indices.extend([center, a, b]);
+			} else {
+				indices.extend([center, b, a]);
+			}
+		}
+	}
+	finish(vertices, indices)
+}
+
+/// Torus around y, u follows the ring of `major_segments` and v the tube of `minor_segments`,
+/// starting outside and going down.
+#[allow(dead_code)]
+pub fn torus<T: IndexType>(
+	major_radius: f32,
+	minor_radius: f32,
+	major_segments: u32,
+	minor_segments: u32,
+) -> (Vec<Vertex>, Vec<T>) {
+	assert!(major_segments >= 3 && minor_segments >= 3);
+	let mut vertices = Vec::with_capacity(((major_segments + 1) * (minor_segments + 1)) as usize);
+	for i in 0..=major_segments {
+		let u = i as f32 / major_segments as f32;
+		let ring = sphere_direction(PI / 2.0, u * TAU);
+		for j in 0..=minor_segments {
+			let v = j as f32 / minor_segments as f32;
+			let normal = ring * (v * TAU).cos() - uv::Vec3::unit_y() * (v * TAU).sin();
+			let position = ring * major_radius + normal * minor_radius;
+			vertices.push(vertex(position, normal, uv::Vec2::new(u, v)));
+		}
+	}
+
+	let mut indices = Vec::with_capacity((6 * major_segments * minor_segments) as usize);
+	for i in 0..major_segments {
+		for j in 0..minor_segments {
+			let top_left = i * (minor_segments + 1) + j;
+			let top_right = top_left + minor_segments + 1;
+			indices.extend([top_left, top_left + 1, top_right]);
+			indices.extend([top_right, top_left + 1, top_right + 1]);
+		}
+	}
+	finish(vertices, indices)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	type Generated = (Vec<Vertex>, Vec<u32>);
+
+	fn all() -> Vec<(&'static str, Generated)> {
+		vec![
+			("uv sphere", uv_sphere(1.5, 16, 8)),
+			("icosphere", icosphere(1.5, 2)),
+			("cube", cube(2.0)),
+			("plane", plane(4.0, 3)),
+			("cylinder", cylinder(0.5, 2.0, 12)),
+			("torus", torus(1.0, 0.25, 24, 12)),
+		]
+	}
+
+	#[test]
+	fn vertex_and_index_counts() {
+		let (vertices, indices) = uv_sphere::<u32>(1.0, 16, 8);
+		assert_eq!((vertices.len(), indices.len()), (17 * 9, 6 * 16 * 7));
+
+		// Without the copies on the seam and at the poles
+		let (vertices, indices) = icosphere::<u32>(1.0, 2);
+		let mut positions: Vec<[u32; 3]> = vertices
+			.iter()
+			.map(|v| {
+				[
+					v.position.x.to_bits(),
+					v.position.y.to_bits(),
+					v.position.z.to_bits(),
+				]
+			})
+			.collect();
+		positions.sort_unstable();
+		positions.dedup();
+		assert_eq!((positions.len(), indices.len()), (10 * 16 + 2, 60 * 16));
+
+		let (vertices, indices) = cube::<u32>(1.0);
+		assert_eq!((vertices.len(), indices.len()), (24, 36));
+		let (vertices, indices) = plane::<u32>(1.0, 3);
+		assert_eq!((vertices.len(), indices.len()), (16, 54));
+		let (vertices, indices) = cylinder::<u32>(1.0, 1.0, 12);
+		assert_eq!((vertices.len(), indices.len()), (4 * 12 + 4, 12 * 12));
+		let (vertices, indices) = torus::<u32>(1.0, 0.25, 24, 12);
+		assert_eq!((vertices.len(), indices.len()), (25 * 13, 6 * 24 * 12));
+	}
+
+	#[test]
+	fn indices_fit_in_16_bits() {
+		let (_, wide) = icosphere::<u32>(1.0, 3);
+		let (_, narrow) = icosphere::<u16>(1.0, 3);
+		assert!(wide.iter().zip(&narrow).all(|(&w, &n)| w == n as u32));
+	}
+
+	#[test]
+	fn normals_and_tangents_are_unit_length() {
+		for (name, (vertices, indices)) in all() {
+			assert!(
+				indices.iter().all(|&i| (i as usize) < vertices.len()),
+				"{}",
+				name
+			);
+			for v in vertices {
+				let tangent = uv::Vec3::new(v.tangent.x, v.tangent.y, v.tangent.z);
+				assert!((v.normal.mag() - 1.0).abs() < 1e-5, "{} normal", name);
+				assert!((tangent.mag() - 1.0).abs() < 1e-5, "{} tangent", name);
+				assert!(v.normal.dot(tangent).abs() < 1e-5, "{} tangent", name);
+				assert!(v.tangent.w.abs() == 1.0, "{} bitangent sign", name);
+			}
+		}
+	}
+
+	#[test]
+	fn triangles_face_the_normals() {
+		for (name, (vertices, indices)) in all() {
+			for triangle in indices.chunks_exact(3) {
+				let [a, b, c] = [0, 1, 2].map(|i| vertices[triangle[i] as usize]);
+				let face = (b.position - a.position).cross(c.position - a.position);
+				assert!(face.mag() > 1e-6, "{} has a degenerate triangle", name);
+				let normal = a.normal + b.normal + c.normal;
+				assert!(
+					face.dot(normal) > 0.0,
+					"{} triangle {:?} is clockwise",
+					name,
+					triangle
+				);
+			}
+		}
+	}
+
+	#[test]
+	fn textures_are_not_mirrored() {
+		// The tangent follows u, and the bitangent of pbr.wgsl is against v
+		for (name, (vertices, indices)) in all() {
+			for triangle in indices.chunks_exact(3) {
+				let [a, b, c] = [0, 1, 2].map(|i| vertices[triangle[i] as usize]);
+				let (e1, e2) = (b.position - a.position, c.position - a.position);
+				let (d1, d2) = (b.uv - a.uv, c.uv - a.uv);
+				let determinant = d1.x * d2.y - d2.x * d1.y;
+				let dp_dv = (e2 * d1.x - e1 * d2.x) / determinant;
+				for v in [a, b, c] {
+					let tangent = uv::Vec3::new(v.tangent.x, v.tangent.y, v.tangent.z);
+					let bitangent = v.normal.cross(tangent) * v.tangent.w;
+					assert!(bitangent.dot(dp_dv) < 0.0, "{} {:?}", name, triangle);
+				}
+				assert!(
+					determinant < 0.0,
+					"{} triangle {:?} is mirrored",
+					name,
+					triangle
+				);
+			}
+		}
+	}
+}
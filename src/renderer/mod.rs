@@ -1,12 +1,19 @@
 extern crate ultraviolet as uv;
 
+pub mod binding;
 pub mod buffer;
 pub mod camera;
 pub mod gui;
+pub mod json;
 pub mod mesh;
+pub mod png;
+pub mod post_process;
 pub mod texture;
 
-use crate::{bloom, context::Context};
+use crate::{
+	antialiasing, bloom, context::Context, dof, effects, exposure, gltf, grading, ground, ibl,
+	lights, output, pbr_textures, scene, shadow, skybox, sphere_grid, tonemap,
+};
 use texture::Texture;
 use wgpu::util::DeviceExt;
 use winit::{dpi::PhysicalSize, event::WindowEvent};
@@ -17,6 +24,7 @@ pub struct Vertex {
 	pub position: uv::Vec3,
 	pub normal: uv::Vec3,
 	pub uv: uv::Vec2,
+	pub tangent: uv::Vec4, // w is the sign of the bitangent
 }
 
 pub struct Renderer {
@@ -24,18 +32,41 @@ pub struct Renderer {
 	pub hdr_texture: Texture,
 	pub depth_texture: Texture,
 	pub final_pipeline: wgpu::RenderPipeline,
-	fullscreen_vertex_buffer: wgpu::Buffer,
-	final_bind_group: wgpu::BindGroup,
+	pub fullscreen_vertex_buffer: wgpu::Buffer,
 	pub final_buffer: buffer::Buffer,
 	final_bind_group_layout: wgpu::BindGroupLayout,
+	pub exposure_buffer: buffer::Buffer,
+	pub histogram: exposure::HistogramReadback,
+	grading_lut: Texture,
+	spectral_lut: Texture,
+	blue_noise: Texture,
+	last_frame: std::time::Instant,
 	pub camera: camera::PerspectiveCamera,
 	pub meshes: hashbrown::HashMap<String, mesh::Mesh>,
+	// Only taken out while the stack is encoding or resizing
+	pub post_process: Option<post_process::PostProcessStack>,
 	pub gui: gui::Gui,
+	pub bloom_format: bloom::BloomFormat,
+	pub anti_aliasing: antialiasing::AntiAliasing,
+	pub frame_index: u32,
+	pub dof: dof::DofSettings,
+	pub environment: ibl::Environment,
+	pub skybox: skybox::SkyboxParam,
+	pub shadow: shadow::ShadowMap,
+	pub material_textures: pbr_textures::PbrTextures,
+	pub scene: Vec<scene::SceneObject>, // drawn instead of the sphere and the ground when not empty
+	pub default_instance: wgpu::Buffer, // a single Instance::default, for the meshes drawn once
+	pub sphere_grid: Option<wgpu::Buffer>, // instances drawn instead of the sphere when set
+	// Also encode the stack without the bloom, for the split screen of the final pass
+	pub comparison: bool,
+	pub tonemap_changed: bool,
+	pub output_mode: output::OutputMode,
+	pub paper_white: f32, // luminance in nits of the display white in the hdr output modes
 	pub resized: bool,
 }
 
 impl Vertex {
-	fn layout<'a>() -> wgpu::VertexBufferLayout<'a> {
+	pub fn layout<'a>() -> wgpu::VertexBufferLayout<'a> {
 		wgpu::VertexBufferLayout {
 			array_stride: std::mem::size_of::<Vertex>() as wgpu::BufferAddress,
 			step_mode: wgpu::VertexStepMode::Vertex,
@@ -55,11 +86,87 @@ impl Vertex {
 					shader_location: 2,
 					format: wgpu::VertexFormat::Float32x2,
 				},
+				wgpu::VertexAttribute {
+					offset: (std::mem::size_of::<uv::Vec3>() * 2 + std::mem::size_of::<uv::Vec2>())
+						as wgpu::BufferAddress,
+					shader_location: 3,
+					format: wgpu::VertexFormat::Float32x4,
+				},
 			],
 		}
 	}
 }
 
+/// Instance of a mesh drawn by the pbr and shadow passes, its model matrix is applied before
+/// the one of the mesh and may only rotate, translate and scale uniformly.
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct Instance {
+	pub model: uv::Mat4,
+	pub roughness: f32, // replaces the one of PbrParam when not negative
+	pub metallic: f32,  // replaces the one of PbrParam when not negative
+}
+
+impl Default for Instance {
+	fn default() -> Self {
+		Instance {
+			model: uv::Mat4::identity(),
+			roughness: -1.0,
+			metallic: -1.0,
+		}
+	}
+}
+
+impl Instance {
+	pub fn layout<'a>() -> wgpu::VertexBufferLayout<'a> {
+		const COLUMN: wgpu::BufferAddress = std::mem::size_of::<uv::Vec4>() as wgpu::BufferAddress;
+		wgpu::VertexBufferLayout {
+			array_stride: std::mem::size_of::<Instance>() as wgpu::BufferAddress,
+			step_mode: wgpu::VertexStepMode::Instance,
+			attributes: &[
+				wgpu::VertexAttribute {
+					offset: 0,
+					shader_location: 4,
+					format: wgpu::VertexFormat::Float32x4,
+				},
+				wgpu::VertexAttribute {
+					offset: COLUMN,
+					shader_location: 5,
+					format: wgpu::VertexFormat::Float32x4,
+				},
+				wgpu::VertexAttribute {
+					offset: COLUMN * 2,
+					shader_location: 6,
+					format: wgpu::VertexFormat::Float32x4,
+				},
+				wgpu::VertexAttribute {
+					offset: COLUMN * 3,
+					shader_location: 7,
+					format: wgpu::VertexFormat::Float32x4,
+				},
+				wgpu::VertexAttribute {
+					offset: COLUMN * 4,
+					shader_location: 8,
+					format: wgpu::VertexFormat::Float32x2,
+				},
+			],
+		}
+	}
+}
+
+/// Vertex buffer of `instances`.
+pub fn create_instance_buffer(
+	device: &wgpu::Device,
+	label: Option<&str>,
+	instances: &[Instance],
+) -> wgpu::Buffer {
+	device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+		label,
+		contents: unsafe { instances.align_to::<u8>().1 },
+		usage: wgpu::BufferUsages::VERTEX,
+	})
+}
+
 impl Renderer {
 	pub fn new(context: Context) -> Self {
 		let mut hdr_texture = Texture::new(
@@ -69,6 +176,7 @@ impl Renderer {
 			context.size.height,
 			1,
 			1,
+			1,
 			wgpu::TextureDimension::D2,
 			wgpu::TextureFormat::Rgba16Float,
 			wgpu::TextureUsages::RENDER_ATTACHMENT
@@ -90,119 +198,33 @@ impl Renderer {
 			None,
 		);
 
-		let depth_texture = Texture::new(
-			&context.device,
-			Some("depth texture"),
-			context.size.width,
-			context.size.height,
-			1,
-			4,
-			wgpu::TextureDimension::D2,
-			wgpu::TextureFormat::Depth32Float,
-			wgpu::TextureUsages::RENDER_ATTACHMENT,
-			wgpu::TextureAspect::DepthOnly,
-		);
+		let anti_aliasing = antialiasing::AntiAliasing::Msaa(4);
+		let depth_texture =
+			create_depth_texture(&context.device, context.size, anti_aliasing.sample_count());
 
 		let final_buffer = buffer::Buffer::new(
 			&context.device,
 			Some("final Buffer"),
-			vec![1.0f32, 0.68],
+			vec![bloom::FinalParam::default()],
 			wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
 		);
 
-		let final_bind_group_layout =
-			context
-				.device
-				.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
-					label: Some("texture_bind_group_layout"),
-					entries: &[
-						wgpu::BindGroupLayoutEntry {
-							binding: 0,
-							visibility: wgpu::ShaderStages::FRAGMENT,
-							ty: wgpu::BindingType::Texture {
-								multisampled: false,
-								view_dimension: wgpu::TextureViewDimension::D2,
-								sample_type: wgpu::TextureSampleType::Float { filterable: true },
-							},
-							count: None,
-						},
-						wgpu::BindGroupLayoutEntry {
-							binding: 1,
-							visibility: wgpu::ShaderStages::FRAGMENT,
-							ty: wgpu::BindingType::Texture {
-								multisampled: false,
-								view_dimension: wgpu::TextureViewDimension::D2,
-								sample_type: wgpu::TextureSampleType::Float { filterable: true },
-							},
-							count: None,
-						},
-						wgpu::BindGroupLayoutEntry {
-							binding: 2,
-							visibility: wgpu::ShaderStages::FRAGMENT,
-							ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
-							count: None,
-						},
-						wgpu::BindGroupLayoutEntry {
-							binding: 3,
-							visibility: wgpu::ShaderStages::FRAGMENT,
-							ty: wgpu::BindingType::Buffer {
-								ty: wgpu::BufferBindingType::Uniform,
-								has_dynamic_offset: false,
-								min_binding_size: Some(final_buffer.size),
-							},
-							count: None,
-						},
-					],
-				});
+		let exposure_buffer = exposure::create_exposure_buffer(&context.device);
+		let histogram = exposure::HistogramReadback::new(&context.device);
 
-		let shader = context
-			.device
-			.create_shader_module(&wgpu::include_wgsl!("../../assets/shaders/final.wgsl"));
-		let layout = context
-			.device
-			.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
-				label: Some("Render Pipeline Layout"),
-				bind_group_layouts: &[&final_bind_group_layout],
-				push_constant_ranges: &[],
-			});
+		binding::validate("final.wgsl", FINAL_SHADER, 0, &FINAL_SLOTS);
+		let final_bind_group_layout = binding::create_layout(
+			&context.device,
+			Some("final bind group layout"),
+			&FINAL_SLOTS,
+			wgpu::ShaderStages::FRAGMENT,
+		);
 
-		let final_pipeline =
-			context
-				.device
-				.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
-					label: Some("final render pipeline"),
-					layout: Some(&layout),
-					vertex: wgpu::VertexState {
-						module: &shader,
-						entry_point: "vs_main",
-						buffers: &[Vertex::layout()],
-					},
-					fragment: Some(wgpu::FragmentState {
-						module: &shader,
-						entry_point: "fs_main",
-						targets: &[wgpu::ColorTargetState {
-							format: context.config.format,
-							blend: Some(wgpu::BlendState::REPLACE),
-							write_mask: wgpu::ColorWrites::ALL,
-						}],
-					}),
-					primitive: wgpu::PrimitiveState {
-						topology: wgpu::PrimitiveTopology::TriangleList,
-						strip_index_format: None,
-						front_face: wgpu::FrontFace::Ccw,
-						cull_mode: Some(wgpu::Face::Back),
-						polygon_mode: wgpu::PolygonMode::Fill,
-						unclipped_depth: false,
-						conservative: false,
-					},
-					depth_stencil: None,
-					multisample: wgpu::MultisampleState {
-						count: 1,
-						mask: !0,
-						alpha_to_coverage_enabled: false,
-					},
-					multiview: None,
-				});
+		let final_pipeline = create_final_pipeline(
+			&context.device,
+			&final_bind_group_layout,
+			context.config.format,
+		);
 		let fullscreen_quad_data = generate_fullscreen_quad();
 		let contents = unsafe { fullscreen_quad_data.align_to::<u8>().1 };
 		let fullscreen_vertex_buffer =
@@ -214,36 +236,25 @@ impl Renderer {
 					usage: wgpu::BufferUsages::VERTEX,
 				});
 
-		let final_bind_group = context
-			.device
-			.create_bind_group(&wgpu::BindGroupDescriptor {
-				label: Some("final bind group"),
-				layout: &final_bind_group_layout,
-				entries: &[
-					wgpu::BindGroupEntry {
-						binding: 0,
-						resource: wgpu::BindingResource::TextureView(&hdr_texture.view),
-					},
-					wgpu::BindGroupEntry {
-						binding: 1,
-						resource: wgpu::BindingResource::TextureView(&hdr_texture.view),
-					},
-					wgpu::BindGroupEntry {
-						binding: 2,
-						resource: wgpu::BindingResource::Sampler(
-							&hdr_texture.sampler.as_ref().unwrap(),
-						),
-					},
-					wgpu::BindGroupEntry {
-						binding: 3,
-						resource: wgpu::BindingResource::Buffer(wgpu::BufferBinding {
-							buffer: &final_buffer.buffer,
-							offset: 0,
-							size: Some(final_buffer.size),
-						}),
-					},
-				],
-			});
+		let tonemap_mesh = tonemap::init_tonemap_lut(&context.device);
+		let grading_lut =
+			grading::CubeLut::identity().create_texture(&context.device, &context.queue);
+		let spectral_lut = effects::create_spectral_lut(&context.device, &context.queue);
+		let blue_noise = effects::create_blue_noise(&context.device, &context.queue);
+		let environment = ibl::Environment::new(
+			&context.device,
+			&context.queue,
+			&ibl::EquirectMap::procedural_sky(),
+		);
+
+		let shadow = shadow::ShadowMap::new(&context.device, shadow::ShadowSettings::default());
+		let material_textures =
+			pbr_textures::PbrTextures::default_maps(&context.device, &context.queue);
+		let default_instance = create_instance_buffer(
+			&context.device,
+			Some("default instance buffer"),
+			&[Instance::default()],
+		);
 
 		let camera = camera::PerspectiveCamera::new(
 			uv::Vec3::new(0.0, 0.0, -5.0),
@@ -254,15 +265,7 @@ impl Renderer {
 			1000.0,
 		);
 
-		let gui = gui::Gui::new(
-			&context.window,
-			&context.device,
-			context
-				.surface
-				.get_preferred_format(&context.adapter)
-				.unwrap(),
-			1,
-		);
+		let gui = gui::Gui::new(&context.window, &context.device, context.config.format, 1);
 
 		let mut renderer = Renderer {
 			context,
@@ -270,60 +273,64 @@ impl Renderer {
 			depth_texture,
 			final_pipeline,
 			fullscreen_vertex_buffer,
-			final_bind_group,
 			final_bind_group_layout,
 			final_buffer,
+			histogram,
+			grading_lut,
+			spectral_lut,
+			blue_noise,
+			exposure_buffer,
+			last_frame: std::time::Instant::now(),
 			camera,
 			meshes: hashbrown::HashMap::with_capacity(2),
+			post_process: None,
 			gui,
+			bloom_format: bloom::BloomFormat::default(),
+			anti_aliasing,
+			frame_index: 0,
+			dof: dof::DofSettings::default(),
+			environment,
+			skybox: skybox::SkyboxParam::default(),
+			shadow,
+			material_textures,
+			scene: Vec::new(),
+			default_instance,
+			sphere_grid: None,
+			comparison: false,
+			tonemap_changed: true,
+			output_mode: output::OutputMode::Sdr,
+			paper_white: 203.0,
 			resized: false,
 		};
 		let pbr_sphere = super::bloom::init_pbr(&renderer);
+		let ground_mesh = ground::init_ground(&renderer, &pbr_sphere);
+		let skybox_mesh = skybox::init_skybox(&renderer);
 		let bloom_mesh = super::bloom::init_bloom(&mut renderer);
-
-		let bloom_view = if (0..=bloom::BLOOM_MIP_COUNT - 2).count() % 2 == 1 {
-			&bloom_mesh.material.as_ref().unwrap().bind_groups_textures[1].view
-		} else {
-			&bloom_mesh.material.as_ref().unwrap().bind_groups_textures[2].view
-		};
-
-		renderer.final_bind_group =
-			renderer
-				.context
-				.device
-				.create_bind_group(&wgpu::BindGroupDescriptor {
-					label: Some("final bind group"),
-					layout: &renderer.final_bind_group_layout,
-					entries: &[
-						wgpu::BindGroupEntry {
-							binding: 0,
-							resource: wgpu::BindingResource::TextureView(bloom_view),
-						},
-						wgpu::BindGroupEntry {
-							binding: 1,
-							resource: wgpu::BindingResource::TextureView(
-								&renderer.hdr_texture.view,
-							),
-						},
-						wgpu::BindGroupEntry {
-							binding: 2,
-							resource: wgpu::BindingResource::Sampler(
-								&renderer.hdr_texture.sampler.as_ref().unwrap(),
-							),
-						},
-						wgpu::BindGroupEntry {
-							binding: 3,
-							resource: wgpu::BindingResource::Buffer(wgpu::BufferBinding {
-								buffer: &renderer.final_buffer.buffer,
-								offset: 0,
-								size: Some(renderer.final_buffer.size),
-							}),
-						},
-					],
-				});
+		let exposure_mesh = exposure::init_exposure(&renderer);
 
 		renderer.meshes.insert("pbr".to_string(), pbr_sphere);
+		renderer.meshes.insert("ground".to_string(), ground_mesh);
+		renderer.meshes.insert("skybox".to_string(), skybox_mesh);
 		renderer.meshes.insert("bloom".to_string(), bloom_mesh);
+		renderer.meshes.insert("tonemap".to_string(), tonemap_mesh);
+		renderer
+			.meshes
+			.insert("exposure".to_string(), exposure_mesh);
+
+		let mut post_process = post_process::PostProcessStack::new(
+			&renderer.context.device,
+			renderer.context.size.width,
+			renderer.context.size.height,
+		);
+		post_process.push(Box::new(antialiasing::TaaPass::new(&renderer)));
+		post_process.push(Box::new(dof::DofPass::new(&renderer)));
+		post_process.set_enabled(dof::DOF_PASS_NAME, false);
+		post_process.push(Box::new(bloom::BloomPass::new(&renderer)));
+		post_process.push(Box::new(tonemap::TonemapPass::new(&renderer)));
+		post_process.push(Box::new(antialiasing::FxaaPass::new(&renderer)));
+		renderer.post_process = Some(post_process);
+		renderer.enable_anti_aliasing_passes();
+		renderer.update_shadow(&[lights::Light::default()]);
 		renderer
 	}
 
@@ -350,6 +357,10 @@ impl Renderer {
 		};
 
 		for (k, mesh) in self.meshes.iter_mut() {
+			// The tonemap lut does not depend on the screen size
+			if k == "tonemap" {
+				continue;
+			}
 			if mesh.material.is_some() {
 				let material = mesh.material.as_mut().unwrap();
 				for text in material.bind_groups_textures.iter_mut() {
@@ -362,63 +373,16 @@ impl Renderer {
 			}
 		}
 
-		let bloom_view = if (0..=bloom::BLOOM_MIP_COUNT - 2).count() % 2 == 1 {
-			&self
-				.meshes
-				.get("bloom")
-				.as_ref()
-				.unwrap()
-				.material
-				.as_ref()
-				.unwrap()
-				.bind_groups_textures[1]
-				.view
-		} else {
-			&self
-				.meshes
-				.get("bloom")
-				.as_ref()
-				.unwrap()
-				.material
-				.as_ref()
-				.unwrap()
-				.bind_groups_textures[2]
-				.view
-		};
+		let mut post_process = self.post_process.take().unwrap();
+		post_process.resize(self, size);
+		self.post_process = Some(post_process);
 
-		self.final_bind_group = self
-			.context
-			.device
-			.create_bind_group(&wgpu::BindGroupDescriptor {
-				label: Some("final bind group"),
-				layout: &self.final_bind_group_layout,
-				entries: &[
-					wgpu::BindGroupEntry {
-						binding: 0,
-						resource: wgpu::BindingResource::TextureView(bloom_view),
-					},
-					wgpu::BindGroupEntry {
-						binding: 1,
-						resource: wgpu::BindingResource::TextureView(&self.hdr_texture.view),
-					},
-					wgpu::BindGroupEntry {
-						binding: 2,
-						resource: wgpu::BindingResource::Sampler(
-							&self.hdr_texture.sampler.as_ref().unwrap(),
-						),
-					},
-					wgpu::BindGroupEntry {
-						binding: 3,
-						resource: wgpu::BindingResource::Buffer(wgpu::BufferBinding {
-							buffer: &self.final_buffer.buffer,
-							offset: 0,
-							size: Some(self.final_buffer.size),
-						}),
-					},
-				],
-			});
 		self.camera.aspect_ratio = new_size.width as f32 / new_size.height as f32;
 		self.camera.recreate_matrices();
+		self.update_camera_buffer();
+	}
+
+	fn update_camera_buffer(&mut self) {
 		let mut pbr_mesh = self.meshes.get_mut("pbr");
 		let pbr_mat = pbr_mesh.as_mut().unwrap().material.as_mut().unwrap();
 
@@ -429,6 +393,312 @@ impl Renderer {
 			0,
 			vec![self.camera.view_proj],
 		);
+		self.meshes
+			.get_mut("ground")
+			.unwrap()
+			.material
+			.as_mut()
+			.unwrap()
+			.copy_to_buffer(
+				&self.context.device,
+				&self.context.queue,
+				0,
+				0,
+				vec![self.camera.view_proj],
+			);
+		for object in self.scene.iter_mut() {
+			object.mesh.material.as_mut().unwrap().copy_to_buffer(
+				&self.context.device,
+				&self.context.queue,
+				0,
+				0,
+				vec![self.camera.view_proj],
+			);
+		}
+		self.update_skybox_buffer();
+	}
+
+	/// Uploads `skybox` with the inverse of the current camera matrix.
+	pub fn update_skybox_buffer(&mut self) {
+		self.skybox.inv_view_proj = self.camera.view_proj.inversed();
+		self.meshes
+			.get_mut("skybox")
+			.unwrap()
+			.material
+			.as_mut()
+			.unwrap()
+			.copy_to_buffer(
+				&self.context.device,
+				&self.context.queue,
+				0,
+				0,
+				vec![self.skybox],
+			);
+	}
+
+	/// Recreates the bloom chain and its pipeline with the new storage format.
+	/// The bloom parameters are reset to their defaults and have to be uploaded again.
+	pub fn set_bloom_format(&mut self, format: bloom::BloomFormat) {
+		self.bloom_format = if format.is_supported(&self.context) {
+			format
+		} else {
+			bloom::BloomFormat::default()
+		};
+		let bloom_mesh = bloom::init_bloom(self);
+		self.meshes.insert("bloom".to_string(), bloom_mesh);
+	}
+
+	/// Recreates the pbr targets and pipeline when the sample count changes and toggles the FXAA
+	/// and TAA passes.
+	pub fn set_anti_aliasing(&mut self, anti_aliasing: antialiasing::AntiAliasing) {
+		let samples_changed = anti_aliasing.sample_count() != self.anti_aliasing.sample_count();
+		self.anti_aliasing = anti_aliasing;
+		if samples_changed {
+			self.depth_texture = create_depth_texture(
+				&self.context.device,
+				self.context.size,
+				anti_aliasing.sample_count(),
+			);
+			let mut pbr_mat =
+				std::mem::take(&mut self.meshes.get_mut("pbr").unwrap().material).unwrap();
+			bloom::set_pbr_sample_count(self, &mut pbr_mat);
+			self.meshes.get_mut("pbr").unwrap().material = Some(pbr_mat);
+			let mut skybox_mat =
+				std::mem::take(&mut self.meshes.get_mut("skybox").unwrap().material).unwrap();
+			skybox::set_skybox_sample_count(self, &mut skybox_mat);
+			self.meshes.get_mut("skybox").unwrap().material = Some(skybox_mat);
+		}
+		if anti_aliasing != antialiasing::AntiAliasing::Taa {
+			self.camera.set_jitter(uv::Vec2::zero());
+			self.update_camera_buffer();
+		}
+		self.enable_anti_aliasing_passes();
+	}
+
+	fn enable_anti_aliasing_passes(&mut self) {
+		let post_process = self.post_process.as_mut().unwrap();
+		post_process.set_enabled(
+			antialiasing::FXAA_PASS_NAME,
+			self.anti_aliasing == antialiasing::AntiAliasing::Fxaa,
+		);
+		post_process.set_enabled(
+			antialiasing::TAA_PASS_NAME,
+			self.anti_aliasing == antialiasing::AntiAliasing::Taa,
+		);
+	}
+
+	/// Reconfigures the surface for `mode` and recreates what depends on its format.
+	/// Returns false and keeps the current mode if the surface does not support it.
+	pub fn set_output_mode(&mut self, mode: output::OutputMode) -> bool {
+		if !mode.is_available(&self.context) {
+			return false;
+		}
+		let format = mode.surface_format(self.context.preferred_format);
+		self.context.set_format(format);
+		self.output_mode = mode;
+		self.final_pipeline =
+			create_final_pipeline(&self.context.device, &self.final_bind_group_layout, format);
+		self.gui.render_pass = egui_wgpu_backend::RenderPass::new(&self.context.device, format, 1);
+		true
+	}
+
+	/// Recomputes the image based lighting from `map`.
+	pub fn set_environment(&mut self, map: &ibl::EquirectMap) {
+		self.environment = ibl::Environment::new(&self.context.device, &self.context.queue, map);
+		let mut pbr_mat =
+			std::mem::take(&mut self.meshes.get_mut("pbr").unwrap().material).unwrap();
+		pbr_mat.set_bind_group(
+			&self.context.device,
+			Some("environment bind group"),
+			1,
+			self.environment.pbr_bindings(),
+		);
+		self.meshes.get_mut("pbr").unwrap().material = Some(pbr_mat);
+		let mut skybox_mat =
+			std::mem::take(&mut self.meshes.get_mut("skybox").unwrap().material).unwrap();
+		skybox::set_skybox_environment(self, &mut skybox_mat);
+		self.meshes.get_mut("skybox").unwrap().material = Some(skybox_mat);
+	}
+
+	/// Replaces the maps of the sphere material.
+	pub fn set_material_textures(&mut self, textures: pbr_textures::PbrTextures) {
+		self.material_textures = textures;
+		let mut pbr_mat =
+			std::mem::take(&mut self.meshes.get_mut("pbr").unwrap().material).unwrap();
+		pbr_mat.set_bind_group(
+			&self.context.device,
+			Some("material textures bind group"),
+			3,
+			self.material_textures.pbr_bindings(),
+		);
+		self.meshes.get_mut("pbr").unwrap().material = Some(pbr_mat);
+	}
+
+	/// Replaces the sphere and the ground by the meshes of `scene`, or restores them when `None`.
+	/// The camera moves to the one of the scene, or frames it. The lights are not changed, and
+	/// the shadow has to be updated.
+	pub fn set_scene(&mut self, scene: Option<&gltf::Scene>) {
+		let fov = std::f32::consts::FRAC_PI_3;
+		match scene {
+			Some(scene) => {
+				let (center, radius) = scene.bounds();
+				let radius = radius.max(0.01);
+				match &scene.camera {
+					Some(camera) => {
+						self.camera.position = camera.position;
+						self.camera.look_at = camera.look_at;
+						self.camera.up = camera.up;
+						self.camera.fov = camera.fov;
+						self.camera.near = camera.near;
+						self.camera.far = camera.far.unwrap_or(1000.0);
+					}
+					None => {
+						self.camera.look_at = center;
+						self.camera.position =
+							center - uv::Vec3::unit_z() * radius / (fov / 2.0).sin();
+						self.camera.up = uv::Vec3::unit_y();
+						self.camera.fov = fov;
+						self.camera.near = radius * 0.01;
+						self.camera.far = 1000.0f32.max(radius * 10.0);
+					}
+				}
+				self.shadow.bounds = shadow::ShadowBounds {
+					center,
+					caster_radius: radius,
+					receiver_radius: radius,
+				};
+			}
+			None if self.sphere_grid.is_some() => {
+				let (center, radius) = sphere_grid::bounds();
+				self.camera.look_at = center;
+				self.camera.position = center - uv::Vec3::unit_z() * radius / (fov / 2.0).sin();
+				self.camera.up = uv::Vec3::unit_y();
+				self.camera.fov = fov;
+				self.camera.near = 0.1;
+				self.camera.far = 1000.0;
+				self.shadow.bounds = shadow::ShadowBounds {
+					center,
+					caster_radius: radius,
+					receiver_radius: radius,
+				};
+			}
+			None => {
+				self.camera.position = uv::Vec3::new(0.0, 0.0, -5.0);
+				self.camera.look_at = uv::Vec3::zero();
+				self.camera.up = uv::Vec3::unit_y();
+				self.camera.fov = fov;
+				self.camera.near = 0.1;
+				self.camera.far = 1000.0;
+				self.shadow.bounds = shadow::ShadowBounds::default();
+			}
+		}
+		self.camera.recreate_matrices();
+		self.scene = scene.map_or_else(Vec::new, |scene| scene::create_scene(self, scene));
+		self.update_camera_buffer();
+	}
+
+	/// Draws the roughness and metallic chart instead of the sphere and the ground, or restores
+	/// them. The camera is moved to frame it when no scene is loaded, and the shadow has to be
+	/// updated.
+	pub fn set_sphere_grid(&mut self, enabled: bool) {
+		self.sphere_grid = enabled.then(|| {
+			create_instance_buffer(
+				&self.context.device,
+				Some("sphere grid instance buffer"),
+				&sphere_grid::instances(),
+			)
+		});
+		if self.scene.is_empty() {
+			self.set_scene(None);
+		}
+	}
+
+	/// Uploads the parameters of the scene meshes with the camera position and environment
+	/// intensity of the sphere.
+	pub fn update_scene_params(&mut self, cam_pos: uv::Vec3, environment_intensity: f32) {
+		for object in self.scene.iter_mut() {
+			object.param.cam_pos = cam_pos;
+			object.param.environment_intensity = environment_intensity;
+			object.mesh.material.as_mut().unwrap().copy_to_buffer(
+				&self.context.device,
+				&self.context.queue,
+				1,
+				0,
+				vec![object.param],
+			);
+		}
+	}
+
+	/// Uploads the shadow parameters for the first of `lights`, and recreates the shadow map and
+	/// rebinds it to the pbr pass when its resolution changed.
+	pub fn update_shadow(&mut self, lights: &[lights::Light]) {
+		let resolution = self.shadow.texture.size.width;
+		self.shadow
+			.update(&self.context.device, &self.context.queue, lights.first());
+		if resolution != self.shadow.texture.size.width {
+			let mut pbr_mat =
+				std::mem::take(&mut self.meshes.get_mut("pbr").unwrap().material).unwrap();
+			pbr_mat.set_bind_group(
+				&self.context.device,
+				Some("shadow bind group"),
+				2,
+				self.shadow.pbr_bindings(),
+			);
+			self.meshes.get_mut("pbr").unwrap().material = Some(pbr_mat);
+		}
+	}
+
+	/// Uploads the color grading lut applied by the final pass. The domain of the lut has to be
+	/// uploaded with the `FinalParam`.
+	pub fn set_grading_lut(&mut self, lut: &grading::CubeLut) {
+		self.grading_lut.texture.destroy();
+		self.grading_lut = lut.create_texture(&self.context.device, &self.context.queue);
+	}
+
+	/// `input` is the result of the post processing stack, `compare` the one shown left of the
+	/// comparison divider.
+	fn create_final_bind_group(
+		&self,
+		input: &wgpu::TextureView,
+		compare: &wgpu::TextureView,
+	) -> wgpu::BindGroup {
+		binding::create_bind_group(
+			&self.context.device,
+			Some("final bind group"),
+			&self.final_bind_group_layout,
+			&FINAL_SLOTS,
+			&[
+				("input_texture", wgpu::BindingResource::TextureView(input)),
+				(
+					"samp",
+					wgpu::BindingResource::Sampler(self.hdr_texture.sampler.as_ref().unwrap()),
+				),
+				(
+					"composite_parameter",
+					wgpu::BindingResource::Buffer(wgpu::BufferBinding {
+						buffer: &self.final_buffer.buffer,
+						offset: 0,
+						size: Some(self.final_buffer.size),
+					}),
+				),
+				(
+					"grading_lut",
+					wgpu::BindingResource::TextureView(&self.grading_lut.view),
+				),
+				(
+					"spectral_lut",
+					wgpu::BindingResource::TextureView(&self.spectral_lut.view),
+				),
+				(
+					"compare_texture",
+					wgpu::BindingResource::TextureView(compare),
+				),
+				(
+					"blue_noise",
+					wgpu::BindingResource::TextureView(&self.blue_noise.view),
+				),
+			],
+		)
 	}
 
 	#[allow(unused)]
@@ -448,10 +718,39 @@ impl Renderer {
 					label: Some("Render Encoder"),
 				});
 
+		self.histogram.poll(&self.context.device);
+		let now = std::time::Instant::now();
+		let delta_time = (now - self.last_frame).as_secs_f32();
+		self.last_frame = now;
+
+		self.frame_index = self.frame_index.wrapping_add(1);
+		if self.anti_aliasing == antialiasing::AntiAliasing::Taa {
+			self.camera.set_jitter(antialiasing::taa_jitter(
+				self.frame_index,
+				self.context.size.width,
+				self.context.size.height,
+			));
+			self.update_camera_buffer();
+		}
+
+		shadow::render_shadow(self, &mut encoder);
 		bloom::render_pbr(self, &mut encoder);
-		bloom::render_bloom(self, &mut encoder);
+		exposure::render_exposure(self, &mut encoder, delta_time);
+		if self.tonemap_changed {
+			tonemap::render_tonemap_lut(self, &mut encoder);
+			self.tonemap_changed = false;
+		}
 
+		let mut post_process = self.post_process.take().unwrap();
 		{
+			let (input, compare) = if self.comparison {
+				post_process.encode_comparison(self, &mut encoder, bloom::BLOOM_PASS_NAME)
+			} else {
+				let input = post_process.encode(self, &mut encoder);
+				(input, input)
+			};
+			let final_bind_group = self.create_final_bind_group(&input.view, &compare.view);
+
 			let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
 				label: Some("final Render Pass"),
 				color_attachments: &[wgpu::RenderPassColorAttachment {
@@ -473,9 +772,19 @@ impl Renderer {
 			});
 			render_pass.set_pipeline(&self.final_pipeline);
 			render_pass.set_vertex_buffer(0, self.fullscreen_vertex_buffer.slice(..));
-			render_pass.set_bind_group(0, &self.final_bind_group, &[]);
+			render_pass.set_bind_group(0, &final_bind_group, &[]);
+			let output_param = [output::OutputParam::new(
+				self.output_mode,
+				self.context.config.format,
+				self.paper_white,
+				self.frame_index,
+			)];
+			render_pass.set_push_constants(wgpu::ShaderStages::FRAGMENT, 0, unsafe {
+				output_param.align_to::<u8>().1
+			});
 			render_pass.draw(0..6, 0..1);
 		}
+		self.post_process = Some(post_process);
 
 		if draw_gui {
 			let full_gui_output = self.gui.platform.end_frame(Some(&self.context.window));
@@ -514,12 +823,105 @@ impl Renderer {
 
 		// submit will accept anything that implements IntoIter
 		self.context.queue.submit(std::iter::once(encoder.finish()));
+		self.histogram.request_map();
 		output.present();
 
 		Ok(())
 	}
 }
 
+fn create_depth_texture(
+	device: &wgpu::Device,
+	size: PhysicalSize<u32>,
+	sample_count: u32,
+) -> Texture {
+	Texture::new(
+		device,
+		Some("depth texture"),
+		size.width,
+		size.height,
+		1,
+		1,
+		sample_count,
+		wgpu::TextureDimension::D2,
+		wgpu::TextureFormat::Depth32Float,
+		// Read by the TAA and depth of field passes
+		wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING,
+		wgpu::TextureAspect::DepthOnly,
+	)
+}
+
+const FINAL_SHADER: &str = include_str!("../../assets/shaders/final.wgsl");
+
+/// Bindings of the final pass, checked against `final.wgsl` when the renderer is created.
+const FINAL_SLOTS: [binding::Slot; 7] = [
+	binding::Slot::new("input_texture", 0, binding::SlotType::Texture2d),
+	binding::Slot::new("samp", 1, binding::SlotType::Sampler),
+	binding::Slot::new(
+		"composite_parameter",
+		2,
+		binding::SlotType::Uniform(std::mem::size_of::<bloom::FinalParam>() as u64),
+	),
+	binding::Slot::new("grading_lut", 3, binding::SlotType::Texture3d),
+	binding::Slot::new("spectral_lut", 4, binding::SlotType::Texture2d),
+	binding::Slot::new("compare_texture", 5, binding::SlotType::Texture2d),
+	binding::Slot::new("blue_noise", 6, binding::SlotType::Texture2d),
+];
+
+fn create_final_pipeline(
+	device: &wgpu::Device,
+	bind_group_layout: &wgpu::BindGroupLayout,
+	format: wgpu::TextureFormat,
+) -> wgpu::RenderPipeline {
+	let shader = device.create_shader_module(&wgpu::ShaderModuleDescriptor {
+		label: Some("final.wgsl"),
+		source: wgpu::ShaderSource::Wgsl(FINAL_SHADER.into()),
+	});
+	let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+		label: Some("Render Pipeline Layout"),
+		bind_group_layouts: &[bind_group_layout],
+		push_constant_ranges: &[wgpu::PushConstantRange {
+			stages: wgpu::ShaderStages::FRAGMENT,
+			range: 0..std::mem::size_of::<output::OutputParam>() as u32,
+		}],
+	});
+
+	device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+		label: Some("final render pipeline"),
+		layout: Some(&layout),
+		vertex: wgpu::VertexState {
+			module: &shader,
+			entry_point: "vs_main",
+			buffers: &[Vertex::layout()],
+		},
+		fragment: Some(wgpu::FragmentState {
+			module: &shader,
+			entry_point: "fs_main",
+			targets: &[wgpu::ColorTargetState {
+				format,
+				blend: Some(wgpu::BlendState::REPLACE),
+				write_mask: wgpu::ColorWrites::ALL,
+			}],
+		}),
+		primitive: wgpu::PrimitiveState {
+			topology: wgpu::PrimitiveTopology::TriangleList,
+			strip_index_format: None,
+			front_face: wgpu::FrontFace::Ccw,
+			cull_mode: Some(wgpu::Face::Back),
+			polygon_mode: wgpu::PolygonMode::Fill,
+			unclipped_depth: false,
+			conservative: false,
+		},
+		depth_stencil: None,
+		multisample: wgpu::MultisampleState {
+			count: 1,
+			mask: !0,
+			alpha_to_coverage_enabled: false,
+		},
+		multiview: None,
+	})
+}
+
 pub fn generate_fullscreen_quad() -> Vec<Vertex> {
 	let mut fullscreen_quad_data = Vec::with_capacity(6);
 	fullscreen_quad_data.push(Vertex {
@@ -0,0 +1,586 @@
+use std::path::Path;
+
+/// 8 bits RGBA image, rows from the top.
+pub struct Image {
+	pub width: u32,
+	pub height: u32,
+	pub pixels: Vec<[u8; 4]>,
+}
+
+impl Image {
+	pub fn load(path: &Path) -> Result<Self, String> {
+		let source = std::fs::read(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+		decode(&source).map_err(|e| format!("{}: {}", path.display(), e))
+	}
+}
+
+/// Non interlaced PNG of any color type, 16 bits channels are truncated to 8 bits.
+pub fn decode(source: &[u8]) -> Result<Image, String> {
+	const SIGNATURE: [u8; 8] = [0x89, b'P', b'N', b'G', 0x0d, 0x0a, 0x1a, 0x0a];
+	if source.len() < 8 || source[..8] != SIGNATURE {
+		return Err("not a PNG file".to_owned());
+	}
+
+	let mut header = None;
+	let mut palette: Vec<[u8; 4]> = Vec::new();
+	let mut transparency = Vec::new();
+	let mut data = Vec::new();
+	let mut offset = 8;
+	loop {
+		if offset + 8 > source.len() {
+			return Err("unexpected end of the file".to_owned());
+		}
+		let length = u32::from_be_bytes(source[offset..offset + 4].try_into().unwrap()) as usize;
+		let kind = &source[offset + 4..offset + 8];
+		let start = offset + 8;
+		// The chunk is followed by its crc, which is not checked
+		let end = start
+			.checked_add(length)
+			.and_then(|end| end.checked_add(4))
+			.filter(|&end| end <= source.len())
+			.ok_or("unexpected end of the file")?;
+		let chunk = &source[start..end - 4];
+		offset = end;
+
+		match kind {
+			b"IHDR" => {
+				if length != 13 {
+					return Err("invalid header".to_owned());
+				}
+				let width = u32::from_be_bytes(chunk[0..4].try_into().unwrap());
+				let height = u32::from_be_bytes(chunk[4..8].try_into().unwrap());
+				let (bit_depth, color_type, interlace) = (chunk[8], chunk[9], chunk[12]);
+				if width == 0 || height == 0 {
+					return Err("empty image".to_owned());
+				}
+				if interlace != 0 {
+					return Err("interlaced images are not supported".to_owned());
+				}
+				let supported = match color_type {
+					0 => matches!(bit_depth, 8 | 16),
+					3 => matches!(bit_depth, 1 | 2 | 4 | 8),
+					2 | 4 | 6 => matches!(bit_depth, 8 | 16),
+					_ => false,
+				};
+				if !supported {
+					return Err(format!(
+						"unsupported color type {} with {} bits",
+						color_type, bit_depth
+					));
+				}
+				header = Some((width, height, bit_depth, color_type));
+			}
+			b"PLTE" => {
+				palette = chunk
+					.chunks_exact(3)
+					.map(|c| [c[0], c[1], c[2], 255])
+					.collect();
+			}
+			b"tRNS" => transparency = chunk.to_vec(),
+			b"IDAT" => data.extend_from_slice(chunk),
+			b"IEND" => break,
+			_ => {}
+		}
+	}
+	let (width, height, bit_depth, color_type) = header.ok_or("missing header")?;
+	for (entry, &alpha) in palette.iter_mut().zip(&transparency) {
+		entry[3] = alpha;
+	}
+
+	let channels = match color_type {
+		0 | 3 => 1,
+		2 => 3,
+		4 => 2,
+		_ => 4,
+	};
+	let bits_per_pixel = channels * bit_depth as usize;
+	let stride = (width as usize)
+		.checked_mul(bits_per_pixel)
+		.ok_or("image too large")?
+		.div_ceil(8);
+	let filter_offset = (bits_per_pixel / 8).max(1);
+	// Every row starts with its filter type
+	let size = (stride + 1)
+		.checked_mul(height as usize)
+		.ok_or("image too large")?;
+
+	let raw = inflate(zlib_data(&data)?, size)?;
+	if raw.len() < size {
+		return Err("not enough image data".to_owned());
+	}
+
+	// Bounded by the size of the decompressed data
+	let mut pixels = Vec::with_capacity(width as usize * height as usize);
+	let mut previous = vec![0u8; stride];
+	let mut row = vec![0u8; stride];
+	for y in 0..height as usize {
+		let line = &raw[y * (stride + 1)..(y + 1) * (stride + 1)];
+		unfilter(line[0], &line[1..], &previous, &mut row, filter_offset)?;
+
+		let sample = |x: usize, channel: usize| -> u8 {
+			match bit_depth {
+				8 => row[x * channels + channel],
+				// Most significant byte first
+				16 => row[(x * channels + channel) * 2],
+				_ => {
+					let bit = x * bit_depth as usize;
+					let shift = 8 - bit_depth as usize - bit % 8;
+					(row[bit / 8] >> shift) & ((1 << bit_depth) - 1)
+				}
+			}
+		};
+		for x in 0..width as usize {
+			pixels.push(match color_type {
+				0 => {
+					let v = sample(x, 0);
+					[v, v, v, 255]
+				}
+				2 => [sample(x, 0), sample(x, 1), sample(x, 2), 255],
+				3 => *palette
+					.get(sample(x, 0) as usize)
+					.ok_or("palette index out of range")?,
+				4 => {
+					let v = sample(x, 0);
+					[v, v, v, sample(x, 1)]
+				}
+				_ => [sample(x, 0), sample(x, 1), sample(x, 2), sample(x, 3)],
+			});
+		}
+		std::mem::swap(&mut previous, &mut row);
+	}
+
+	Ok(Image {
+		width,
+		height,
+		pixels,
+	})
+}
+
+fn unfilter(
+	filter: u8,
+	line: &[u8],
+	previous: &[u8],
+	row: &mut [u8],
+	offset: usize,
+) -> Result<(), String> {
+	for i in 0..line.len() {
+		let left = if i >= offset { row[i - offset] } else { 0 };
+		let up = previous[i];
+		let up_left = if i >= offset { previous[i - offset] } else { 0 };
+		let predictor = match filter {
+			0 => 0,
+			1 => left,
+			2 => up,
+			3 => ((left as u16 + up as u16) / 2) as u8,
+			4 => {
+				let p = left as i16 + up as i16 - up_left as i16;
+				let (pa, pb, pc) = (
+					(p - left as i16).abs(),
+					(p - up as i16).abs(),
+					(p - up_left as i16).abs(),
+				);
+				if pa <= pb && pa <= pc {
+					left
+				} else if pb <= pc {
+					up
+				} else {
+					up_left
+				}
+			}
+			_ => return Err(format!("invalid filter {}", filter)),
+		};
+		row[i] = line[i].wrapping_add(predictor);
+	}
+	Ok(())
+}
+
+/// Deflate stream of a zlib container, the adler32 checksum is not checked.
+fn zlib_data(data: &[u8]) -> Result<&[u8], String> {
+	if data.len() < 2
+		|| data[0] & 0x0f != 8
+		|| !(data[0] as u16 * 256 + data[1] as u16).is_multiple_of(31)
+	{
+		return Err("invalid zlib header".to_owned());
+	}
+	if data[1] & 0x20 != 0 {
+		return Err("preset zlib dictionaries are not supported".to_owned());
+	}
+	Ok(&data[2..])
+}
+
+struct BitReader<'a> {
+	data: &'a [u8],
+	position: usize, // in bits
+}
+
+impl BitReader<'_> {
+	fn bits(&mut self, count: u32) -> Result<u32, String> {
+		let mut value = 0;
+		for i in 0..count {
+			let byte = *self
+				.data
+				.get(self.position / 8)
+				.ok_or("unexpected end of the compressed data")?;
+			value |= ((byte >> (self.position % 8)) as u32 & 1) << i;
+			self.position += 1;
+		}
+		Ok(value)
+	}
+
+	fn align_to_byte(&mut self) {
+		self.position = self.position.div_ceil(8) * 8;
+	}
+}
+
+/// Canonical Huffman code, decoded one bit at a time.
+struct Huffman {
+	counts: [u16; 16], // number of codes of each length
+	symbols: Vec<u16>, // sorted by code
+}
+
+impl Huffman {
+	fn new(lengths: &[u8]) -> Self {
+		let mut counts = [0u16; 16];
+		for &length in lengths {
+			counts[length as usize] += 1;
+		}
+		counts[0] = 0;
+		let mut offsets = [0u16; 16];
+		for length in 1..15 {
+			offsets[length + 1] = offsets[length] + counts[length];
+		}
+		let mut symbols = vec![0; lengths.len()];
+		for (symbol, &length) in lengths.iter().enumerate() {
+			if length != 0 {
+				symbols[offsets[length as usize] as usize] = symbol as u16;
+				offsets[length as usize] += 1;
+			}
+		}
+		Huffman { counts, symbols }
+	}
+
+	fn decode(&self, reader: &mut BitReader) -> Result<u16, String> {
+		let (mut code, mut first, mut index) = (0i32, 0i32, 0i32);
+		for length in 1..16 {
+			code |= reader.bits(1)? as i32;
+			let count = self.counts[length] as i32;
+			if code - first < count {
+				return Ok(self.symbols[(index + code - first) as usize]);
+			}
+			index += count;
+			first = (first + count) << 1;
+			code <<= 1;
+		}
+		Err("invalid Huffman code".to_owned())
+	}
+}
+
+const LENGTH_BASE: [u16; 29] = [
+	3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31, 35, 43, 51, 59, 67, 83, 99, 115, 131,
+	163, 195, 227, 258,
+];
+const LENGTH_EXTRA: [u8; 29] = [
+	0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 0,
+];
+const DISTANCE_BASE: [u16; 30] = [
+	1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193, 257, 385, 513, 769, 1025, 1537,
+	2049, 3073, 4097, 6145, 8193, 12289, 16385, 24577,
+];
+const DISTANCE_EXTRA: [u8; 30] = [
+	0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6, 7, 7, 8, 8, 9, 9, 10, 10, 11, 11, 12, 12, 13,
+	13,
+];
+/// Order of the code lengths of the code length alphabet in dynamic blocks.
+const CODE_LENGTH_ORDER: [usize; 19] = [
+	16, 17, 18, 0, 8, 7, 9, 6, 10, 5, 11, 4, 12, 3, 13, 2, 14, 1, 15,
+];
+
+/// Raw deflate (RFC 1951) decompression, fails instead of producing more than `limit` bytes.
+fn inflate(data: &[u8], limit: usize) -> Result<Vec<u8>, String> {
+	let mut reader = BitReader { data, position: 0 };
+	let mut out = Vec::with_capacity((data.len() * 4).min(limit));
+	loop {
+		let last = reader.bits(1)? == 1;
+		match reader.bits(2)? {
+			0 => {
+				reader.align_to_byte();
+				let length = reader.bits(16)?;
+				let complement = reader.bits(16)?;
+				if length != !complement & 0xffff {
+					return Err("invalid stored block".to_owned());
+				}
+				let start = reader.position / 8;
+				let block = data
+					.get(start..start + length as usize)
+					.ok_or("unexpected end of the compressed data")?;
+				if out.len() + block.len() > limit {
+					return Err("more compressed data than expected".to_owned());
+				}
+				out.extend_from_slice(block);
+				reader.position += length as usize * 8;
+			}
+			1 => {
+				let mut lengths = [0u8; 288];
+				lengths[..144].fill(8);
+				lengths[144..256].fill(9);
+				lengths[256..280].fill(7);
+				lengths[280..].fill(8);
+				let literals = Huffman::new(&lengths);
+				let distances = Huffman::new(&[5; 30]);
+				inflate_block(&mut reader, &mut out, limit, &literals, &distances)?;
+			}
+			2 => {
+				let literal_count = reader.bits(5)? as usize + 257;
+				let distance_count = reader.bits(5)? as usize + 1;
+				let code_length_count = reader.bits(4)? as usize + 4;
+				let mut code_lengths = [0u8; 19];
+				for &index in &CODE_LENGTH_ORDER[..code_length_count] {
+					code_lengths[index] = reader.bits(3)? as u8;
+				}
+				let code_lengths = Huffman::new(&code_lengths);
+
+				let mut lengths = Vec::with_capacity(literal_count + distance_count);
+				while lengths.len() < literal_count + distance_count {
+					let (value, repeat) = match code_lengths.decode(&mut reader)? {
+						symbol @ 0..=15 => (symbol as u8, 1),
+						16 => (
+							*lengths.last().ok_or("repeat of no code length")?,
+							3 + reader.bits(2)?,
+						),
+						17 => (0, 3 + reader.bits(3)?),
+						_ => (0, 11 + reader.bits(7)?),
+					};
+					lengths.extend(std::iter::repeat_n(value, repeat as usize));
+				}
+				if lengths.len() != literal_count + distance_count {
+					return Err("too many code lengths".to_owned());
+				}
+				let literals = Huffman::new(&lengths[..literal_count]);
+				let distances = Huffman::new(&lengths[literal_count..]);
+				inflate_block(&mut reader, &mut out, limit, &literals, &distances)?;
+			}
+			_ => return Err("invalid block type".to_owned()),
+		}
+		if last {
+			return Ok(out);
+		}
+	}
+}
+
+fn inflate_block(
+	reader: &mut BitReader,
+	out: &mut Vec<u8>,
+	limit: usize,
+	literals: &Huffman,
+	distances: &Huffman,
+) -> Result<(), String> {
+	loop {
+		if out.len() > limit {
+			return Err("more compressed data than expected".to_owned());
+		}
+		let symbol = literals.decode(reader)? as usize;
+		match symbol {
+			0..=255 => out.push(symbol as u8),
+			256 => return Ok(()),
+			_ => {
+				let index = symbol - 257;
+				if index >= LENGTH_BASE.len() {
+					return Err("invalid length code".to_owned());
+				}
+				let length =
+					LENGTH_BASE[index] as usize + reader.bits(LENGTH_EXTRA[index] as u32)? as usize;
+				let index = distances.decode(reader)? as usize;
+				if index >= DISTANCE_BASE.len() {
+					return Err("invalid distance code".to_owned());
+				}
+				let distance = DISTANCE_BASE[index] as usize
+					+ reader.bits(DISTANCE_EXTRA[index] as u32)? as usize;
+				if distance > out.len() {
+					return Err("distance before the start of the data".to_owned());
+				}
+				// The copy can overlap the bytes it writes
+				let start = out.len() - distance;
+				for i in 0..length {
+					out.push(out[start + i]);
+				}
+			}
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	// The crcs are not checked, they are left at zero
+	fn chunk(kind: &[u8; 4], data: &[u8]) -> Vec<u8> {
+		let mut chunk = (data.len() as u32).to_be_bytes().to_vec();
+		chunk.extend_from_slice(kind);
+		chunk.extend_from_slice(data);
+		chunk.extend_from_slice(&[0; 4]);
+		chunk
+	}
+
+	fn png(
+		width: u32,
+		height: u32,
+		bit_depth: u8,
+		color_type: u8,
+		chunks: &[(&[u8; 4], &[u8])],
+		zlib: &[u8],
+	) -> Vec<u8> {
+		let mut header = width.to_be_bytes().to_vec();
+		header.extend_from_slice(&height.to_be_bytes());
+		header.extend_from_slice(&[bit_depth, color_type, 0, 0, 0]);
+
+		let mut png = vec![0x89, b'P', b'N', b'G', 0x0d, 0x0a, 0x1a, 0x0a];
+		png.extend(chunk(b"IHDR", &header));
+		for (kind, data) in chunks {
+			png.extend(chunk(kind, data));
+		}
+		png.extend(chunk(b"IDAT", zlib));
+		png.extend(chunk(b"IEND", &[]));
+		png
+	}
+
+	/// Zlib stream of a single stored block, its adler32 is not checked.
+	fn stored(raw: &[u8]) -> Vec<u8> {
+		let length = raw.len() as u16;
+		let mut zlib = vec![0x78, 0x01, 0x01];
+		zlib.extend_from_slice(&length.to_le_bytes());
+		zlib.extend_from_slice(&(!length).to_le_bytes());
+		zlib.extend_from_slice(raw);
+		zlib.extend_from_slice(&[0; 4]);
+		zlib
+	}
+
+	/// Filtered rows of a 2x5 RGBA image, row `y` using the filter `y`.
+	const FILTERED: [[u8; 9]; 5] = [
+		[0, 121, 66, 189, 242, 33, 6, 240, 132],
+		[1, 119, 98, 240, 243, 84, 235, 134, 90],
+		[2, 80, 165, 48, 94, 74, 77, 153, 60],
+		[3, 143, 195, 202, 162, 96, 148, 71, 136],
+		[4, 32, 127, 35, 165, 114, 155, 192, 166],
+	];
+	const UNFILTERED: [[u8; 4]; 10] = [
+		[121, 66, 189, 242],
+		[33, 6, 240, 132],
+		[119, 98, 240, 243],
+		[203, 77, 118, 77],
+		[199, 7, 32, 81],
+		[21, 154, 15, 137],
+		[242, 198, 218, 202],
+		[227, 68, 187, 49],
+		[18, 69, 253, 111],
+		[132, 223, 154, 215],
+	];
+
+	/// 16x16 grayscale image of `gray_pixel`, without filtering, compressed by zlib with fixed
+	/// Huffman codes and with dynamic ones.
+	const FIXED: [u8; 72] = [
+		120, 1, 99, 96, 192, 0, 114, 54, 81, 21, 211, 182, 192, 41, 6, 6, 155, 138, 45, 114, 81,
+		211, 224, 20, 3, 67, 212, 22, 155, 105, 114, 21, 112, 138, 129, 161, 66, 110, 154, 205,
+		150, 40, 56, 197, 192, 48, 45, 74, 110, 75, 133, 13, 156, 98, 96, 216, 50, 173, 34, 202,
+		70, 14, 78, 49, 12, 86, 107, 1, 252, 15, 69, 7,
+	];
+	const DYNAMIC: [u8; 60] = [
+		120, 218, 205, 205, 49, 1, 192, 64, 12, 195, 64, 33, 49, 146, 32, 49, 146, 32, 49, 18, 3,
+		252, 45, 75, 9, 84, 203, 141, 130, 79, 26, 111, 122, 192, 108, 229, 28, 224, 78, 180, 7,
+		172, 50, 245, 1, 177, 186, 115, 64, 179, 30, 29, 252, 117, 251, 0, 252, 15, 69, 7,
+	];
+
+	fn gray_pixel(x: u8, y: u8) -> [u8; 4] {
+		let v = (x * y) % 7 * 30;
+		[v, v, v, 255]
+	}
+
+	#[test]
+	fn every_filter_type() {
+		let image = decode(&png(2, 5, 8, 6, &[], &stored(&FILTERED.concat()))).unwrap();
+		assert_eq!((image.width, image.height), (2, 5));
+		assert_eq!(image.pixels, UNFILTERED);
+	}
+
+	#[test]
+	fn stored_fixed_and_dynamic_blocks() {
+		// Type of the first block, after the zlib header and the last block bit
+		assert_eq!(FIXED[2] >> 1 & 3, 1);
+		assert_eq!(DYNAMIC[2] >> 1 & 3, 2);
+		let expected: Vec<[u8; 4]> = (0..16)
+			.flat_map(|y| (0..16).map(move |x| gray_pixel(x, y)))
+			.collect();
+		let raw: Vec<u8> = (0..16)
+			.flat_map(|y| std::iter::once(0).chain((0..16).map(move |x| gray_pixel(x, y)[0])))
+			.collect();
+
+		for zlib in [&stored(&raw)[..], &FIXED, &DYNAMIC] {
+			let image = decode(&png(16, 16, 8, 0, &[], zlib)).unwrap();
+			assert_eq!(image.pixels, expected);
+		}
+	}
+
+	#[test]
+	fn palette_with_transparency() {
+		let palette = [10, 11, 12, 20, 21, 22, 30, 31, 32, 40, 41, 42];
+		// 2 bits indices 0, 1, 2, 3 and 1, the last byte is padded
+		let raw = [0, 0b0001_1011, 0b0100_0000];
+		let chunks: [(&[u8; 4], &[u8]); 2] = [(b"PLTE", &palette), (b"tRNS", &[0, 128])];
+		let image = decode(&png(5, 1, 2, 3, &chunks, &stored(&raw))).unwrap();
+		assert_eq!(
+			image.pixels,
+			[
+				[10, 11, 12, 0],
+				[20, 21, 22, 128],
+				[30, 31, 32, 255],
+				[40, 41, 42, 255],
+				[20, 21, 22, 128],
+			]
+		);
+	}
+
+	#[test]
+	fn sixteen_bits_keep_the_high_byte() {
+		let first: [u8; 8] = [0x12, 0x34, 0x56, 0x78, 0x9a, 0xbc, 0xff, 0x00];
+		let second: [u8; 8] = [0x20, 0x01, 0x30, 0x02, 0x40, 0x03, 0x50, 0x04];
+		// The sub filter predicts from the previous pixel, 8 bytes before
+		let mut raw = vec![1];
+		raw.extend_from_slice(&first);
+		raw.extend(second.iter().zip(first).map(|(&b, a)| b.wrapping_sub(a)));
+		let image = decode(&png(2, 1, 16, 6, &[], &stored(&raw))).unwrap();
+		assert_eq!(
+			image.pixels,
+			[[0x12, 0x56, 0x9a, 0xff], [0x20, 0x30, 0x40, 0x50]]
+		);
+	}
+
+	#[test]
+	fn truncated_input_is_an_error() {
+		let png_file = png(16, 16, 8, 0, &[], &DYNAMIC);
+		assert!(decode(&png_file).is_ok());
+		for length in 0..png_file.len() {
+			assert!(decode(&png_file[..length]).is_err(), "length {}", length);
+		}
+		// Complete chunks around a cut compressed stream
+		for length in 2..DYNAMIC.len() - 4 {
+			assert!(decode(&png(16, 16, 8, 0, &[], &DYNAMIC[..length])).is_err());
+		}
+	}
+
+	#[test]
+	fn oversized_images_are_rejected() {
+		let raw: Vec<u8> = (0..16)
+			.flat_map(|_| std::iter::once(0).chain([0; 16]))
+			.collect();
+		// The sizes computed from the header overflow
+		for (width, height) in [(u32::MAX, u32::MAX), (u32::MAX, 1), (1, u32::MAX)] {
+			assert!(decode(&png(width, height, 16, 6, &[], &stored(&raw))).is_err());
+		}
+		// More data than the header describes
+		assert!(decode(&png(4, 4, 8, 0, &[], &stored(&raw))).is_err());
+		assert!(decode(&png(4, 4, 8, 0, &[], &DYNAMIC)).is_err());
+		// A chunk length past the end of the file
+		let mut png_file = png(16, 16, 8, 0, &[], &DYNAMIC);
+		png_file[33..37].copy_from_slice(&u32::MAX.to_be_bytes());
+		assert!(decode(&png_file).is_err());
+	}
+}
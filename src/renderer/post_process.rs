@@ -0,0 +1,206 @@
+use super::{texture::Texture, Renderer};
+
+/// A step of the post processing stack, reads the output of the previous step and writes the hdr
+/// target given to the next one.
+pub trait PostProcessPass {
+	fn name(&self) -> &'static str;
+
+	/// Called once the renderer and stack targets have been resized.
+	fn resize(&mut self, renderer: &mut Renderer);
+
+	fn encode(
+		&mut self,
+		renderer: &Renderer,
+		encoder: &mut wgpu::CommandEncoder,
+		input: &Texture,
+		output: &Texture,
+	);
+}
+
+pub struct PostProcessEntry {
+	pub pass: Box<dyn PostProcessPass>,
+	pub enabled: bool,
+}
+
+/// Ordered list of passes, ping-ponging between two hdr targets the size of the screen.
+pub struct PostProcessStack {
+	pub passes: Vec<PostProcessEntry>,
+	targets: [Texture; 2],
+	// Only created once a comparison is encoded
+	comparison_targets: Option<[Texture; 2]>,
+}
+
+fn create_targets(device: &wgpu::Device, width: u32, height: u32, label: &str) -> [Texture; 2] {
+	let create_target = |i| {
+		Texture::new(
+			device,
+			Some(&format!("{} {}", label, i)),
+			width,
+			height,
+			1,
+			1,
+			1,
+			wgpu::TextureDimension::D2,
+			wgpu::TextureFormat::Rgba16Float,
+			wgpu::TextureUsages::RENDER_ATTACHMENT
+				| wgpu::TextureUsages::STORAGE_BINDING
+				| wgpu::TextureUsages::TEXTURE_BINDING
+				// Passes keeping a history copy their output
+				| wgpu::TextureUsages::COPY_SRC,
+			wgpu::TextureAspect::All,
+		)
+	};
+	[create_target(0), create_target(1)]
+}
+
+/// Encodes the enabled `passes` in order from `input`, alternating between `targets`.
+fn encode_passes<'a>(
+	passes: &mut [PostProcessEntry],
+	renderer: &Renderer,
+	encoder: &mut wgpu::CommandEncoder,
+	mut input: &'a Texture,
+	targets: &'a [Texture; 2],
+) -> &'a Texture {
+	// The input may be one of the targets
+	let mut target = if std::ptr::eq(input, &targets[0]) {
+		1
+	} else {
+		0
+	};
+	for entry in passes.iter_mut().filter(|entry| entry.enabled) {
+		let output = &targets[target];
+		entry.pass.encode(renderer, encoder, input, output);
+		input = output;
+		target = 1 - target;
+	}
+	input
+}
+
+impl PostProcessStack {
+	pub fn new(device: &wgpu::Device, width: u32, height: u32) -> Self {
+		PostProcessStack {
+			passes: vec![],
+			targets: create_targets(device, width, height, "post process target"),
+			comparison_targets: None,
+		}
+	}
+
+	pub fn push(&mut self, pass: Box<dyn PostProcessPass>) {
+		self.passes.push(PostProcessEntry {
+			pass,
+			enabled: true,
+		});
+	}
+
+	/// Enables or disables the passes named `name`.
+	pub fn set_enabled(&mut self, name: &str, enabled: bool) {
+		for entry in self
+			.passes
+			.iter_mut()
+			.filter(|entry| entry.pass.name() == name)
+		{
+			entry.enabled = enabled;
+		}
+	}
+
+	pub fn resize(&mut self, renderer: &mut Renderer, size: wgpu::Extent3d) {
+		for target in self
+			.targets
+			.iter_mut()
+			.chain(self.comparison_targets.iter_mut().flatten())
+		{
+			target.recreate(&renderer.context.device, size);
+		}
+		for entry in self.passes.iter_mut() {
+			entry.pass.resize(renderer);
+		}
+	}
+
+	/// Encodes the enabled passes in order, starting from the hdr texture of the renderer.
+	/// Returns the texture holding the result, the hdr texture itself if every pass is disabled.
+	pub fn encode<'a>(
+		&'a mut self,
+		renderer: &'a Renderer,
+		encoder: &mut wgpu::CommandEncoder,
+	) -> &'a Texture {
+		encode_passes(
+			&mut self.passes,
+			renderer,
+			encoder,
+			&renderer.hdr_texture,
+			&self.targets,
+		)
+	}
+
+	/// Encodes the stack with and without the pass named `split`, for a side by side comparison.
+	/// The passes before it are shared, the ones after it are encoded twice so they must not keep
+	/// state between frames. Returns the results with and without the pass.
+	pub fn encode_comparison<'a>(
+		&'a mut self,
+		renderer: &'a Renderer,
+		encoder: &mut wgpu::CommandEncoder,
+		split: &str,
+	) -> (&'a Texture, &'a Texture) {
+		let split_index = self
+			.passes
+			.iter()
+			.position(|entry| entry.pass.name() == split)
+			.unwrap_or(self.passes.len());
+		let (shared_passes, passes) = self.passes.split_at_mut(split_index);
+
+		let shared = encode_passes(
+			shared_passes,
+			renderer,
+			encoder,
+			&renderer.hdr_texture,
+			&self.targets,
+		);
+		let size = renderer.hdr_texture.size;
+		let comparison_targets = self.comparison_targets.get_or_insert_with(|| {
+			create_targets(
+				&renderer.context.device,
+				size.width,
+				size.height,
+				"post process comparison target",
+			)
+		});
+		// Encoded first, the other branch may write over the shared result
+		let without = encode_passes(
+			passes.get_mut(1..).unwrap_or_default(),
+			renderer,
+			encoder,
+			shared,
+			comparison_targets,
+		);
+		let with = encode_passes(passes, renderer, encoder, shared, &self.targets);
+		(with, without)
+	}
+}
+
+/// Draws the fullscreen quad of the renderer into `output`, for the passes made of a single
+/// fragment shader.
+pub fn encode_fullscreen(
+	renderer: &Renderer,
+	encoder: &mut wgpu::CommandEncoder,
+	label: Option<&str>,
+	pipeline: &wgpu::RenderPipeline,
+	bind_group: &wgpu::BindGroup,
+	output: &wgpu::TextureView,
+) {
+	let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+		label,
+		color_attachments: &[wgpu::RenderPassColorAttachment {
+			view: output,
+			resolve_target: None,
+			ops: wgpu::Operations {
+				load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+				store: true,
+			},
+		}],
+		depth_stencil_attachment: None,
+	});
+	render_pass.set_pipeline(pipeline);
+	render_pass.set_vertex_buffer(0, renderer.fullscreen_vertex_buffer.slice(..));
+	render_pass.set_bind_group(0, bind_group, &[]);
+	render_pass.draw(0..6, 0..1);
+}
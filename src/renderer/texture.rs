@@ -20,6 +20,7 @@ impl Texture {
 		label: Option<&str>,
 		width: u32,
 		height: u32,
+		depth_or_array_layers: u32,
 		mip_count: u32,
 		sample_count: u32,
 		dimension: wgpu::TextureDimension,
@@ -30,7 +31,7 @@ impl Texture {
 		let size = wgpu::Extent3d {
 			width,
 			height,
-			depth_or_array_layers: 1,
+			depth_or_array_layers,
 		};
 		let texture = device.create_texture(&wgpu::TextureDescriptor {
 			label,
@@ -171,3 +172,86 @@ impl Texture {
 		self.mip_view = mip_view;
 	}
 }
+
+/// Round to nearest even conversion, Rgba16Float is the only filterable float format
+/// wgpu guarantees.
+pub fn f32_to_f16(value: f32) -> u16 {
+	let bits = value.to_bits();
+	let sign = ((bits >> 16) & 0x8000) as u16;
+	let exponent = ((bits >> 23) & 0xff) as i32;
+	let mantissa = bits & 0x7f_ffff;
+
+	if exponent == 0xff {
+		// Inf and NaN
+		return sign | 0x7c00 | if mantissa != 0 { 0x200 } else { 0 };
+	}
+
+	let half_exponent = exponent - 127 + 15;
+	if half_exponent >= 0x1f {
+		return sign | 0x7c00;
+	}
+	if half_exponent <= 0 {
+		// Subnormal or zero
+		if half_exponent < -10 {
+			return sign;
+		}
+		let mantissa = mantissa | 0x80_0000;
+		let shift = (14 - half_exponent) as u32;
+		let half_mantissa = mantissa >> shift;
+		let remainder = mantissa & ((1 << shift) - 1);
+		let halfway = 1 << (shift - 1);
+		let round =
+			(remainder > halfway || (remainder == halfway && half_mantissa & 1 == 1)) as u32;
+		return sign | (half_mantissa + round) as u16;
+	}
+
+	let half = ((half_exponent as u32) << 10) | (mantissa >> 13);
+	let remainder = mantissa & 0x1fff;
+	let round = (remainder > 0x1000 || (remainder == 0x1000 && half & 1 == 1)) as u32;
+	// A carry into the exponent is still the correctly rounded value
+	sign | (half + round) as u16
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn half_float_rounds_to_nearest_even() {
+		assert_eq!(f32_to_f16(1.0), 0x3c00);
+		assert_eq!(f32_to_f16(-2.0), 0xc000);
+		assert_eq!(f32_to_f16(65504.0), 0x7bff);
+		// Halfway cases round to the even mantissa
+		assert_eq!(f32_to_f16(1.0 + 2f32.powi(-11)), 0x3c00);
+		assert_eq!(f32_to_f16(1.0 + 3.0 * 2f32.powi(-11)), 0x3c02);
+		assert_eq!(f32_to_f16(1.0 + 2f32.powi(-11) + 2f32.powi(-20)), 0x3c01);
+		// A mantissa carry moves to the next exponent
+		assert_eq!(f32_to_f16(2.0 - 2f32.powi(-12)), 0x4000);
+	}
+
+	#[test]
+	fn half_float_subnormals() {
+		assert_eq!(f32_to_f16(2f32.powi(-14)), 0x0400);
+		assert_eq!(f32_to_f16(2f32.powi(-15)), 0x0200);
+		assert_eq!(f32_to_f16(2f32.powi(-24)), 0x0001);
+		assert_eq!(f32_to_f16(-2f32.powi(-24)), 0x8001);
+		assert_eq!(f32_to_f16(2f32.powi(-25)), 0x0000);
+		assert_eq!(f32_to_f16(3.0 * 2f32.powi(-25)), 0x0002);
+		assert_eq!(f32_to_f16(2f32.powi(-30)), 0x0000);
+		assert_eq!(f32_to_f16(-0.0), 0x8000);
+		// The largest subnormal rounds up to the smallest normal
+		assert_eq!(f32_to_f16(2f32.powi(-14) - 2f32.powi(-26)), 0x0400);
+	}
+
+	#[test]
+	fn half_float_overflow_and_nan() {
+		assert_eq!(f32_to_f16(65520.0), 0x7c00);
+		assert_eq!(f32_to_f16(1.0e6), 0x7c00);
+		assert_eq!(f32_to_f16(-1.0e6), 0xfc00);
+		assert_eq!(f32_to_f16(f32::INFINITY), 0x7c00);
+		assert_eq!(f32_to_f16(f32::NEG_INFINITY), 0xfc00);
+		let nan = f32_to_f16(f32::NAN);
+		assert_eq!(nan & 0x7c00, 0x7c00);
+		assert_ne!(nan & 0x3ff, 0);
+	}
+}
@@ -0,0 +1,165 @@
+use std::path::Path;
+
+use wgpu::util::DeviceExt;
+
+use crate::bloom::PbrParam;
+use crate::pbr_textures::PbrTextures;
+use crate::renderer::{mesh, Renderer};
+use crate::{gltf, obj};
+
+/// Loads a Wavefront `.obj` file, or a glTF scene for the other extensions.
+pub fn load(path: &Path) -> Result<gltf::Scene, String> {
+	match path.extension() {
+		Some(extension) if extension.eq_ignore_ascii_case("obj") => obj::load(path),
+		_ => gltf::Scene::load(path),
+	}
+}
+
+/// Content of the matrix buffer of the pbr pass.
+pub fn object_matrices(view_proj: uv::Mat4, model: uv::Mat4) -> [uv::Mat4; 3] {
+	[view_proj, model, model.inversed().transposed()]
+}
+
+/// Material of a mesh drawn with the pipeline of `pbr_mat`. Its first bind group holds the
+/// matrices, `param` and the light buffer of `pbr_mat`, in `bind_groups_buffers[0]` and `[1]`,
+/// and its second one the maps of `textures`, which it keeps alive.
+pub fn create_object_material(
+	renderer: &Renderer,
+	pbr_mat: &mesh::Material,
+	label: &str,
+	model: uv::Mat4,
+	param: &PbrParam,
+	textures: &PbrTextures,
+) -> mesh::Material {
+	let device = &renderer.context.device;
+	let matrices = object_matrices(renderer.camera.view_proj, model);
+	let matrix_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+		label: Some(&format!("{} Matix buffer", label)),
+		contents: unsafe { matrices.align_to::<u8>().1 },
+		usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+	});
+	let param = [*param];
+	let param_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+		label: Some(&format!("{} param buffer", label)),
+		contents: unsafe { param.align_to::<u8>().1 },
+		usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+	});
+
+	let mut object_mat = mesh::Material::new(2, 0);
+	object_mat
+		.bind_group
+		.push(device.create_bind_group(&wgpu::BindGroupDescriptor {
+			label: Some(&format!(
+				"{} matrices, PbrParam and lights bind group",
+				label
+			)),
+			layout: &pbr_mat.bind_group_layout[0],
+			entries: &[
+				wgpu::BindGroupEntry {
+					binding: 0,
+					resource: matrix_buffer.as_entire_binding(),
+				},
+				wgpu::BindGroupEntry {
+					binding: 1,
+					resource: param_buffer.as_entire_binding(),
+				},
+				wgpu::BindGroupEntry {
+					binding: 2,
+					resource: pbr_mat.bind_groups_buffers[2].as_entire_binding(),
+				},
+			],
+		}));
+	object_mat.bind_group.push(
+		device.create_bind_group(&wgpu::BindGroupDescriptor {
+			label: Some(&format!("{} material textures bind group", label)),
+			layout: &pbr_mat.bind_group_layout[3],
+			entries: &textures
+				.pbr_bindings()
+				.into_iter()
+				.enumerate()
+				.map(|(binding, resource)| wgpu::BindGroupEntry {
+					binding: binding as u32,
+					resource,
+				})
+				.collect::<Vec<_>>(),
+		}),
+	);
+	object_mat.bind_groups_buffers.push(matrix_buffer);
+	object_mat.bind_groups_buffers.push(param_buffer);
+	object_mat
+}
+
+/// Mesh of a loaded scene, drawn instead of the sphere.
+pub struct SceneObject {
+	pub mesh: mesh::Mesh,
+	pub param: PbrParam, // its camera position and environment intensity follow the sphere
+}
+
+pub fn create_scene(renderer: &Renderer, scene: &gltf::Scene) -> Vec<SceneObject> {
+	let device = &renderer.context.device;
+	let queue = &renderer.context.queue;
+	let pbr_mat = renderer
+		.meshes
+		.get("pbr")
+		.unwrap()
+		.material
+		.as_ref()
+		.unwrap();
+
+	let default_material = gltf::Material::default();
+	let mut textures: Vec<Option<PbrTextures>> =
+		(0..=scene.materials.len()).map(|_| None).collect();
+	let mut objects = Vec::with_capacity(scene.primitives.len());
+	for (i, primitive) in scene.primitives.iter().enumerate() {
+		// The default material is the last one
+		let material_index = primitive
+			.material
+			.filter(|&m| m < scene.materials.len())
+			.unwrap_or(scene.materials.len());
+		let material = scene
+			.materials
+			.get(material_index)
+			.unwrap_or(&default_material);
+		let textures = textures[material_index].get_or_insert_with(|| {
+			let images = material
+				.maps
+				.map(|map| map.and_then(|image| scene.images.get(image)?.as_ref()));
+			PbrTextures::from_images(device, queue, images)
+		});
+
+		let param = PbrParam {
+			cam_pos: renderer.camera.position,
+			metallic: material.metallic,
+			albedo: material.albedo,
+			roughness: material.roughness,
+			emissive_color: material.emissive,
+			ao: 1.0,
+			emissive_intensity: material.emissive_strength,
+			environment_intensity: 1.0,
+			normal_scale: material.normal_scale,
+			occlusion_strength: material.occlusion_strength,
+		};
+		let label = format!("scene primitive {}", i);
+		let object_mat =
+			create_object_material(renderer, pbr_mat, &label, primitive.model, &param, textures);
+		let mut mesh = mesh::Mesh::new(
+			device,
+			Some(&label),
+			&primitive.vertices,
+			Some(&primitive.indices),
+			Some(object_mat),
+		);
+		mesh.model = primitive.model;
+		objects.push(SceneObject { mesh, param });
+	}
+	objects
+}
+
+/// Recorded in the pbr render pass, the pipeline and the environment and shadow bind groups of
+/// the sphere are still set.
+pub fn render_object<'a>(mesh: &'a mesh::Mesh, render_pass: &mut wgpu::RenderPass<'a>) {
+	let object_mat = mesh.material.as_ref().unwrap();
+	render_pass.set_bind_group(0, &object_mat.bind_group[0], &[]);
+	render_pass.set_bind_group(3, &object_mat.bind_group[1], &[]);
+	mesh.draw(render_pass, 0..1);
+}
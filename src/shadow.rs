@@ -0,0 +1,367 @@
+use wgpu::util::DeviceExt;
+
+use crate::lights;
+use crate::renderer::{
+	mesh::{Material, Mesh},
+	texture::Texture,
+	Instance, Renderer, Vertex,
+};
+use crate::sphere_grid;
+
+/// Radius around the origin of the sphere casting a shadow, the frustum of the light encloses it.
+const CASTER_RADIUS: f32 = 2.0;
+/// Radius around the origin of the meshes receiving a shadow, the depth range of the light covers
+/// it. Receivers outside of the frustum are lit.
+const RECEIVER_RADIUS: f32 = 12.0;
+
+pub const SHADOW_RESOLUTIONS: [u32; 4] = [512, 1024, 2048, 4096];
+
+#[derive(Clone, Copy, Debug)]
+pub struct ShadowSettings {
+	pub enabled: bool,
+	pub resolution: u32,
+	pub depth_bias: f32,  // subtracted from the depth of the receiver
+	pub normal_bias: f32, // offset of the receiver along its normal, in world units
+}
+
+impl Default for ShadowSettings {
+	fn default() -> Self {
+		ShadowSettings {
+			enabled: true,
+			resolution: 2048,
+			depth_bias: 0.0001,
+			normal_bias: 0.03,
+		}
+	}
+}
+
+/// Spheres enclosing the meshes casting and receiving a shadow.
+#[derive(Clone, Copy, Debug)]
+pub struct ShadowBounds {
+	pub center: uv::Vec3,
+	pub caster_radius: f32,
+	pub receiver_radius: f32,
+}
+
+impl Default for ShadowBounds {
+	fn default() -> Self {
+		ShadowBounds {
+			center: uv::Vec3::zero(),
+			caster_radius: CASTER_RADIUS,
+			receiver_radius: RECEIVER_RADIUS,
+		}
+	}
+}
+
+#[repr(C, align(16))]
+#[derive(Clone, Copy)]
+pub struct ShadowParam {
+	pub light_view_proj: uv::Mat4,
+	pub depth_bias: f32,
+	pub normal_bias: f32,
+	pub texel_size: f32, // of the shadow map, in uv
+	pub enabled: u32,
+}
+
+/// Matrix of the shadow map of `light`. Directional lights use an orthographic projection
+/// around the casters, spot and point lights a perspective one toward them, clamped to the cone
+/// of spot lights. Only one direction of point lights is shadowed.
+fn light_view_proj(light: &lights::Light, bounds: &ShadowBounds) -> uv::Mat4 {
+	// A null direction can be typed in the gui
+	let direction = if light.direction.mag_sq() > 0.0 {
+		light.direction.normalized()
+	} else {
+		-uv::Vec3::unit_y()
+	};
+	let up = |dir: uv::Vec3| {
+		if dir.y.abs() > 0.99 {
+			uv::Vec3::unit_z()
+		} else {
+			uv::Vec3::unit_y()
+		}
+	};
+	match light.kind {
+		lights::LightKind::Directional => {
+			let eye = bounds.center - direction * bounds.receiver_radius;
+			let view = uv::Mat4::look_at(eye, bounds.center, up(direction));
+			let proj = uv::projection::orthographic_wgpu_dx(
+				-bounds.caster_radius,
+				bounds.caster_radius,
+				-bounds.caster_radius,
+				bounds.caster_radius,
+				0.0,
+				bounds.receiver_radius * 2.0,
+			);
+			proj * view
+		}
+		lights::LightKind::Point | lights::LightKind::Spot => {
+			let to_center = bounds.center - light.position;
+			let distance = to_center.mag();
+			let max_fov = 170f32.to_radians();
+			let to_casters = if distance > 0.0 {
+				to_center / distance
+			} else {
+				direction
+			};
+			let casters_fov = if distance > bounds.caster_radius {
+				((bounds.caster_radius / distance).asin() * 2.0).min(max_fov)
+			} else {
+				max_fov
+			};
+			let (forward, fov) = if light.kind == lights::LightKind::Spot {
+				(direction, (light.outer_angle * 2.0).min(max_fov))
+			} else {
+				(to_casters, casters_fov)
+			};
+			let view = uv::Mat4::look_at(light.position, light.position + forward, up(forward));
+			// Receivers in front of the near plane are closer than the casters, and lit
+			let near = (distance - bounds.caster_radius).max(0.05);
+			let far = distance + bounds.receiver_radius;
+			uv::projection::perspective_wgpu_dx(fov, 1.0, near, far) * view
+		}
+	}
+}
+
+/// Shadow map of the first light of the scene.
+pub struct ShadowMap {
+	pub settings: ShadowSettings,
+	pub bounds: ShadowBounds,
+	pub texture: Texture,
+	param_buffer: wgpu::Buffer,
+	shadow_mat: Material,
+}
+
+impl ShadowMap {
+	pub fn new(device: &wgpu::Device, settings: ShadowSettings) -> Self {
+		let param_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+			label: Some("shadow param buffer"),
+			contents: unsafe {
+				[ShadowParam {
+					light_view_proj: uv::Mat4::identity(),
+					depth_bias: 0.0,
+					normal_bias: 0.0,
+					texel_size: 0.0,
+					enabled: 0,
+				}]
+				.align_to::<u8>()
+				.1
+			},
+			usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+		});
+
+		let mut shadow_mat = Material::new(1, 1);
+		shadow_mat.add_bind_group(
+			device,
+			Some("shadow bind group"),
+			vec![wgpu::BindGroupLayoutEntry {
+				binding: 0,
+				visibility: wgpu::ShaderStages::VERTEX,
+				ty: wgpu::BindingType::Buffer {
+					ty: wgpu::BufferBindingType::Uniform,
+					has_dynamic_offset: false,
+					min_binding_size: std::num::NonZeroU64::new(
+						std::mem::size_of::<ShadowParam>() as u64
+					),
+				},
+				count: None,
+			}],
+			vec![param_buffer.as_entire_binding()],
+		);
+		shadow_mat.add_push_constant(
+			wgpu::ShaderStages::VERTEX,
+			0..std::mem::size_of::<uv::Mat4>() as u32,
+		);
+		shadow_mat.render_pipeline = Some(create_shadow_pipeline(device, &shadow_mat));
+
+		ShadowMap {
+			settings,
+			bounds: ShadowBounds::default(),
+			texture: create_shadow_texture(device, settings.resolution),
+			param_buffer,
+			shadow_mat,
+		}
+	}
+
+	/// Recreates the shadow map when the resolution changed and uploads the parameters for
+	/// `light`, `None` when there is no light.
+	pub fn update(
+		&mut self,
+		device: &wgpu::Device,
+		queue: &wgpu::Queue,
+		light: Option<&lights::Light>,
+	) {
+		if self.texture.size.width != self.settings.resolution {
+			self.texture = create_shadow_texture(device, self.settings.resolution);
+		}
+		let param = [ShadowParam {
+			light_view_proj: light.map_or(uv::Mat4::identity(), |light| {
+				light_view_proj(light, &self.bounds)
+			}),
+			depth_bias: self.settings.depth_bias,
+			normal_bias: self.settings.normal_bias,
+			texel_size: 1.0 / self.settings.resolution as f32,
+			enabled: (self.settings.enabled && light.is_some()) as u32,
+		}];
+		queue.write_buffer(&self.param_buffer, 0, unsafe { param.align_to::<u8>().1 });
+	}
+
+	/// Layout of the shadow bind group of the pbr pass.
+	pub fn pbr_layout_entries() -> Vec<wgpu::BindGroupLayoutEntry> {
+		vec![
+			wgpu::BindGroupLayoutEntry {
+				binding: 0,
+				visibility: wgpu::ShaderStages::FRAGMENT,
+				ty: wgpu::BindingType::Texture {
+					sample_type: wgpu::TextureSampleType::Depth,
+					view_dimension: wgpu::TextureViewDimension::D2,
+					multisampled: false,
+				},
+				count: None,
+			},
+			wgpu::BindGroupLayoutEntry {
+				binding: 1,
+				visibility: wgpu::ShaderStages::FRAGMENT,
+				ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Comparison),
+				count: None,
+			},
+			wgpu::BindGroupLayoutEntry {
+				binding: 2,
+				visibility: wgpu::ShaderStages::FRAGMENT,
+				ty: wgpu::BindingType::Buffer {
+					ty: wgpu::BufferBindingType::Uniform,
+					has_dynamic_offset: false,
+					min_binding_size: std::num::NonZeroU64::new(
+						std::mem::size_of::<ShadowParam>() as u64
+					),
+				},
+				count: None,
+			},
+		]
+	}
+
+	pub fn pbr_bindings(&self) -> Vec<wgpu::BindingResource<'_>> {
+		vec![
+			wgpu::BindingResource::TextureView(&self.texture.view),
+			wgpu::BindingResource::Sampler(self.texture.sampler.as_ref().unwrap()),
+			self.param_buffer.as_entire_binding(),
+		]
+	}
+}
+
+fn create_shadow_texture(device: &wgpu::Device, resolution: u32) -> Texture {
+	let mut texture = Texture::new(
+		device,
+		Some("shadow map"),
+		resolution,
+		resolution,
+		1,
+		1,
+		1,
+		wgpu::TextureDimension::D2,
+		wgpu::TextureFormat::Depth32Float,
+		wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING,
+		wgpu::TextureAspect::DepthOnly,
+	);
+	// Linear filtering of the comparison results, on top of the PCF of pbr.wgsl
+	texture.set_sampler(
+		device,
+		Some("shadow sampler"),
+		wgpu::AddressMode::ClampToEdge,
+		wgpu::FilterMode::Linear,
+		wgpu::FilterMode::Linear,
+		wgpu::FilterMode::Nearest,
+		None,
+		None,
+		Some(wgpu::CompareFunction::LessEqual),
+		None,
+		None,
+	);
+	texture
+}
+
+fn create_shadow_pipeline(device: &wgpu::Device, shadow_mat: &Material) -> wgpu::RenderPipeline {
+	let shader = device.create_shader_module(&wgpu::include_wgsl!("../assets/shaders/shadow.wgsl"));
+	let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+		label: Some("shadow pipeline layout"),
+		bind_group_layouts: &[&shadow_mat.bind_group_layout[0]],
+		push_constant_ranges: &shadow_mat.push_constant,
+	});
+
+	device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+		label: Some("shadow pipeline"),
+		layout: Some(&layout),
+		vertex: wgpu::VertexState {
+			module: &shader,
+			entry_point: "vs_main",
+			buffers: &[Vertex::layout(), Instance::layout()],
+		},
+		fragment: None,
+		primitive: wgpu::PrimitiveState {
+			topology: wgpu::PrimitiveTopology::TriangleList,
+			strip_index_format: None,
+			front_face: wgpu::FrontFace::Ccw,
+			cull_mode: None,
+			polygon_mode: wgpu::PolygonMode::Fill,
+			unclipped_depth: false,
+			conservative: false,
+		},
+		depth_stencil: Some(wgpu::DepthStencilState {
+			format: wgpu::TextureFormat::Depth32Float,
+			depth_write_enabled: true,
+			depth_compare: wgpu::CompareFunction::Less,
+			stencil: wgpu::StencilState::default(),
+			bias: wgpu::DepthBiasState::default(),
+		}),
+		multisample: wgpu::MultisampleState::default(),
+		multiview: None,
+	})
+}
+
+/// Renders the depth of the meshes drawn by the pbr pass from the light.
+pub fn render_shadow(renderer: &Renderer, encoder: &mut wgpu::CommandEncoder) {
+	let shadow = &renderer.shadow;
+	let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+		label: Some("shadow Render Pass"),
+		color_attachments: &[],
+		depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+			view: &shadow.texture.view,
+			depth_ops: Some(wgpu::Operations {
+				load: wgpu::LoadOp::Clear(1.0),
+				store: true,
+			}),
+			stencil_ops: None,
+		}),
+	});
+	if !shadow.settings.enabled {
+		return;
+	}
+
+	render_pass.set_pipeline(shadow.shadow_mat.render_pipeline.as_ref().unwrap());
+	render_pass.set_bind_group(0, &shadow.shadow_mat.bind_group[0], &[]);
+	render_pass.set_vertex_buffer(1, renderer.default_instance.slice(..));
+	if let (true, Some(instances)) = (renderer.scene.is_empty(), &renderer.sphere_grid) {
+		let sphere = renderer.meshes.get("pbr").unwrap();
+		let model = [sphere.model];
+		render_pass.set_push_constants(wgpu::ShaderStages::VERTEX, 0, unsafe {
+			model.align_to::<u8>().1
+		});
+		render_pass.set_vertex_buffer(1, instances.slice(..));
+		sphere.draw(&mut render_pass, 0..sphere_grid::INSTANCE_COUNT);
+		return;
+	}
+	let meshes: Vec<&Mesh> = if renderer.scene.is_empty() {
+		vec![
+			renderer.meshes.get("pbr").unwrap(),
+			renderer.meshes.get("ground").unwrap(),
+		]
+	} else {
+		renderer.scene.iter().map(|object| &object.mesh).collect()
+	};
+	for mesh in meshes {
+		let model = [mesh.model];
+		render_pass.set_push_constants(wgpu::ShaderStages::VERTEX, 0, unsafe {
+			model.align_to::<u8>().1
+		});
+		mesh.draw(&mut render_pass, 0..1);
+	}
+}
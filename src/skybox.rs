@@ -0,0 +1,187 @@
+use wgpu::util::DeviceExt;
+
+use crate::renderer::{mesh, Renderer, Vertex};
+
+#[repr(C, align(16))]
+#[derive(Clone, Copy)]
+pub struct SkyboxParam {
+	pub inv_view_proj: uv::Mat4, // set from the camera by the renderer
+	pub rotation: f32,           // around the up axis, in radians
+	pub intensity: f32,
+}
+
+impl Default for SkyboxParam {
+	fn default() -> Self {
+		SkyboxParam {
+			inv_view_proj: uv::Mat4::identity(),
+			rotation: 0.0,
+			intensity: 1.0,
+		}
+	}
+}
+
+/// Draws the environment cubemap of the renderer behind the pbr geometry, into the hdr texture
+/// so its bright regions bloom.
+pub fn init_skybox(renderer: &Renderer) -> mesh::Mesh {
+	let param = [SkyboxParam {
+		inv_view_proj: renderer.camera.view_proj.inversed(),
+		..Default::default()
+	}];
+	let param_buffer =
+		renderer
+			.context
+			.device
+			.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+				label: Some("skybox param buffer"),
+				contents: unsafe { param.align_to::<u8>().1 },
+				usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+			});
+
+	let mut skybox_mat = mesh::Material::new(1, 0);
+	skybox_mat.add_bind_group(
+		&renderer.context.device,
+		Some("skybox bind group"),
+		vec![
+			wgpu::BindGroupLayoutEntry {
+				binding: 0,
+				visibility: wgpu::ShaderStages::FRAGMENT,
+				ty: wgpu::BindingType::Buffer {
+					ty: wgpu::BufferBindingType::Uniform,
+					has_dynamic_offset: false,
+					min_binding_size: std::num::NonZeroU64::new(
+						std::mem::size_of::<SkyboxParam>() as u64
+					),
+				},
+				count: None,
+			},
+			wgpu::BindGroupLayoutEntry {
+				binding: 1,
+				visibility: wgpu::ShaderStages::FRAGMENT,
+				ty: wgpu::BindingType::Texture {
+					sample_type: wgpu::TextureSampleType::Float { filterable: true },
+					view_dimension: wgpu::TextureViewDimension::Cube,
+					multisampled: false,
+				},
+				count: None,
+			},
+			wgpu::BindGroupLayoutEntry {
+				binding: 2,
+				visibility: wgpu::ShaderStages::FRAGMENT,
+				ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+				count: None,
+			},
+		],
+		skybox_bindings(renderer, &param_buffer),
+	);
+	skybox_mat.bind_groups_buffers.push(param_buffer);
+
+	set_skybox_sample_count(renderer, &mut skybox_mat);
+
+	mesh::Mesh::new::<u32>(
+		&renderer.context.device,
+		Some("skyboxMesh"),
+		&vec![],
+		None,
+		Some(skybox_mat),
+	)
+}
+
+fn skybox_bindings<'a>(
+	renderer: &'a Renderer,
+	param_buffer: &'a wgpu::Buffer,
+) -> Vec<wgpu::BindingResource<'a>> {
+	let cubemap = &renderer.environment.cubemap;
+	vec![
+		wgpu::BindingResource::Buffer(wgpu::BufferBinding {
+			buffer: param_buffer,
+			offset: 0,
+			size: std::num::NonZeroU64::new(std::mem::size_of::<SkyboxParam>() as u64),
+		}),
+		wgpu::BindingResource::TextureView(&cubemap.view),
+		wgpu::BindingResource::Sampler(cubemap.sampler.as_ref().unwrap()),
+	]
+}
+
+/// Binds the current environment cubemap of the renderer.
+pub fn set_skybox_environment(renderer: &Renderer, skybox_mat: &mut mesh::Material) {
+	let param_buffer = skybox_mat.bind_groups_buffers.remove(0);
+	skybox_mat.set_bind_group(
+		&renderer.context.device,
+		Some("skybox bind group"),
+		0,
+		skybox_bindings(renderer, &param_buffer),
+	);
+	skybox_mat.bind_groups_buffers.insert(0, param_buffer);
+}
+
+/// The skybox is drawn in the pbr pass, its pipeline follows the sample count of
+/// `renderer.anti_aliasing`.
+pub fn set_skybox_sample_count(renderer: &Renderer, skybox_mat: &mut mesh::Material) {
+	let device = &renderer.context.device;
+	let shader = device.create_shader_module(&wgpu::include_wgsl!("../assets/shaders/skybox.wgsl"));
+	let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+		label: Some("skybox pipeline layout"),
+		bind_group_layouts: &[&skybox_mat.bind_group_layout[0]],
+		push_constant_ranges: &[],
+	});
+
+	skybox_mat.render_pipeline = Some(device.create_render_pipeline(
+		&wgpu::RenderPipelineDescriptor {
+			label: Some("skybox pipeline"),
+			layout: Some(&layout),
+			vertex: wgpu::VertexState {
+				module: &shader,
+				entry_point: "vs_main",
+				buffers: &[Vertex::layout()],
+			},
+			fragment: Some(wgpu::FragmentState {
+				module: &shader,
+				entry_point: "fs_main",
+				targets: &[wgpu::ColorTargetState {
+					format: renderer.hdr_texture.format,
+					blend: Some(wgpu::BlendState::REPLACE),
+					write_mask: wgpu::ColorWrites::ALL,
+				}],
+			}),
+			primitive: wgpu::PrimitiveState {
+				topology: wgpu::PrimitiveTopology::TriangleList,
+				strip_index_format: None,
+				front_face: wgpu::FrontFace::Ccw,
+				cull_mode: Some(wgpu::Face::Back),
+				polygon_mode: wgpu::PolygonMode::Fill,
+				unclipped_depth: false,
+				conservative: false,
+			},
+			// The sky is at the far plane, where the depth was cleared to, and is not written so
+			// the depth of field and TAA passes see it at the far plane
+			depth_stencil: Some(wgpu::DepthStencilState {
+				format: renderer.depth_texture.format,
+				depth_write_enabled: false,
+				depth_compare: wgpu::CompareFunction::LessEqual,
+				stencil: wgpu::StencilState::default(),
+				bias: wgpu::DepthBiasState::default(),
+			}),
+			multisample: wgpu::MultisampleState {
+				count: renderer.anti_aliasing.sample_count(),
+				mask: !0,
+				alpha_to_coverage_enabled: false,
+			},
+			multiview: None,
+		},
+	));
+}
+
+/// Recorded in the pbr render pass, after the geometry.
+pub fn render_skybox<'a>(renderer: &'a Renderer, render_pass: &mut wgpu::RenderPass<'a>) {
+	let skybox_mat = renderer
+		.meshes
+		.get("skybox")
+		.unwrap()
+		.material
+		.as_ref()
+		.unwrap();
+	render_pass.set_pipeline(skybox_mat.render_pipeline.as_ref().unwrap());
+	render_pass.set_vertex_buffer(0, renderer.fullscreen_vertex_buffer.slice(..));
+	render_pass.set_bind_group(0, &skybox_mat.bind_group[0], &[]);
+	render_pass.draw(0..6, 0..1);
+}
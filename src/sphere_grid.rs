@@ -0,0 +1,38 @@
+use crate::renderer::Instance;
+
+/// Spheres per row and per column of the chart.
+pub const GRID_SIZE: u32 = 7;
+pub const INSTANCE_COUNT: u32 = GRID_SIZE * GRID_SIZE;
+/// Distance between the centers of neighbouring spheres, the sphere has a radius of 1.
+const SPACING: f32 = 2.5;
+/// A perfectly smooth sphere reflects a single texel of the environment.
+const MIN_ROUGHNESS: f32 = 0.05;
+
+/// Spheres whose roughness grows to the right of the default camera and metallic upward.
+pub fn instances() -> Vec<Instance> {
+	let last = (GRID_SIZE - 1) as f32;
+	let mut instances = Vec::with_capacity(INSTANCE_COUNT as usize);
+	for row in 0..GRID_SIZE {
+		for column in 0..GRID_SIZE {
+			let (row, column) = (row as f32, column as f32);
+			// The camera looks toward +Z, so its right is -X
+			let position = uv::Vec3::new(
+				-(column - last / 2.0) * SPACING,
+				(row - last / 2.0) * SPACING,
+				0.0,
+			);
+			instances.push(Instance {
+				model: uv::Mat4::from_translation(position),
+				roughness: (column / last).max(MIN_ROUGHNESS),
+				metallic: row / last,
+			});
+		}
+	}
+	instances
+}
+
+/// Center and radius of the sphere enclosing the chart.
+pub fn bounds() -> (uv::Vec3, f32) {
+	let half_extent = (GRID_SIZE - 1) as f32 / 2.0 * SPACING + 1.0;
+	(uv::Vec3::zero(), half_extent * std::f32::consts::SQRT_2)
+}
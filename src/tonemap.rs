@@ -0,0 +1,559 @@
+use wgpu::util::DeviceExt;
+
+use crate::exposure;
+use crate::renderer::{
+	mesh,
+	post_process::{self, PostProcessPass},
+	texture, Renderer,
+};
+
+/// Resolution of each axis of the baked tonemapping LUT.
+pub const LUT_SIZE: u32 = 32;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Tonemapper {
+	Clamp,
+	Reinhard,
+	ReinhardExtended,
+	Aces,
+	Uchimura,
+	AgX,
+	Uncharted2,
+}
+
+impl Tonemapper {
+	pub const ALL: [Tonemapper; 7] = [
+		Tonemapper::Clamp,
+		Tonemapper::Reinhard,
+		Tonemapper::ReinhardExtended,
+		Tonemapper::Aces,
+		Tonemapper::Uchimura,
+		Tonemapper::AgX,
+		Tonemapper::Uncharted2,
+	];
+
+	pub fn name(self) -> &'static str {
+		match self {
+			Tonemapper::Clamp => "None (clamp)",
+			Tonemapper::Reinhard => "Reinhard",
+			Tonemapper::ReinhardExtended => "Reinhard extended",
+			Tonemapper::Aces => "ACES fitted",
+			Tonemapper::Uchimura => "Uchimura GT",
+			Tonemapper::AgX => "AgX",
+			Tonemapper::Uncharted2 => "Uncharted 2 filmic",
+		}
+	}
+
+	/// Value of `TonemapParam::tonemapper`, matches the TONEMAP_* constants of tonemap_lut.wgsl.
+	pub fn id(self) -> u32 {
+		self as u32
+	}
+
+	pub fn from_id(id: u32) -> Self {
+		Tonemapper::ALL
+			.get(id as usize)
+			.copied()
+			.unwrap_or(Tonemapper::Clamp)
+	}
+}
+
+// Kept 16 bytes aligned and sized so it can be nested in a uniform block
+#[repr(C, align(16))]
+#[derive(Clone, Copy)]
+pub struct TonemapParam {
+	pub tonemapper: u32,
+	pub white_point: f32,
+	// Uchimura GT
+	pub gt_max_brightness: f32,  // P
+	pub gt_contrast: f32,        // a
+	pub gt_linear_start: f32,    // m
+	pub gt_linear_length: f32,   // l
+	pub gt_black_tightness: f32, // c
+	// ACES fitted (Narkowicz)
+	pub aces_a: f32,
+	pub aces_b: f32,
+	pub aces_c: f32,
+	pub aces_d: f32,
+	pub aces_e: f32,
+	// Uncharted 2 filmic (Hable)
+	pub uc2_shoulder_strength: f32, // A
+	pub uc2_linear_strength: f32,   // B
+	pub uc2_linear_angle: f32,      // C
+	pub uc2_toe_strength: f32,      // D
+	pub uc2_toe_numerator: f32,     // E
+	pub uc2_toe_denominator: f32,   // F
+	pub uc2_linear_white: f32,      // W
+	pub uc2_exposure_bias: f32,
+	// AgX look
+	pub agx_offset: f32,
+	pub agx_slope: f32,
+	pub agx_power: f32,
+	pub agx_saturation: f32,
+}
+
+impl Default for TonemapParam {
+	fn default() -> Self {
+		TonemapParam {
+			tonemapper: Tonemapper::Uchimura.id(),
+			white_point: 4.0,
+			gt_max_brightness: 1.0,
+			gt_contrast: 1.0,
+			gt_linear_start: 0.22,
+			gt_linear_length: 0.4,
+			gt_black_tightness: 1.33,
+			aces_a: 2.51,
+			aces_b: 0.03,
+			aces_c: 2.43,
+			aces_d: 0.59,
+			aces_e: 0.14,
+			uc2_shoulder_strength: 0.15,
+			uc2_linear_strength: 0.50,
+			uc2_linear_angle: 0.10,
+			uc2_toe_strength: 0.20,
+			uc2_toe_numerator: 0.02,
+			uc2_toe_denominator: 0.30,
+			uc2_linear_white: 11.2,
+			uc2_exposure_bias: 2.0,
+			agx_offset: 0.0,
+			agx_slope: 1.0,
+			agx_power: 1.0,
+			agx_saturation: 1.0,
+		}
+	}
+}
+
+pub fn init_tonemap_lut(device: &wgpu::Device) -> mesh::Mesh {
+	let mut tonemap_mesh = mesh::Mesh::new::<u32>(device, Some("tonemapMesh"), &vec![], None, None);
+
+	let lut_texture = texture::Texture::new(
+		device,
+		Some("tonemap lut"),
+		LUT_SIZE,
+		LUT_SIZE,
+		LUT_SIZE,
+		1,
+		1,
+		wgpu::TextureDimension::D3,
+		wgpu::TextureFormat::Rgba16Float,
+		wgpu::TextureUsages::STORAGE_BINDING | wgpu::TextureUsages::TEXTURE_BINDING,
+		wgpu::TextureAspect::All,
+	);
+
+	let param_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+		label: Some("tonemap parameters buffer"),
+		contents: unsafe { [TonemapParam::default()].align_to::<u8>().1 },
+		usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+	});
+
+	let mut tonemap_mat = mesh::Material::new(1, 0);
+	tonemap_mat.add_bind_group(
+		device,
+		Some("tonemap lut bind group"),
+		vec![
+			wgpu::BindGroupLayoutEntry {
+				binding: 0,
+				visibility: wgpu::ShaderStages::COMPUTE,
+				ty: wgpu::BindingType::StorageTexture {
+					access: wgpu::StorageTextureAccess::WriteOnly,
+					format: wgpu::TextureFormat::Rgba16Float,
+					view_dimension: wgpu::TextureViewDimension::D3,
+				},
+				count: None,
+			},
+			wgpu::BindGroupLayoutEntry {
+				binding: 1,
+				visibility: wgpu::ShaderStages::COMPUTE,
+				ty: wgpu::BindingType::Buffer {
+					ty: wgpu::BufferBindingType::Uniform,
+					has_dynamic_offset: false,
+					min_binding_size: std::num::NonZeroU64::new(
+						std::mem::size_of::<TonemapParam>() as u64,
+					),
+				},
+				count: None,
+			},
+		],
+		vec![
+			wgpu::BindingResource::TextureView(&lut_texture.view),
+			wgpu::BindingResource::Buffer(wgpu::BufferBinding {
+				buffer: &param_buffer,
+				offset: 0,
+				size: std::num::NonZeroU64::new(std::mem::size_of::<TonemapParam>() as u64),
+			}),
+		],
+	);
+	tonemap_mat.bind_groups_buffers.push(param_buffer);
+	tonemap_mat.bind_groups_textures.push(lut_texture);
+
+	tonemap_mat.set_compute_pipeline(
+		device,
+		Some("tonemap lut compute pipeline"),
+		wgpu::include_wgsl!("../assets/shaders/tonemap_lut.wgsl"),
+	);
+
+	tonemap_mesh.material = Some(tonemap_mat);
+
+	tonemap_mesh
+}
+
+/// Bakes the tonemapping curve described by the current parameters into the LUT.
+pub fn render_tonemap_lut(renderer: &Renderer, encoder: &mut wgpu::CommandEncoder) {
+	let tonemap_mat = renderer
+		.meshes
+		.get("tonemap")
+		.unwrap()
+		.material
+		.as_ref()
+		.unwrap();
+
+	let mut compute_pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+		label: Some("tonemap lut Compute Pass"),
+	});
+	compute_pass.set_pipeline(tonemap_mat.compute_pipeline.as_ref().unwrap());
+	compute_pass.set_bind_group(0, &tonemap_mat.bind_group[0], &[]);
+	compute_pass.dispatch(LUT_SIZE / 4, LUT_SIZE / 4, LUT_SIZE / 4);
+}
+
+/// Applies the exposure and the baked tonemapping curve.
+pub struct TonemapPass {
+	tonemap_mat: mesh::Material,
+}
+
+impl TonemapPass {
+	pub fn new(renderer: &Renderer) -> Self {
+		let mut tonemap_mat = mesh::Material::new(1, 0);
+		tonemap_mat.add_bind_group(
+			&renderer.context.device,
+			Some("tonemap bind group layout"),
+			vec![
+				wgpu::BindGroupLayoutEntry {
+					binding: 0,
+					visibility: wgpu::ShaderStages::FRAGMENT,
+					ty: wgpu::BindingType::Texture {
+						sample_type: wgpu::TextureSampleType::Float { filterable: true },
+						view_dimension: wgpu::TextureViewDimension::D2,
+						multisampled: false,
+					},
+					count: None,
+				},
+				wgpu::BindGroupLayoutEntry {
+					binding: 1,
+					visibility: wgpu::ShaderStages::FRAGMENT,
+					ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+					count: None,
+				},
+				wgpu::BindGroupLayoutEntry {
+					binding: 2,
+					visibility: wgpu::ShaderStages::FRAGMENT,
+					ty: wgpu::BindingType::Texture {
+						sample_type: wgpu::TextureSampleType::Float { filterable: true },
+						view_dimension: wgpu::TextureViewDimension::D3,
+						multisampled: false,
+					},
+					count: None,
+				},
+				wgpu::BindGroupLayoutEntry {
+					binding: 3,
+					visibility: wgpu::ShaderStages::FRAGMENT,
+					ty: wgpu::BindingType::Buffer {
+						ty: wgpu::BufferBindingType::Storage { read_only: true },
+						has_dynamic_offset: false,
+						min_binding_size: std::num::NonZeroU64::new(std::mem::size_of::<
+							exposure::Exposure,
+						>() as u64),
+					},
+					count: None,
+				},
+			],
+			vec![],
+		);
+		tonemap_mat.set_render_pipeline(
+			&renderer.context.device,
+			Some("tonemap pipeline"),
+			wgpu::include_wgsl!("../assets/shaders/tonemap.wgsl"),
+			wgpu::TextureFormat::Rgba16Float,
+			1,
+			None,
+		);
+
+		TonemapPass { tonemap_mat }
+	}
+}
+
+impl PostProcessPass for TonemapPass {
+	fn name(&self) -> &'static str {
+		"Tonemapping"
+	}
+
+	// The LUT does not depend on the screen size
+	fn resize(&mut self, _renderer: &mut Renderer) {}
+
+	fn encode(
+		&mut self,
+		renderer: &Renderer,
+		encoder: &mut wgpu::CommandEncoder,
+		input: &texture::Texture,
+		output: &texture::Texture,
+	) {
+		let lut_view = &renderer
+			.meshes
+			.get("tonemap")
+			.unwrap()
+			.material
+			.as_ref()
+			.unwrap()
+			.bind_groups_textures[0]
+			.view;
+		let bind_group = renderer
+			.context
+			.device
+			.create_bind_group(&wgpu::BindGroupDescriptor {
+				label: Some("tonemap bind group"),
+				layout: &self.tonemap_mat.bind_group_layout[0],
+				entries: &[
+					wgpu::BindGroupEntry {
+						binding: 0,
+						resource: wgpu::BindingResource::TextureView(&input.view),
+					},
+					wgpu::BindGroupEntry {
+						binding: 1,
+						resource: wgpu::BindingResource::Sampler(
+							renderer.hdr_texture.sampler.as_ref().unwrap(),
+						),
+					},
+					wgpu::BindGroupEntry {
+						binding: 2,
+						resource: wgpu::BindingResource::TextureView(lut_view),
+					},
+					wgpu::BindGroupEntry {
+						binding: 3,
+						resource: renderer.exposure_buffer.buffer.as_entire_binding(),
+					},
+				],
+			});
+
+		post_process::encode_fullscreen(
+			renderer,
+			encoder,
+			Some("tonemap Render Pass"),
+			self.tonemap_mat.render_pipeline.as_ref().unwrap(),
+			&bind_group,
+			&output.view,
+		);
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	// Mirrors of the shaper constants in tonemap.wgsl and tonemap_lut.wgsl
+	const LUT_SCALE: f32 = 256.0;
+	const LUT_MAX: f32 = 64.0;
+
+	// Largest allowed difference once gamma encoded
+	const TOLERANCE: f32 = 3.0 / 255.0;
+
+	fn smooth_step(edge0: f32, edge1: f32, x: f32) -> f32 {
+		let t = ((x - edge0) / (edge1 - edge0)).clamp(0.0, 1.0);
+		t * t * (3.0 - 2.0 * t)
+	}
+
+	fn gt_tonemap_point(x: f32, p: &TonemapParam) -> f32 {
+		let m = p.gt_linear_start;
+		let a = p.gt_contrast;
+		let c = p.gt_black_tightness;
+		let big_p = p.gt_max_brightness;
+		let l = p.gt_linear_length;
+		let l0 = ((big_p - m) * l) / a;
+		let s0 = m + l0;
+		let s1 = m + a * l0;
+		let c2 = (a * big_p) / (big_p - s1);
+		let big_l = m + a * (x - m);
+		let t = m * (x / m).powf(c);
+		let s = big_p - (big_p - s1) * (-c2 * (x - s0) / big_p).exp();
+		let w0 = 1.0 - smooth_step(0.0, m, x);
+		let w2 = if x < m + l { 0.0 } else { 1.0 };
+		let w1 = 1.0 - w0 - w2;
+		t * w0 + big_l * w1 + s * w2
+	}
+
+	fn uncharted2_partial(x: f32, p: &TonemapParam) -> f32 {
+		let a = p.uc2_shoulder_strength;
+		let b = p.uc2_linear_strength;
+		let c = p.uc2_linear_angle;
+		let d = p.uc2_toe_strength;
+		let e = p.uc2_toe_numerator;
+		let f = p.uc2_toe_denominator;
+		((x * (a * x + c * b) + d * e) / (x * (a * x + b) + d * f)) - e / f
+	}
+
+	fn agx_contrast_approx(x: f32) -> f32 {
+		let x2 = x * x;
+		let x4 = x2 * x2;
+		15.5 * x4 * x2 - 40.14 * x4 * x + 31.96 * x4 - 6.868 * x2 * x + 0.4298 * x2 + 0.1191 * x
+			- 0.00232
+	}
+
+	#[allow(clippy::excessive_precision)]
+	fn agx(x: uv::Vec3, p: &TonemapParam) -> uv::Vec3 {
+		// Same column major matrices as tonemap_lut.wgsl
+		let agx_mat = uv::Mat3::new(
+			uv::Vec3::new(0.842479062253094, 0.0423282422610123, 0.0423756549057051),
+			uv::Vec3::new(0.0784335999999992, 0.878468636469772, 0.0784336),
+			uv::Vec3::new(0.0792237451477643, 0.0791661274605434, 0.879142973793104),
+		);
+		let agx_mat_inv = uv::Mat3::new(
+			uv::Vec3::new(1.19687900512017, -0.0528968517574562, -0.0529716355144438),
+			uv::Vec3::new(-0.0980208811401368, 1.15190312990417, -0.0980434501171241),
+			uv::Vec3::new(-0.0990297440797205, -0.0989611768448433, 1.15107367264116),
+		);
+		let min_ev = -12.47393f32;
+		let max_ev = 4.026069f32;
+
+		let color = (agx_mat * x).map(|c| {
+			let c = c.max(1.0e-10).log2().clamp(min_ev, max_ev);
+			agx_contrast_approx((c - min_ev) / (max_ev - min_ev))
+		});
+
+		let luma = color.dot(uv::Vec3::new(0.2126, 0.7152, 0.0722));
+		let color = color.map(|c| (c * p.agx_slope + p.agx_offset).max(0.0).powf(p.agx_power));
+		let color =
+			uv::Vec3::broadcast(luma) + p.agx_saturation * (color - uv::Vec3::broadcast(luma));
+
+		(agx_mat_inv * color).map(|c| c.clamp(0.0, 1.0).powf(2.2))
+	}
+
+	// CPU version of tonemap() in tonemap_lut.wgsl
+	fn analytic_tonemap(x: uv::Vec3, p: &TonemapParam) -> uv::Vec3 {
+		match Tonemapper::from_id(p.tonemapper) {
+			Tonemapper::Clamp => x.map(|c| c.clamp(0.0, 1.0)),
+			Tonemapper::Reinhard => x.map(|c| c / (1.0 + c)),
+			Tonemapper::ReinhardExtended => x.map(|c| {
+				let numerator = c * (1.0 + c / (p.white_point * p.white_point));
+				(numerator / (1.0 + c)).clamp(0.0, 1.0)
+			}),
+			Tonemapper::Aces => x.map(|c| {
+				((c * (p.aces_a * c + p.aces_b)) / (c * (p.aces_c * c + p.aces_d) + p.aces_e))
+					.clamp(0.0, 1.0)
+			}),
+			Tonemapper::Uchimura => x.map(|c| gt_tonemap_point(c, p)),
+			Tonemapper::AgX => agx(x, p),
+			Tonemapper::Uncharted2 => x.map(|c| {
+				let white_scale = 1.0 / uncharted2_partial(p.uc2_linear_white, p);
+				(uncharted2_partial(c * p.uc2_exposure_bias, p) * white_scale).clamp(0.0, 1.0)
+			}),
+		}
+	}
+
+	fn lut_shaper(x: f32) -> f32 {
+		((1.0 + LUT_SCALE * x.max(0.0)).log2() / (1.0 + LUT_SCALE * LUT_MAX).log2()).clamp(0.0, 1.0)
+	}
+
+	fn lut_shaper_inverse(t: f32) -> f32 {
+		((t * (1.0 + LUT_SCALE * LUT_MAX).log2()).exp2() - 1.0) / LUT_SCALE
+	}
+
+	// Same content as the compute bake, indexed [z][y][x]
+	fn bake_lut(p: &TonemapParam) -> Vec<uv::Vec3> {
+		let n = LUT_SIZE as usize;
+		let mut lut = Vec::with_capacity(n * n * n);
+		for z in 0..n {
+			for y in 0..n {
+				for x in 0..n {
+					let t = uv::Vec3::new(x as f32, y as f32, z as f32) / (n - 1) as f32;
+					lut.push(analytic_tonemap(t.map(lut_shaper_inverse), p));
+				}
+			}
+		}
+		lut
+	}
+
+	// Trilinear filtering with the texel center mapping of tonemap.wgsl
+	fn sample_lut(lut: &[uv::Vec3], x: uv::Vec3) -> uv::Vec3 {
+		let n = LUT_SIZE as usize;
+		let coord = x.map(|c| lut_shaper(c) * (n - 1) as f32);
+		let base = coord.map(|c| c.floor().min((n - 2) as f32));
+		let frac = coord - base;
+		let (bx, by, bz) = (base.x as usize, base.y as usize, base.z as usize);
+
+		let mut result = uv::Vec3::zero();
+		for corner in 0..8 {
+			let (dx, dy, dz) = (corner & 1, (corner >> 1) & 1, (corner >> 2) & 1);
+			let weight = if dx == 1 { frac.x } else { 1.0 - frac.x }
+				* if dy == 1 { frac.y } else { 1.0 - frac.y }
+				* if dz == 1 { frac.z } else { 1.0 - frac.z };
+			result += lut[((bz + dz) * n + by + dy) * n + bx + dx] * weight;
+		}
+		result
+	}
+
+	fn max_encoded_error(p: &TonemapParam, colors: &[uv::Vec3]) -> f32 {
+		let lut = bake_lut(p);
+		let encode = |c: uv::Vec3| c.map(|v| v.max(0.0).powf(1.0 / 2.2));
+		colors
+			.iter()
+			.map(|&color| {
+				let diff = encode(sample_lut(&lut, color)) - encode(analytic_tonemap(color, p));
+				diff.abs().component_max()
+			})
+			.fold(0.0, f32::max)
+	}
+
+	// Gray ramp plus pseudo random colors, biased toward the dark end like real scenes
+	fn test_colors() -> Vec<uv::Vec3> {
+		let mut colors = (0..2048)
+			.map(|i| uv::Vec3::broadcast((i as f32 / 2048.0).powi(4) * LUT_MAX))
+			.collect::<Vec<_>>();
+		let mut seed = 0x2545f491u32;
+		let mut random = move || {
+			seed ^= seed << 13;
+			seed ^= seed >> 17;
+			seed ^= seed << 5;
+			(seed as f32 / u32::MAX as f32).powi(3) * 16.0
+		};
+		for _ in 0..2048 {
+			colors.push(uv::Vec3::new(random(), random(), random()));
+		}
+		colors
+	}
+
+	#[test]
+	fn lut_matches_analytic_curves() {
+		let colors = test_colors();
+		for tonemapper in Tonemapper::ALL {
+			// Hard clipping has a kink the LUT can only approximate
+			if tonemapper == Tonemapper::Clamp {
+				continue;
+			}
+			let param = TonemapParam {
+				tonemapper: tonemapper.id(),
+				..Default::default()
+			};
+			let error = max_encoded_error(&param, &colors);
+			assert!(
+				error < TOLERANCE,
+				"{} LUT differs from the analytic curve by {}",
+				tonemapper.name(),
+				error
+			);
+		}
+	}
+
+	#[test]
+	fn lut_preserves_black() {
+		for tonemapper in Tonemapper::ALL {
+			let param = TonemapParam {
+				tonemapper: tonemapper.id(),
+				..Default::default()
+			};
+			let black = sample_lut(&bake_lut(&param), uv::Vec3::zero());
+			assert!(
+				black.component_max() < 1.0e-3,
+				"{} maps black to {:?}",
+				tonemapper.name(),
+				black
+			);
+		}
+	}
+}